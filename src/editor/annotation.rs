@@ -0,0 +1,44 @@
+use super::syntax::TokenKind;
+use std::ops::Range;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AnnotationKind {
+    SearchMatch,
+    Selection,
+    MatchingBracket,
+    LineNumber,
+    CursorLineNumber,
+    // `~` and the replacements of the characters like `→` for a tab
+    NonText,
+    Syntax(TokenKind),
+}
+
+// a decoration on a part of a line, in col_idx
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Annotation {
+    pub kind: AnnotationKind,
+    pub line_idx: usize,
+    pub range: Range<usize>,
+}
+
+// a piece of a rendered row sharing the same decoration
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub kind: Option<AnnotationKind>,
+}
+
+impl Span {
+    pub fn plain(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            kind: None,
+        }
+    }
+    pub fn non_text(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            kind: Some(AnnotationKind::NonText),
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use super::buffer::Buffer;
+use super::file_info::FileType;
+use super::position::Position;
+use super::text_object::Text;
+use std::ops::Range;
+
+// pairs matched by `%`, the full-width ones are common in Japanese text
+pub const BRACKETS: [(char, char); 8] = [
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('「', '」'),
+    ('『', '』'),
+    ('（', '）'),
+    ('【', '】'),
+    ('〈', '〉'),
+];
+
+// the text of the buffer with its string and character literals and comments
+// the buffer keeps it until an edit, the literals are only found in Rust code
+pub struct TextScan {
+    pub text: Text,
+    pub literals: Vec<Range<usize>>,
+    pub file_type: Option<FileType>,
+}
+
+impl TextScan {
+    pub fn new(buffer: &Buffer) -> Self {
+        let text = Text::new(buffer);
+        let file_type = buffer.file_info.get_file_type();
+        let literals = if file_type == Some(FileType::Rust) {
+            rust_literal_ranges(&text.content)
+        } else {
+            Vec::new()
+        };
+        Self {
+            text,
+            literals,
+            file_type,
+        }
+    }
+    // the literal which has the byte
+    pub fn literal_at(&self, idx: usize) -> Option<&Range<usize>> {
+        self.literals
+            .get(self.literals.partition_point(|literal| literal.end <= idx))
+            .filter(|literal| literal.start <= idx)
+    }
+}
+
+// the bracket paired with the one at the position
+// in Rust code, brackets in string literals and comments are skipped
+pub fn find_pair(buffer: &Buffer, position: Position) -> Option<Position> {
+    let grapheme = buffer
+        .lines
+        .get(position.line_idx)?
+        .get_fragment_by_col_idx(position.col_idx)?
+        .grapheme();
+    let mut chars = grapheme.chars();
+    let (Some(c), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+    // the bracket under the cursor, and the partner to find
+    let (near, far, forward) = BRACKETS.into_iter().find_map(|(open, close)| {
+        if c == open {
+            Some((open, close, true))
+        } else if c == close {
+            Some((close, open, false))
+        } else {
+            None
+        }
+    })?;
+    let scan = buffer.text_scan();
+    let text = &scan.text;
+    let offset = text.offset(position, buffer);
+    // a bracket in a literal is matched only with the other brackets in the literal
+    let literal = scan.literal_at(offset);
+    let bounds = literal.map_or(0..text.content.len(), Clone::clone);
+    let mut depth: usize = 0;
+    let mut is_partner = |(idx, c): &(usize, char)| {
+        if literal.is_none() && scan.literal_at(*idx).is_some() {
+            return false;
+        }
+        if *c == near {
+            depth = depth.saturating_add(1);
+        } else if *c == far {
+            if depth == 0 {
+                return true;
+            }
+            depth = depth.saturating_sub(1);
+        }
+        false
+    };
+    let found = if forward {
+        let start = offset.saturating_add(near.len_utf8());
+        text.content
+            .get(start..bounds.end)?
+            .char_indices()
+            .map(|(idx, c)| (start.saturating_add(idx), c))
+            .find(&mut is_partner)
+    } else {
+        text.content
+            .get(bounds.start..offset)?
+            .char_indices()
+            .rev()
+            .map(|(idx, c)| (bounds.start.saturating_add(idx), c))
+            .find(&mut is_partner)
+    };
+    found.map(|(idx, _)| text.position(idx, buffer))
+}
+
+// `%` on a character which is not a bracket uses the next bracket in the line
+pub fn find_pair_from(buffer: &Buffer, position: Position) -> Option<Position> {
+    let line = buffer.lines.get(position.line_idx)?;
+    let fragment = line.fragments().iter().find(|fragment| {
+        fragment.left_col_width().saturating_add(fragment.width()) > position.col_idx
+            && BRACKETS.iter().any(|(open, close)| {
+                let mut chars = fragment.grapheme().chars();
+                let c = chars.next();
+                chars.next().is_none() && (c == Some(*open) || c == Some(*close))
+            })
+    })?;
+    find_pair(
+        buffer,
+        Position {
+            line_idx: position.line_idx,
+            col_idx: fragment.left_col_width(),
+        },
+    )
+}
+
+// byte ranges of string and character literals and comments in Rust code, in order
+pub fn rust_literal_ranges(content: &str) -> Vec<Range<usize>> {
+    // the delimiters are ASCII, so scanning bytes never splits them from multibyte characters
+    let bytes = content.as_bytes();
+    let at = |idx: usize| bytes.get(idx).copied();
+    let is_ident = |b: Option<u8>| b.is_some_and(|b| b.is_ascii_alphanumeric() || b == b'_');
+    let mut ranges = Vec::new();
+    let mut idx = 0;
+    while let Some(b) = at(idx) {
+        let next = idx.saturating_add(1);
+        let end = match (b, at(next)) {
+            (b'/', Some(b'/')) => Some(
+                content
+                    .get(idx..)
+                    .and_then(|rest| rest.find('\n'))
+                    .map_or(bytes.len(), |len| idx.saturating_add(len)),
+            ),
+            (b'/', Some(b'*')) => Some(block_comment_end(bytes, idx)),
+            (b'"', _) => Some(string_end(bytes, next)),
+            (b'r', Some(b'"' | b'#'))
+                if !is_ident(idx.checked_sub(1).and_then(at))
+                    || idx.checked_sub(1).and_then(at) == Some(b'b') =>
+            {
+                raw_string_end(bytes, next)
+            }
+            (b'\'', _) => char_literal_end(content, next),
+            _ => None,
+        };
+        if let Some(end) = end {
+            ranges.push(idx..end);
+            idx = end;
+        } else {
+            idx = next;
+        }
+    }
+    ranges
+}
+
+// block comments can be nested in Rust
+fn block_comment_end(bytes: &[u8], start: usize) -> usize {
+    let mut depth: usize = 0;
+    let mut idx = start;
+    while let Some(b) = bytes.get(idx) {
+        let next = bytes.get(idx.saturating_add(1));
+        match (b, next) {
+            (b'/', Some(b'*')) => {
+                depth = depth.saturating_add(1);
+                idx = idx.saturating_add(2);
+            }
+            (b'*', Some(b'/')) => {
+                depth = depth.saturating_sub(1);
+                idx = idx.saturating_add(2);
+                if depth == 0 {
+                    return idx;
+                }
+            }
+            _ => idx = idx.saturating_add(1),
+        }
+    }
+    bytes.len()
+}
+
+// the end of a string, after the closing quote, from the byte after the opening quote
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let mut idx = start;
+    while let Some(b) = bytes.get(idx) {
+        match b {
+            b'\\' => idx = idx.saturating_add(2),
+            b'"' => return idx.saturating_add(1),
+            _ => idx = idx.saturating_add(1),
+        }
+    }
+    bytes.len()
+}
+
+// r"..." or r#"..."#, from the byte after `r`
+fn raw_string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let hashes = bytes
+        .get(start..)?
+        .iter()
+        .take_while(|b| **b == b'#')
+        .count();
+    let quote = start.saturating_add(hashes);
+    if bytes.get(quote) != Some(&b'"') {
+        return None;
+    }
+    let mut idx = quote.saturating_add(1);
+    while let Some(b) = bytes.get(idx) {
+        idx = idx.saturating_add(1);
+        if *b == b'"'
+            && bytes
+                .get(idx..idx.saturating_add(hashes))
+                .is_some_and(|tail| tail.iter().all(|b| *b == b'#'))
+        {
+            return Some(idx.saturating_add(hashes));
+        }
+    }
+    Some(bytes.len())
+}
+
+// 'x' or '\n', from the byte after the quote, a lifetime like 'a is not a literal
+fn char_literal_end(content: &str, start: usize) -> Option<usize> {
+    let rest = content.get(start..)?;
+    if let Some(escaped) = rest.strip_prefix('\\') {
+        // '\'' and '\\' have a quote or a backslash right after the backslash
+        let quote = escaped.get(1..)?.find('\'')?;
+        return Some(start.saturating_add(quote).saturating_add(3));
+    }
+    let c = rest.chars().next()?;
+    let len = c.len_utf8();
+    rest.get(len..)?
+        .starts_with('\'')
+        .then(|| start.saturating_add(len).saturating_add(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::file_info::FileInfo;
+    use super::*;
+
+    #[test]
+    fn test_find_pair() {
+        let buffer = Buffer::from_string("fn f(a: [u8]) {\n    g(「x」);\n}\n");
+        assert_eq!(
+            find_pair(&buffer, Position::new(0, 4)),
+            Some(Position::new(0, 12))
+        );
+        assert_eq!(
+            find_pair(&buffer, Position::new(0, 12)),
+            Some(Position::new(0, 4))
+        );
+        assert_eq!(
+            find_pair(&buffer, Position::new(0, 8)),
+            Some(Position::new(0, 11))
+        );
+        assert_eq!(
+            find_pair(&buffer, Position::new(0, 14)),
+            Some(Position::new(2, 0))
+        );
+        assert_eq!(
+            find_pair(&buffer, Position::new(2, 0)),
+            Some(Position::new(0, 14))
+        );
+        assert_eq!(
+            find_pair(&buffer, Position::new(1, 6)),
+            Some(Position::new(1, 9))
+        );
+        assert_eq!(find_pair(&buffer, Position::new(0, 0)), None);
+        assert_eq!(
+            find_pair_from(&buffer, Position::new(0, 0)),
+            Some(Position::new(0, 12))
+        );
+        assert_eq!(
+            find_pair_from(&buffer, Position::new(1, 0)),
+            Some(Position::new(1, 11))
+        );
+
+        // literals and comments in Rust are skipped
+        let text = "f(\")\", ')', '\\'', /* ) /* ) */ */ r#\")\"#) // )\n'a: { x }\n";
+        let mut buffer = Buffer::from_string(text);
+        assert_eq!(
+            find_pair(&buffer, Position::new(0, 1)),
+            Some(Position::new(0, 3))
+        );
+        buffer.file_info = FileInfo::from("a.rs");
+        assert_eq!(
+            find_pair(&buffer, Position::new(0, 1)),
+            Some(Position::new(0, 40))
+        );
+        assert_eq!(
+            find_pair(&buffer, Position::new(0, 40)),
+            Some(Position::new(0, 1))
+        );
+        // a bracket in a literal is matched in it
+        assert_eq!(find_pair(&buffer, Position::new(0, 37)), None);
+        assert_eq!(find_pair(&buffer, Position::new(0, 3)), None);
+        assert_eq!(
+            find_pair(&buffer, Position::new(1, 4)),
+            Some(Position::new(1, 8))
+        );
+    }
+
+    #[test]
+    fn test_find_pair_in_literal() {
+        let mut buffer = Buffer::from_string("g(\"(\", x)\nh(\"[()]\")\n");
+        buffer.file_info = FileInfo::from("a.rs");
+        assert_eq!(find_pair(&buffer, Position::new(0, 3)), None);
+        assert_eq!(
+            find_pair(&buffer, Position::new(0, 1)),
+            Some(Position::new(0, 8))
+        );
+        assert_eq!(
+            find_pair(&buffer, Position::new(1, 3)),
+            Some(Position::new(1, 6))
+        );
+        assert_eq!(
+            find_pair(&buffer, Position::new(1, 5)),
+            Some(Position::new(1, 4))
+        );
+    }
+
+    #[test]
+    fn test_rust_literal_ranges() {
+        let text = "a\"b\\\"c\" 'x' 'a '\\n' // c\n/* /* */ */ br#\"\"#";
+        let ranges: Vec<&str> = rust_literal_ranges(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                "\"b\\\"c\"",
+                "'x'",
+                "'\\n'",
+                "// c",
+                "/* /* */ */",
+                "r#\"\"#"
+            ]
+        );
+    }
+}
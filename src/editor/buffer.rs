@@ -1,23 +1,46 @@
+use super::annotation::{Annotation, Span};
+use super::bracket::TextScan;
 use super::file_info::FileInfo;
+use super::highlight::Highlighter;
+use super::history::{Edit, History};
 use super::line::Line;
+use super::list_chars::ListChars;
+use super::marks::{adjust_position, PositionList};
 use super::position::Position;
-use super::size::Size;
+use super::text_fragment::TAB_WIDTH;
+use super::wrap::ScreenRow;
+use std::cell::RefCell;
 use std::cmp::min;
+use std::collections::HashMap;
 use std::fs::{read_to_string, File};
 use std::io::{Error, Write};
+use std::ops::Range;
+use std::rc::Rc;
 
 pub struct Buffer {
     pub lines: Vec<Line>,
     pub needs_redraw: bool,
     pub file_info: FileInfo,
-    pub modified_count: usize,
+    history: History,
+    marks: HashMap<char, Position>,
+    // positions of the jumps and the changes, they move with the text like the marks
+    pub jumplist: PositionList,
+    pub changelist: PositionList,
+    highlighter: Highlighter,
+    tab_width: usize,
+    // increased by every edit, for what is computed from the text and kept outside
+    revision: usize,
+    // the whole text with its literals, made again after an edit or for another file type
+    scan: RefCell<Option<Rc<TextScan>>>,
 }
 
 impl Buffer {
     pub fn from_file(path: &str) -> Self {
+        let file_info = FileInfo::from(path);
         Self {
             lines: Self::load(path).unwrap_or_default(),
-            file_info: FileInfo::from(path),
+            highlighter: Highlighter::new(file_info.get_file_type()),
+            file_info,
             ..Self::default()
         }
     }
@@ -37,11 +60,62 @@ impl Buffer {
     pub fn ensure_redraw(&mut self) {
         self.needs_redraw = true;
     }
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+    // the columns of the lines change, so do the highlighted tokens
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        if tab_width == self.tab_width {
+            return;
+        }
+        self.tab_width = tab_width;
+        for line in &mut self.lines {
+            line.set_tab_width(tab_width);
+        }
+        self.invalidate(0);
+        self.ensure_redraw();
+    }
+    // a line of whitespace only, which separates paragraphs and sentences
+    // the lines after the last one are blank
+    pub fn is_blank_line(&self, line_idx: usize) -> bool {
+        self.lines
+            .get(line_idx)
+            .is_none_or(|line| line.content().trim().is_empty())
+    }
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+    pub fn text_scan(&self) -> Rc<TextScan> {
+        let file_type = self.file_info.get_file_type();
+        let mut scan = self.scan.borrow_mut();
+        match scan.as_ref() {
+            Some(scan) if scan.file_type == file_type => Rc::clone(scan),
+            _ => {
+                let new_scan = Rc::new(TextScan::new(self));
+                *scan = Some(Rc::clone(&new_scan));
+                new_scan
+            }
+        }
+    }
+    // the lines from the edited one and the whole text have to be read again
+    fn invalidate(&mut self, line_idx: usize) {
+        self.highlighter.invalidate(line_idx);
+        self.scan.take();
+        self.revision = self.revision.wrapping_add(1);
+    }
+    // a line with the tab width of the buffer
+    pub fn new_line(&self, text: &str) -> Line {
+        Line::with_tab_width(text, self.tab_width)
+    }
     pub fn has_path(&self) -> bool {
         self.file_info.has_path()
     }
     pub fn save_as(&mut self, path: &str) -> Result<(), Error> {
         self.file_info = FileInfo::from(path);
+        self.highlighter = Highlighter::new(self.file_info.get_file_type());
+        // the literals depend on the file type
+        self.invalidate(0);
+        self.ensure_redraw();
         self.save()
     }
     pub fn save(&mut self) -> Result<(), Error> {
@@ -50,37 +124,57 @@ impl Buffer {
             for line in &self.lines {
                 writeln!(file, "{}", line.content())?;
             }
-            self.modified_count = 0;
+            self.history.mark_saved();
             Ok(())
         } else {
-            Err(Error::new(std::io::ErrorKind::Other, "No file path"))
+            Err(Error::other("No file path"))
         }
     }
-    pub fn render<F: Fn(usize, &str) -> Result<(), Error>>(
+    // render the rows of the screen, the rows below the last line are shown as `~`
+    // each row of a line starts with its gutter
+    pub fn render<G, F>(
         &mut self,
-        size: Size,
-        offset: Position,
-        renderer: F,
-    ) -> Result<(), Error> {
+        rows: &[ScreenRow],
+        annotations: &[Annotation],
+        list_chars: Option<&ListChars>,
+        gutter: G,
+        mut renderer: F,
+    ) where
+        G: Fn(&ScreenRow) -> Vec<Span>,
+        F: FnMut(usize, &[Span]),
+    {
         // render function
-        if !self.needs_redraw || size.width == 0 || size.height == 0 {
-            return Ok(());
-        }
-        let top = offset.line_idx;
-        let left = offset.col_idx;
-        let right = left.saturating_add(size.width);
-        for current_row in 0..size.height {
-            let current_line = top.saturating_add(current_row);
-            if let Some(line) = self.lines.get(current_line) {
-                let end = min(right, line.col_width());
-                let str = line.get_str_by_col_range(left..end);
-                renderer(current_row, &str)?;
+        if !self.needs_redraw || rows.is_empty() {
+            return;
+        }
+        for (current_row, row) in rows.iter().enumerate() {
+            if let Some(line) = self.lines.get(row.line_idx) {
+                let line_annotations: Vec<&Annotation> = annotations
+                    .iter()
+                    .filter(|annotation| annotation.line_idx == row.line_idx)
+                    .collect();
+                let mut spans = gutter(row);
+                if !row.prefix.is_empty() {
+                    spans.push(Span::non_text(&row.prefix));
+                }
+                spans.extend(line.get_spans_by_col_range(
+                    row.cols.clone(),
+                    &line_annotations,
+                    list_chars,
+                ));
+                renderer(current_row, &spans);
                 continue;
             }
-            renderer(current_row, "~")?;
+            renderer(current_row, &[Span::non_text("~")]);
         }
         self.needs_redraw = false;
-        Ok(())
+    }
+    // the lines up to `end` are highlighted, from the first line edited since the last time
+    pub fn highlight(&mut self, end: usize) {
+        self.highlighter.update(&self.lines, end);
+    }
+    pub fn syntax_annotations(&self, range: Range<usize>) -> Vec<Annotation> {
+        self.highlighter.annotations(range)
     }
     pub fn get_line_col_width(&self, line_idx: usize) -> usize {
         self.lines.get(line_idx).map_or(0, Line::col_width)
@@ -89,34 +183,137 @@ impl Buffer {
         self.lines.len()
     }
 
-    fn increase_modified_count(&mut self) {
-        self.modified_count = self.modified_count.saturating_add(1);
+    // `'` and `` ` `` are the same mark, the position before the latest jump
+    pub fn set_mark(&mut self, name: char, position: Position) {
+        self.marks.insert(Self::mark_name(name), position);
+    }
+    pub fn get_mark(&self, name: char) -> Option<Position> {
+        self.marks.get(&Self::mark_name(name)).copied()
+    }
+    pub fn remove_mark(&mut self, name: char) {
+        self.marks.remove(&Self::mark_name(name));
+    }
+    fn mark_name(name: char) -> char {
+        if name == '\'' {
+            '`'
+        } else {
+            name
+        }
+    }
+    // the marks on removed lines are deleted, and the marks below them are shifted
+    fn adjust_marks(&mut self, line_idx: usize, removed: usize, added: usize) {
+        if removed == added {
+            return;
+        }
+        self.marks.retain(|_, position| {
+            adjust_position(*position, line_idx, removed, added)
+                .map(|adjusted| *position = adjusted)
+                .is_some()
+        });
+        self.jumplist.adjust(line_idx, removed, added);
+        self.changelist.adjust(line_idx, removed, added);
+    }
+    pub fn modified_count(&self) -> usize {
+        self.history.modified_count()
+    }
+    fn get_contents(&self, line_idx: usize, count: usize) -> Vec<String> {
+        self.lines
+            .iter()
+            .skip(line_idx)
+            .take(count)
+            .map(|line| line.content().to_string())
+            .collect()
+    }
+    // record the change of lines from `at.line_idx`
+    // `before` is taken before the change, and `after_count` lines are taken after the change
+    // the marks follow the change, and `.` is set to where it happened
+    fn record_edit(&mut self, at: Position, before: Vec<String>, after_count: usize) {
+        let line_idx = at.line_idx;
+        let after = self.get_contents(line_idx, after_count);
+        if before == after {
+            return;
+        }
+        self.invalidate(line_idx);
+        self.adjust_marks(line_idx, before.len(), after.len());
+        self.marks.insert('.', at);
+        self.changelist.push_change(at);
+        self.history.push(Edit {
+            line_idx,
+            before,
+            after,
+            at,
+        });
+    }
+    // close the current undo step
+    pub fn commit_history(&mut self) -> bool {
+        self.history.commit()
+    }
+    fn replace_lines(&mut self, line_idx: usize, remove_count: usize, lines: &[String]) {
+        let end = min(
+            line_idx.saturating_add(remove_count),
+            self.get_lines_count(),
+        );
+        let start = min(line_idx, end);
+        let tab_width = self.tab_width;
+        self.lines.splice(
+            start..end,
+            lines
+                .iter()
+                .map(|line| Line::with_tab_width(line, tab_width)),
+        );
+        self.ensure_redraw();
+    }
+    // returns the position where the undone change happened
+    pub fn undo(&mut self) -> Option<Position> {
+        let step = self.history.undo()?;
+        for edit in step.iter().rev() {
+            self.replace_lines(edit.line_idx, edit.after.len(), &edit.before);
+            self.invalidate(edit.line_idx);
+            self.adjust_marks(edit.line_idx, edit.after.len(), edit.before.len());
+        }
+        let at = step.first()?.at;
+        self.marks.insert('.', at);
+        Some(at)
+    }
+    // returns the position where the redone change happened
+    pub fn redo(&mut self) -> Option<Position> {
+        let step = self.history.redo()?;
+        for edit in &step {
+            self.replace_lines(edit.line_idx, edit.before.len(), &edit.after);
+            self.invalidate(edit.line_idx);
+            self.adjust_marks(edit.line_idx, edit.before.len(), edit.after.len());
+        }
+        let at = step.first()?.at;
+        self.marks.insert('.', at);
+        Some(at)
     }
 
-    // TODO add test
     pub fn set_line(&mut self, str: &str, line_idx: usize) {
-        let line = Line::from(str);
+        let at = Position {
+            line_idx,
+            col_idx: 0,
+        };
+        let before = self.get_contents(line_idx, 1);
+        let line = self.new_line(str);
         if line_idx >= self.get_lines_count() {
             self.lines.push(line);
         } else {
             self.lines[line_idx] = line;
         }
-        self.increase_modified_count();
+        self.record_edit(at, before, 1);
         self.ensure_redraw();
     }
     pub fn remove_line(&mut self, line_idx: usize) {
-        self.lines.remove(line_idx);
-        self.increase_modified_count();
-        self.ensure_redraw();
-    }
-    pub fn cutoff_line(&mut self, at: Position) {
-        let Position { line_idx, col_idx } = at;
         if line_idx >= self.get_lines_count() {
             return;
         }
-        // we have a valid line_idx
-        self.lines[line_idx].split_off(col_idx);
-        self.increase_modified_count();
+        let at = Position {
+            line_idx,
+            col_idx: 0,
+        };
+        let before = self.get_contents(line_idx, 1);
+        self.lines.remove(line_idx);
+        self.record_edit(at, before, 0);
         self.ensure_redraw();
     }
     pub fn insert(&mut self, str: &str, at: Position) -> bool {
@@ -127,9 +324,10 @@ impl Buffer {
             return false;
         }
 
+        let before = self.get_contents(line_idx, 1);
         if line_idx == self.get_lines_count() {
             // append a new line
-            self.lines.push(Line::from(str));
+            self.lines.push(self.new_line(str));
         } else if let Some(line) = self.lines.get_mut(line_idx) {
             // insert a new character in an existing line
             line.insert(col_idx, str);
@@ -137,22 +335,205 @@ impl Buffer {
             // maybe dead code, but the compiler doesn't know that
             return false;
         }
-        self.increase_modified_count();
+        self.record_edit(at, before, 1);
         self.ensure_redraw();
         true
     }
+    // insert text which may contain newlines, returns the end of the inserted text
+    pub fn insert_text(&mut self, text: &str, at: Position) -> Position {
+        let Position { line_idx, col_idx } = at;
+        if line_idx > self.get_lines_count() {
+            return at;
+        }
+        let before = self.get_contents(line_idx, 1);
+        let (head, tail) = self.lines.get(line_idx).map_or_else(
+            || (String::default(), String::default()),
+            |line| {
+                (
+                    line.get_content_by_col_range(0..col_idx).to_string(),
+                    line.get_content_by_col_range(col_idx..line.col_width())
+                        .to_string(),
+                )
+            },
+        );
+        let mut new_lines: Vec<String> = format!("{head}{text}")
+            .split('\n')
+            .map(String::from)
+            .collect();
+        let count = new_lines.len();
+        let end = Position {
+            line_idx: line_idx.saturating_add(count).saturating_sub(1),
+            col_idx: new_lines
+                .last()
+                .map_or(0, |last| self.new_line(last).col_width()),
+        };
+        if let Some(last) = new_lines.last_mut() {
+            last.push_str(&tail);
+        }
+        self.replace_lines(line_idx, before.len(), &new_lines);
+        self.record_edit(at, before, count);
+        end
+    }
+    pub fn insert_lines(&mut self, lines: &[String], line_idx: usize) {
+        let line_idx = min(line_idx, self.get_lines_count());
+        self.replace_lines(line_idx, 0, lines);
+        let at = Position {
+            line_idx,
+            col_idx: 0,
+        };
+        self.record_edit(at, Vec::new(), lines.len());
+    }
     pub fn insert_newline(&mut self, at: Position) -> bool {
         let Position { line_idx, col_idx } = at;
+        let before = self.get_contents(line_idx, 1);
         if line_idx >= self.get_lines_count() {
-            self.lines.push(Line::default());
+            self.lines.push(self.new_line(""));
+            self.record_edit(at, before, 1);
         } else {
             // we have a valid line_idx
             let second_half = self.lines[line_idx].split_off(col_idx);
             self.lines.insert(line_idx.saturating_add(1), second_half);
+            self.record_edit(at, before, 2);
         }
-        self.increase_modified_count();
         true
     }
+    // positions below the last line are moved to the end of the last line
+    fn clamp_position(&self, pos: Position) -> Position {
+        let last_line_idx = self.get_lines_count().saturating_sub(1);
+        if pos.line_idx > last_line_idx {
+            Position {
+                line_idx: last_line_idx,
+                col_idx: self.get_line_col_width(last_line_idx),
+            }
+        } else {
+            pos
+        }
+    }
+    // charwise text between the positions, `end` is exclusive
+    pub fn get_text(&self, start: Position, end: Position) -> String {
+        let start = self.clamp_position(start);
+        let end = self.clamp_position(end);
+        if start >= end {
+            return String::default();
+        }
+        let first = &self.lines[start.line_idx];
+        if start.line_idx == end.line_idx {
+            return first
+                .get_content_by_col_range(start.col_idx..end.col_idx)
+                .to_string();
+        }
+        let last = &self.lines[end.line_idx];
+        let mut text = vec![first.get_content_by_col_range(start.col_idx..first.col_width())];
+        text.extend(
+            self.lines[start.line_idx.saturating_add(1)..end.line_idx]
+                .iter()
+                .map(Line::content),
+        );
+        text.push(last.get_content_by_col_range(0..end.col_idx));
+        text.join("\n")
+    }
+    // remove charwise text between the positions and return it, `end` is exclusive
+    pub fn delete_range(&mut self, start: Position, end: Position) -> String {
+        let start = self.clamp_position(start);
+        let end = self.clamp_position(end);
+        if start >= end {
+            return String::default();
+        }
+        let text = self.get_text(start, end);
+        let count = end
+            .line_idx
+            .saturating_sub(start.line_idx)
+            .saturating_add(1);
+        let before = self.get_contents(start.line_idx, count);
+        let first = &self.lines[start.line_idx];
+        let last = &self.lines[end.line_idx];
+        let merged = format!(
+            "{}{}",
+            first.get_content_by_col_range(0..start.col_idx),
+            last.get_content_by_col_range(end.col_idx..last.col_width())
+        );
+        let merged = self.new_line(&merged);
+        self.lines.splice(start.line_idx..=end.line_idx, [merged]);
+        self.record_edit(start, before, 1);
+        self.ensure_redraw();
+        text
+    }
+    pub fn get_lines(&self, range: Range<usize>) -> Vec<String> {
+        self.get_contents(range.start, range.end.saturating_sub(range.start))
+    }
+    // remove whole lines and return their contents
+    pub fn delete_lines(&mut self, range: Range<usize>) -> Vec<String> {
+        let end = min(range.end, self.get_lines_count());
+        let start = min(range.start, end);
+        let before = self.get_lines(start..end);
+        if before.is_empty() {
+            return before;
+        }
+        self.lines.drain(start..end);
+        let at = Position {
+            line_idx: start,
+            col_idx: 0,
+        };
+        self.record_edit(at, before.clone(), 0);
+        self.ensure_redraw();
+        before
+    }
+    // the text of each line within the columns
+    pub fn get_block(&self, lines: Range<usize>, cols: Range<usize>) -> Vec<String> {
+        self.lines
+            .get(lines)
+            .unwrap_or_default()
+            .iter()
+            .map(|line| {
+                let bytes = line.get_byte_range_by_col_range(cols.clone());
+                line.content()[bytes].to_string()
+            })
+            .collect()
+    }
+    // remove the columns from each line and return the removed text
+    pub fn delete_block(&mut self, lines: Range<usize>, cols: Range<usize>) -> Vec<String> {
+        let block = self.get_block(lines.clone(), cols.clone());
+        for line_idx in lines {
+            let Some(line) = self.lines.get(line_idx) else {
+                break;
+            };
+            let bytes = line.get_byte_range_by_col_range(cols.clone());
+            if bytes.is_empty() {
+                continue;
+            }
+            let content = line.content();
+            let rest = format!("{}{}", &content[..bytes.start], &content[bytes.end..]);
+            self.set_line(&rest, line_idx);
+        }
+        block
+    }
+    // insert each text to the successive lines at the same column
+    // short lines are padded with spaces, and missing lines are added
+    pub fn insert_block(&mut self, block: &[String], at: Position) {
+        let block_width = block
+            .iter()
+            .map(|text| self.new_line(text).col_width())
+            .max()
+            .unwrap_or(0);
+        for (line_idx, text) in (at.line_idx..).zip(block) {
+            if line_idx >= self.get_lines_count() {
+                self.insert_lines(&[String::new()], line_idx);
+            }
+            let line = &self.lines[line_idx];
+            let content = line.content();
+            let byte_idx = line.col_idx_to_byte_idx(at.col_idx);
+            let padding = " ".repeat(at.col_idx.saturating_sub(line.col_width()));
+            let (head, tail) = content.split_at(byte_idx);
+            // keep the text after the block aligned
+            let text_padding = if tail.is_empty() {
+                String::new()
+            } else {
+                " ".repeat(block_width.saturating_sub(self.new_line(text).col_width()))
+            };
+            let new_content = format!("{head}{padding}{text}{text_padding}{tail}");
+            self.set_line(&new_content, line_idx);
+        }
+    }
     pub fn remove_char(&mut self, at: Position) -> bool {
         let Position { line_idx, col_idx } = at;
         // out of bounds
@@ -163,16 +544,19 @@ impl Buffer {
         // below here, we have a valid line_idx
         if col_idx < self.lines[line_idx].col_width() {
             // remove a character
+            let before = self.get_contents(line_idx, 1);
             self.lines[line_idx].remove(col_idx, 1);
+            self.record_edit(at, before, 1);
         } else if line_idx < self.get_lines_count().saturating_sub(1) {
             // remove a newline (merge two lines)
+            let before = self.get_contents(line_idx, 2);
             let next_line = self.lines.remove(line_idx.saturating_add(1));
             self.lines[line_idx].append(&next_line);
+            self.record_edit(at, before, 1);
         } else {
             // the last line, the last character
             return false;
         }
-        self.increase_modified_count();
         true
     }
 }
@@ -182,7 +566,14 @@ impl Default for Buffer {
             lines: Vec::new(),
             needs_redraw: true,
             file_info: FileInfo::default(),
-            modified_count: 0,
+            history: History::default(),
+            marks: HashMap::new(),
+            jumplist: PositionList::default(),
+            changelist: PositionList::default(),
+            highlighter: Highlighter::default(),
+            tab_width: TAB_WIDTH,
+            revision: 0,
+            scan: RefCell::new(None),
         }
     }
 }
@@ -190,6 +581,7 @@ impl Default for Buffer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::editor::file_info::FileType;
 
     #[test]
     fn test_load() {
@@ -204,13 +596,11 @@ mod tests {
 
     #[test]
     fn test_insert() {
-        let mut buffer = Buffer::default();
-        buffer.lines = Buffer::gen_lines("this\nis\ntest.\n");
+        let mut buffer = Buffer::from_string("this\nis\ntest.\n");
         buffer.insert("ok", Position::new(0, 1));
         assert_eq!(buffer.lines[0].content(), "tokhis");
 
-        let mut buffer = Buffer::default();
-        buffer.lines = Buffer::gen_lines("qwert");
+        let mut buffer = Buffer::from_string("qwert");
         buffer.insert("\t", Position::new(0, 1));
         assert_eq!(buffer.lines[0].content(), "q\twert");
         buffer.insert("a", Position::new(0, 4));
@@ -219,8 +609,7 @@ mod tests {
 
     #[test]
     fn test_insert_newline() {
-        let mut buffer = Buffer::default();
-        buffer.lines = Buffer::gen_lines("this\nis\ntest.\n");
+        let mut buffer = Buffer::from_string("this\nis\ntest.\n");
         buffer.insert_newline(Position::new(1, 0));
         assert_eq!(buffer.lines.len(), 4);
         assert_eq!(buffer.lines[1].content(), "");
@@ -237,8 +626,7 @@ mod tests {
 
     #[test]
     fn test_remove_char() {
-        let mut buffer = Buffer::default();
-        buffer.lines = Buffer::gen_lines("this\nis\ntest.\n");
+        let mut buffer = Buffer::from_string("this\nis\ntest.\n");
         buffer.remove_char(Position::new(1, 0));
         assert_eq!(buffer.lines.len(), 3);
         assert_eq!(buffer.lines[1].content(), "s");
@@ -246,4 +634,186 @@ mod tests {
         assert_eq!(buffer.lines.len(), 2);
         assert_eq!(buffer.lines[1].content(), "stest.");
     }
+
+    #[test]
+    fn test_text_scan() {
+        let mut buffer = Buffer::from_string("a\nb\n");
+        assert_eq!(buffer.text_scan().text.content, "a\nb");
+        let revision = buffer.revision();
+        // an edit makes the text again, an edit changing nothing keeps it
+        buffer.insert("c", Position::new(1, 1));
+        assert_ne!(buffer.revision(), revision);
+        assert_eq!(buffer.text_scan().text.content, "a\nbc");
+        let revision = buffer.revision();
+        buffer.set_line("a", 0);
+        assert_eq!(buffer.revision(), revision);
+        assert_eq!(buffer.undo(), Some(Position::new(1, 1)));
+        assert_eq!(buffer.text_scan().text.content, "a\nb");
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut buffer = Buffer::from_string("this\nis\ntest.\n");
+        buffer.insert("ok", Position::new(0, 1));
+        buffer.insert_newline(Position::new(1, 1));
+        buffer.commit_history();
+        buffer.remove_char(Position::new(2, 0));
+        buffer.remove_line(0);
+        assert_eq!(buffer.modified_count(), 2);
+        assert_eq!(buffer.lines.len(), 3);
+        assert_eq!(buffer.lines[0].content(), "i");
+        assert_eq!(buffer.lines[1].content(), "");
+
+        assert_eq!(buffer.undo(), Some(Position::new(2, 0)));
+        assert_eq!(buffer.modified_count(), 1);
+        assert_eq!(buffer.lines.len(), 4);
+        assert_eq!(buffer.lines[0].content(), "tokhis");
+        assert_eq!(buffer.lines[2].content(), "s");
+        assert_eq!(buffer.undo(), Some(Position::new(0, 1)));
+        assert_eq!(buffer.modified_count(), 0);
+        assert_eq!(buffer.lines.len(), 3);
+        assert_eq!(buffer.lines[0].content(), "this");
+        assert_eq!(buffer.lines[1].content(), "is");
+        assert_eq!(buffer.undo(), None);
+
+        assert_eq!(buffer.redo(), Some(Position::new(0, 1)));
+        assert_eq!(buffer.lines[0].content(), "tokhis");
+        assert_eq!(buffer.lines[1].content(), "i");
+        assert_eq!(buffer.redo(), Some(Position::new(2, 0)));
+        assert_eq!(buffer.lines[0].content(), "i");
+        assert_eq!(buffer.redo(), None);
+    }
+
+    #[test]
+    fn test_get_text() {
+        let buffer = Buffer::from_string("this\nこんにちは\n\ttest.\n");
+        assert_eq!(
+            buffer.get_text(Position::new(0, 1), Position::new(0, 3)),
+            "hi"
+        );
+        assert_eq!(
+            buffer.get_text(Position::new(0, 3), Position::new(0, 1)),
+            ""
+        );
+        assert_eq!(
+            buffer.get_text(Position::new(0, 2), Position::new(1, 4)),
+            "is\nこん"
+        );
+        assert_eq!(
+            buffer.get_text(Position::new(1, 6), Position::new(2, 5)),
+            "ちは\n\tt"
+        );
+        assert_eq!(
+            buffer.get_text(Position::new(0, 4), Position::new(9, 0)),
+            "\nこんにちは\n\ttest."
+        );
+    }
+
+    #[test]
+    fn test_delete_range() {
+        let mut buffer = Buffer::from_string("this\nこんにちは\n\ttest.\n");
+        assert_eq!(
+            buffer.delete_range(Position::new(1, 4), Position::new(1, 8)),
+            "にち"
+        );
+        assert_eq!(buffer.lines[1].content(), "こんは");
+        assert_eq!(
+            buffer.delete_range(Position::new(0, 2), Position::new(2, 4)),
+            "is\nこんは\n\t"
+        );
+        assert_eq!(buffer.lines.len(), 1);
+        assert_eq!(buffer.lines[0].content(), "thtest.");
+        buffer.undo();
+        assert_eq!(buffer.lines.len(), 3);
+        assert_eq!(buffer.lines[1].content(), "こんにちは");
+    }
+
+    #[test]
+    fn test_delete_lines() {
+        let mut buffer = Buffer::from_string("this\nis\ntest.\n");
+        assert_eq!(buffer.delete_lines(1..5), vec!["is", "test."]);
+        assert_eq!(buffer.lines.len(), 1);
+        assert!(buffer.delete_lines(1..2).is_empty());
+        assert_eq!(buffer.modified_count(), 1);
+    }
+
+    #[test]
+    fn test_block() {
+        let mut buffer = Buffer::from_string("あいう\nabcdef\na\tb\n");
+        assert_eq!(buffer.get_block(0..3, 1..3), vec!["あい", "bc", "\t"]);
+        assert_eq!(buffer.delete_block(0..3, 2..4), vec!["い", "cd", "\t"]);
+        assert_eq!(buffer.lines[0].content(), "あう");
+        assert_eq!(buffer.lines[1].content(), "abef");
+        assert_eq!(buffer.lines[2].content(), "ab");
+        buffer.insert_block(&["x".to_string(), "yz".to_string()], Position::new(2, 4));
+        assert_eq!(buffer.lines[2].content(), "ab  x");
+        assert_eq!(buffer.lines[3].content(), "    yz");
+        buffer.insert_block(&["1".to_string(), "23".to_string()], Position::new(0, 2));
+        assert_eq!(buffer.lines[0].content(), "あ1 う");
+        assert_eq!(buffer.lines[1].content(), "ab23ef");
+    }
+
+    #[test]
+    fn test_insert_text() {
+        let mut buffer = Buffer::from_string("this\nこんにちは\n");
+        let end = buffer.insert_text("ok", Position::new(1, 4));
+        assert_eq!(buffer.lines[1].content(), "こんokにちは");
+        assert_eq!(end, Position::new(1, 6));
+        let end = buffer.insert_text("a\n\tb\nc", Position::new(0, 2));
+        assert_eq!(buffer.lines.len(), 4);
+        assert_eq!(buffer.lines[0].content(), "tha");
+        assert_eq!(buffer.lines[1].content(), "\tb");
+        assert_eq!(buffer.lines[2].content(), "cis");
+        assert_eq!(end, Position::new(2, 1));
+        let end = buffer.insert_text("end", Position::new(4, 0));
+        assert_eq!(buffer.lines[4].content(), "end");
+        assert_eq!(end, Position::new(4, 3));
+        buffer.insert_lines(&["x".to_string(), "y".to_string()], 1);
+        assert_eq!(buffer.lines.len(), 7);
+        assert_eq!(buffer.lines[1].content(), "x");
+        assert_eq!(buffer.lines[3].content(), "\tb");
+        buffer.undo();
+        assert_eq!(buffer.lines[0].content(), "this");
+        assert_eq!(buffer.lines.len(), 2);
+    }
+
+    #[test]
+    fn test_marks_follow_text() {
+        let mut buffer = Buffer::from_string("a\nb\nc\nd\n");
+        buffer.set_mark('a', Position::new(2, 0));
+        buffer.set_mark('b', Position::new(1, 0));
+        buffer.insert_lines(&["x".to_string(), "y".to_string()], 0);
+        assert_eq!(buffer.get_mark('a'), Some(Position::new(4, 0)));
+        buffer.delete_lines(3..4);
+        assert_eq!(buffer.get_mark('a'), Some(Position::new(3, 0)));
+        assert_eq!(buffer.get_mark('b'), None);
+        assert_eq!(buffer.get_mark('.'), Some(Position::new(3, 0)));
+        buffer.commit_history();
+        buffer.insert_newline(Position::new(0, 1));
+        assert_eq!(buffer.get_mark('a'), Some(Position::new(4, 0)));
+        buffer.undo();
+        assert_eq!(buffer.get_mark('a'), Some(Position::new(3, 0)));
+        // `'` and `` ` `` are the same mark
+        buffer.set_mark('\'', Position::new(1, 0));
+        assert_eq!(buffer.get_mark('`'), Some(Position::new(1, 0)));
+    }
+
+    #[test]
+    fn test_highlight() {
+        let mut buffer = Buffer::from_string("fn a() {}\nlet b;\n");
+        buffer.highlighter = Highlighter::new(Some(FileType::Rust));
+        buffer.highlight(2);
+        assert_eq!(buffer.syntax_annotations(0..2).len(), 3);
+        // opening a comment changes the next line
+        buffer.insert("/*", Position::new(0, 0));
+        assert_eq!(buffer.syntax_annotations(0..2).len(), 0);
+        buffer.highlight(2);
+        let annotations = buffer.syntax_annotations(0..2);
+        assert_eq!(annotations.len(), 2);
+        assert_eq!(annotations[1].range, 0..6);
+        buffer.commit_history();
+        buffer.undo();
+        buffer.highlight(2);
+        assert_eq!(buffer.syntax_annotations(0..2).len(), 3);
+    }
 }
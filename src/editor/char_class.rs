@@ -0,0 +1,88 @@
+// the kind of a character for word motions, a change of the class is a word boundary
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CharClass {
+    Whitespace,
+    // an empty line is a word by itself
+    EmptyLine,
+    Punctuation,
+    // ASCII letters, digits and underscore, and letters of other alphabets
+    Word,
+    Hiragana,
+    Katakana,
+    Kanji,
+    // ０-９, Ａ-Ｚ, ａ-ｚ
+    FullWidthAlnum,
+    Emoji,
+    // any non-blank character, for WORD motions
+    NonBlank,
+}
+
+impl CharClass {
+    pub fn of(grapheme: &str, bigword: bool) -> Self {
+        let Some(c) = grapheme.chars().next() else {
+            return CharClass::Whitespace;
+        };
+        if c.is_whitespace() {
+            return CharClass::Whitespace;
+        }
+        if bigword {
+            return CharClass::NonBlank;
+        }
+        // variation selector 16 or zero width joiner makes a pictograph an emoji
+        if grapheme.contains(['\u{fe0f}', '\u{200d}']) {
+            return CharClass::Emoji;
+        }
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => CharClass::Word,
+            '\u{3005}' | '\u{3007}' | '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' => {
+                CharClass::Kanji
+            }
+            '\u{f900}'..='\u{faff}' | '\u{20000}'..='\u{2ffff}' => CharClass::Kanji,
+            '\u{3041}'..='\u{309f}' => CharClass::Hiragana,
+            '\u{30a0}'..='\u{30ff}' | '\u{31f0}'..='\u{31ff}' | '\u{ff66}'..='\u{ff9f}' => {
+                CharClass::Katakana
+            }
+            '\u{ff10}'..='\u{ff19}' | '\u{ff21}'..='\u{ff3a}' | '\u{ff41}'..='\u{ff5a}' => {
+                CharClass::FullWidthAlnum
+            }
+            '\u{1f1e6}'..='\u{1f1ff}' | '\u{1f300}'..='\u{1faff}' | '\u{2600}'..='\u{27bf}' => {
+                CharClass::Emoji
+            }
+            _ if c.is_alphanumeric() => CharClass::Word,
+            _ => CharClass::Punctuation,
+        }
+    }
+    // whether the motions skip over it
+    pub fn is_blank(self) -> bool {
+        self == CharClass::Whitespace
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_of() {
+        assert_eq!(CharClass::of(" ", false), CharClass::Whitespace);
+        assert_eq!(CharClass::of("\u{3000}", false), CharClass::Whitespace);
+        assert_eq!(CharClass::of("_", false), CharClass::Word);
+        assert_eq!(CharClass::of("é", false), CharClass::Word);
+        assert_eq!(CharClass::of("-", false), CharClass::Punctuation);
+        assert_eq!(CharClass::of("。", false), CharClass::Punctuation);
+        assert_eq!(CharClass::of("「", false), CharClass::Punctuation);
+        assert_eq!(CharClass::of("の", false), CharClass::Hiragana);
+        assert_eq!(CharClass::of("カ", false), CharClass::Katakana);
+        assert_eq!(CharClass::of("ー", false), CharClass::Katakana);
+        assert_eq!(CharClass::of("ｶ", false), CharClass::Katakana);
+        assert_eq!(CharClass::of("漢", false), CharClass::Kanji);
+        assert_eq!(CharClass::of("々", false), CharClass::Kanji);
+        assert_eq!(CharClass::of("Ａ", false), CharClass::FullWidthAlnum);
+        assert_eq!(CharClass::of("１", false), CharClass::FullWidthAlnum);
+        assert_eq!(CharClass::of("😀", false), CharClass::Emoji);
+        assert_eq!(CharClass::of("🇯🇵", false), CharClass::Emoji);
+        assert_eq!(CharClass::of("❤️", false), CharClass::Emoji);
+        assert_eq!(CharClass::of("漢", true), CharClass::NonBlank);
+        assert_eq!(CharClass::of("\t", true), CharClass::Whitespace);
+    }
+}
@@ -1,6 +1,6 @@
-use super::terminal::Terminal;
+use super::screen::Screen;
+use super::theme::HighlightGroup;
 use super::Mode;
-use std::io::Error;
 use unicode_width::UnicodeWidthStr;
 
 pub struct CommandBar {
@@ -11,9 +11,8 @@ pub struct CommandBar {
     pub mode: Mode,
 }
 impl CommandBar {
-    pub fn new(mode: Mode) -> Self {
+    pub fn new(mode: Mode, prompt: &str) -> Self {
         debug_assert!(mode == Mode::Command || mode == Mode::Search);
-        let prompt = if mode == Mode::Command { ":" } else { "/" };
         Self {
             prompt: prompt.to_string(),
             value: String::default(),
@@ -38,12 +37,14 @@ impl CommandBar {
     pub fn text(&self) -> String {
         format!("{}{}", &self.prompt, &self.value)
     }
-    pub fn render(&mut self, bottom_line: usize) -> Result<(), Error> {
+    pub fn ensure_redraw(&mut self) {
+        self.needs_redraw = true;
+    }
+    pub fn render(&mut self, screen: &mut Screen, bottom_line: usize) {
         if !self.needs_redraw {
-            return Ok(());
+            return;
         }
-        Terminal::print_row(bottom_line, &self.text())?;
+        screen.put_row(bottom_line, &self.text(), HighlightGroup::Normal);
         self.needs_redraw = false;
-        Ok(())
     }
 }
@@ -1,9 +1,68 @@
 use super::buffer::Buffer;
+use super::char_class::CharClass;
 use super::line::Line;
 use super::position::Position;
 use super::text_fragment::TextFragment;
 use std::{cmp::min, fmt};
 
+// the character to find by `f`, `F`, `t` or `T`
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct CharSearch {
+    pub target: char,
+    pub forward: bool,
+    // `t` and `T` stop right before the character
+    pub till: bool,
+    // set by `;` and `,`, so that `t` does not stop before the same character again
+    pub repeated: bool,
+}
+
+impl CharSearch {
+    // keys like `fx`, `t<SPACE>`
+    pub fn from_keys(keys: &str) -> Option<Self> {
+        let (forward, till) = match keys.chars().next()? {
+            'f' => (true, false),
+            'F' => (false, false),
+            't' => (true, true),
+            'T' => (false, true),
+            _ => return None,
+        };
+        let rest = keys.get(1..)?;
+        let mut chars = rest.chars();
+        let target = match (chars.next(), chars.next()) {
+            (Some(c), None) => c,
+            _ => match rest {
+                "<SPACE>" => ' ',
+                "<TAB>" => '\t',
+                "<LT>" => '<',
+                _ => return None,
+            },
+        };
+        Some(Self {
+            target,
+            forward,
+            till,
+            repeated: false,
+        })
+    }
+    pub fn again(self) -> Self {
+        Self {
+            repeated: true,
+            ..self
+        }
+    }
+    pub fn reverse(self) -> Self {
+        Self {
+            forward: !self.forward,
+            ..self
+        }
+    }
+    // only a grapheme of the target character alone, not one made of it and other characters
+    fn matches(self, grapheme: &str) -> bool {
+        let mut chars = grapheme.chars();
+        chars.next() == Some(self.target) && chars.next().is_none()
+    }
+}
+
 #[derive(Eq, PartialEq, Copy, Clone, Default)]
 pub struct Cursor {
     line_idx: usize,
@@ -37,7 +96,6 @@ impl Cursor {
     //     self.set_line_idx(self.line_idx, current_buffer);
     //     self.set_col_idx(self.col_idx, current_buffer);
     // }
-    #[cfg(test)]
     pub fn set_position(&mut self, position: Position, current_buffer: &Buffer) {
         self.set_line_idx(position.line_idx, current_buffer);
         self.set_col_idx(position.col_idx, current_buffer);
@@ -134,6 +192,264 @@ impl Cursor {
         self.set_line_idx(self.line_idx.saturating_add(step), current_buffer);
         self.snap_col_idx(current_buffer);
     }
+    // `w`, the start of the next word
+    pub fn move_next_word_start(&mut self, bigword: bool, current_buffer: &Buffer) {
+        let mut position = self.position();
+        let class = Self::class_at(position, bigword, current_buffer);
+        while let Some(next) = Self::next_char_position(position, current_buffer) {
+            position = next;
+            if class == CharClass::EmptyLine
+                || Self::class_at(next, bigword, current_buffer) != class
+            {
+                break;
+            }
+        }
+        while Self::class_at(position, bigword, current_buffer).is_blank() {
+            let Some(next) = Self::next_char_position(position, current_buffer) else {
+                break;
+            };
+            position = next;
+        }
+        self.set_position(position, current_buffer);
+    }
+    // back on the last character from the end of the buffer, where `w` stops on the last word
+    pub fn move_off_buffer_end(&mut self, current_buffer: &Buffer) {
+        let position = self.position();
+        let is_last_line = position.line_idx.saturating_add(1) >= current_buffer.get_lines_count();
+        if is_last_line && position.col_idx >= current_buffer.get_line_col_width(position.line_idx)
+        {
+            if let Some(prev) = Self::prev_char_position(position, current_buffer)
+                .filter(|prev| prev.line_idx == position.line_idx)
+            {
+                self.set_position(prev, current_buffer);
+            }
+        }
+    }
+    // `e`, the end of the next word
+    pub fn move_next_word_end(&mut self, bigword: bool, current_buffer: &Buffer) {
+        let Some(mut position) = Self::next_char_position(self.position(), current_buffer) else {
+            return;
+        };
+        // empty lines are skipped unlike `w`
+        while matches!(
+            Self::class_at(position, bigword, current_buffer),
+            CharClass::Whitespace | CharClass::EmptyLine
+        ) {
+            let Some(next) = Self::next_char_position(position, current_buffer) else {
+                break;
+            };
+            position = next;
+        }
+        self.set_position(position, current_buffer);
+        self.move_current_word_end(bigword, current_buffer);
+    }
+    // the end of the word under the cursor, `cw` changes up to here
+    pub fn move_current_word_end(&mut self, bigword: bool, current_buffer: &Buffer) {
+        let mut position = self.position();
+        let class = Self::class_at(position, bigword, current_buffer);
+        if class.is_blank() || class == CharClass::EmptyLine {
+            return;
+        }
+        while let Some(next) = Self::next_char_position(position, current_buffer) {
+            if Self::class_at(next, bigword, current_buffer) != class {
+                break;
+            }
+            position = next;
+        }
+        self.set_position(position, current_buffer);
+    }
+    // `b`, the start of the previous word
+    pub fn move_prev_word_start(&mut self, bigword: bool, current_buffer: &Buffer) {
+        let Some(mut position) = Self::prev_char_position(self.position(), current_buffer) else {
+            return;
+        };
+        while Self::class_at(position, bigword, current_buffer).is_blank() {
+            let Some(prev) = Self::prev_char_position(position, current_buffer) else {
+                break;
+            };
+            position = prev;
+        }
+        let class = Self::class_at(position, bigword, current_buffer);
+        if class != CharClass::EmptyLine {
+            while let Some(prev) = Self::prev_char_position(position, current_buffer) {
+                if Self::class_at(prev, bigword, current_buffer) != class {
+                    break;
+                }
+                position = prev;
+            }
+        }
+        self.set_position(position, current_buffer);
+    }
+    // `ge`, the end of the previous word
+    pub fn move_prev_word_end(&mut self, bigword: bool, current_buffer: &Buffer) {
+        let mut position = self.position();
+        let class = Self::class_at(position, bigword, current_buffer);
+        while let Some(prev) = Self::prev_char_position(position, current_buffer) {
+            position = prev;
+            if class == CharClass::EmptyLine
+                || Self::class_at(prev, bigword, current_buffer) != class
+            {
+                break;
+            }
+        }
+        while Self::class_at(position, bigword, current_buffer).is_blank() {
+            let Some(prev) = Self::prev_char_position(position, current_buffer) else {
+                break;
+            };
+            position = prev;
+        }
+        self.set_position(position, current_buffer);
+    }
+    // `}`, the next blank line after a paragraph, or the end of the last line
+    pub fn move_next_paragraph(&mut self, current_buffer: &Buffer) {
+        let lines_count = current_buffer.get_lines_count();
+        let mut line_idx = self.line_idx;
+        while line_idx < lines_count && current_buffer.is_blank_line(line_idx) {
+            line_idx = line_idx.saturating_add(1);
+        }
+        while line_idx < lines_count && !current_buffer.is_blank_line(line_idx) {
+            line_idx = line_idx.saturating_add(1);
+        }
+        if line_idx < lines_count {
+            self.set_position(
+                Position {
+                    line_idx,
+                    col_idx: 0,
+                },
+                current_buffer,
+            );
+        } else {
+            self.set_line_idx(lines_count.saturating_sub(1), current_buffer);
+            self.move_last_char(current_buffer);
+        }
+    }
+    // `{`, the previous blank line before a paragraph, or the start of the first line
+    pub fn move_prev_paragraph(&mut self, current_buffer: &Buffer) {
+        let mut line_idx = self.line_idx;
+        while line_idx > 0 && current_buffer.is_blank_line(line_idx) {
+            line_idx = line_idx.saturating_sub(1);
+        }
+        while line_idx > 0 && !current_buffer.is_blank_line(line_idx) {
+            line_idx = line_idx.saturating_sub(1);
+        }
+        self.set_position(
+            Position {
+                line_idx,
+                col_idx: 0,
+            },
+            current_buffer,
+        );
+    }
+    // `f`, `F`, `t` and `T` in the current line
+    // the cursor stays if the character is not found count times, returns false then
+    pub fn move_to_char(
+        &mut self,
+        search: CharSearch,
+        count: usize,
+        current_buffer: &Buffer,
+    ) -> bool {
+        let Some(line) = current_buffer.lines.get(self.line_idx) else {
+            return false;
+        };
+        let fragments = line.fragments();
+        let current = fragments
+            .iter()
+            .position(|fragment| fragment.left_col_width() >= self.col_idx)
+            .unwrap_or(fragments.len());
+        // the character right next to the cursor is skipped when `t` is repeated
+        let skip = usize::from(search.till && search.repeated);
+        let nth = count.saturating_sub(1);
+        let found = if search.forward {
+            fragments
+                .iter()
+                .enumerate()
+                .skip(current.saturating_add(1).saturating_add(skip))
+                .filter(|(_, fragment)| search.matches(fragment.grapheme()))
+                .nth(nth)
+                .map(|(idx, _)| {
+                    if search.till {
+                        idx.saturating_sub(1)
+                    } else {
+                        idx
+                    }
+                })
+        } else {
+            fragments
+                .iter()
+                .enumerate()
+                .take(current.saturating_sub(skip))
+                .rev()
+                .filter(|(_, fragment)| search.matches(fragment.grapheme()))
+                .nth(nth)
+                .map(|(idx, _)| {
+                    if search.till {
+                        idx.saturating_add(1)
+                    } else {
+                        idx
+                    }
+                })
+        };
+        let Some(fragment) = found.and_then(|idx| fragments.get(idx)) else {
+            return false;
+        };
+        self.set_col_idx(fragment.left_col_width(), current_buffer);
+        true
+    }
+    // the class of the character at the position, the end of a line is a whitespace
+    fn class_at(position: Position, bigword: bool, current_buffer: &Buffer) -> CharClass {
+        let Some(line) = current_buffer.lines.get(position.line_idx) else {
+            return CharClass::EmptyLine;
+        };
+        if line.col_width() == 0 {
+            return CharClass::EmptyLine;
+        }
+        line.get_fragment_by_col_idx(position.col_idx)
+            .map_or(CharClass::Whitespace, |fragment| {
+                CharClass::of(fragment.grapheme(), bigword)
+            })
+    }
+    // the next character, the end of each line is a position between lines
+    fn next_char_position(position: Position, current_buffer: &Buffer) -> Option<Position> {
+        let line = current_buffer.lines.get(position.line_idx)?;
+        if position.col_idx < line.col_width() {
+            let width = line
+                .get_fragment_by_col_idx(position.col_idx)
+                .map_or(1, TextFragment::width);
+            Some(Position {
+                line_idx: position.line_idx,
+                col_idx: position.col_idx.saturating_add(width),
+            })
+        } else if position.line_idx.saturating_add(1) < current_buffer.get_lines_count() {
+            Some(Position {
+                line_idx: position.line_idx.saturating_add(1),
+                col_idx: 0,
+            })
+        } else {
+            None
+        }
+    }
+    fn prev_char_position(position: Position, current_buffer: &Buffer) -> Option<Position> {
+        if position.col_idx > 0 {
+            let col_idx = position.col_idx.saturating_sub(1);
+            let col_idx = current_buffer
+                .lines
+                .get(position.line_idx)
+                .and_then(|line| line.get_fragment_by_col_idx(col_idx))
+                .map_or(col_idx, TextFragment::left_col_width);
+            Some(Position {
+                line_idx: position.line_idx,
+                col_idx,
+            })
+        } else if position.line_idx > 0 {
+            let line_idx = position.line_idx.saturating_sub(1);
+            Some(Position {
+                line_idx,
+                col_idx: current_buffer.get_line_col_width(line_idx),
+            })
+        } else {
+            None
+        }
+    }
 }
 
 impl fmt::Display for Cursor {
@@ -274,4 +590,122 @@ mod tests {
         cursor.move_next_line(1, &buffer);
         assert_eq!(cursor.position(), Position::new(3, 0));
     }
+
+    #[test]
+    fn test_move_word() {
+        let buffer = Buffer::from_string("foo.bar  baz\n\n  qux\n");
+        let mut cursor = Cursor::default();
+        let mut positions = vec![];
+        for _ in 0..6 {
+            cursor.move_next_word_start(false, &buffer);
+            positions.push(cursor.position());
+        }
+        assert_eq!(
+            positions,
+            vec![
+                Position::new(0, 3),
+                Position::new(0, 4),
+                Position::new(0, 9),
+                Position::new(1, 0), // an empty line is a word
+                Position::new(2, 2),
+                Position::new(2, 5), // the end of the buffer
+            ]
+        );
+        for expected in [(2, 2), (1, 0), (0, 9), (0, 4), (0, 3), (0, 0), (0, 0)] {
+            cursor.move_prev_word_start(false, &buffer);
+            assert_eq!(cursor.position(), Position::new(expected.0, expected.1));
+        }
+        for expected in [(0, 2), (0, 3), (0, 6), (0, 11), (2, 4)] {
+            cursor.move_next_word_end(false, &buffer);
+            assert_eq!(cursor.position(), Position::new(expected.0, expected.1));
+        }
+        for expected in [(1, 0), (0, 11), (0, 6), (0, 3), (0, 2)] {
+            cursor.move_prev_word_end(false, &buffer);
+            assert_eq!(cursor.position(), Position::new(expected.0, expected.1));
+        }
+
+        // WORD motions only stop at whitespace
+        cursor.set_position(Position::default(), &buffer);
+        cursor.move_next_word_start(true, &buffer);
+        assert_eq!(cursor.position(), Position::new(0, 9));
+        cursor.move_next_word_end(true, &buffer);
+        assert_eq!(cursor.position(), Position::new(0, 11));
+        cursor.move_prev_word_start(true, &buffer);
+        assert_eq!(cursor.position(), Position::new(0, 9));
+        cursor.move_prev_word_start(true, &buffer);
+        assert_eq!(cursor.position(), Position::new(0, 0));
+
+        // japanese prose without spaces is split by the character class
+        let buffer = Buffer::from_string("私はカタカナとＡＢＣを使う。😀ok\n");
+        cursor.set_position(Position::default(), &buffer);
+        let mut cols = vec![];
+        for _ in 0..10 {
+            cursor.move_next_word_start(false, &buffer);
+            cols.push(cursor.col_idx());
+        }
+        // 私|は|カタカナ|と|ＡＢＣ|を|使|う|。|😀|ok
+        assert_eq!(cols, vec![2, 4, 12, 14, 20, 22, 24, 26, 28, 30]);
+        cursor.move_prev_word_start(false, &buffer);
+        assert_eq!(cursor.col_idx(), 28);
+        cursor.set_position(Position::new(0, 4), &buffer);
+        cursor.move_next_word_end(false, &buffer);
+        assert_eq!(cursor.col_idx(), 10);
+        cursor.move_current_word_end(false, &buffer);
+        assert_eq!(cursor.col_idx(), 10);
+    }
+
+    #[test]
+    fn test_move_to_char() {
+        let buffer = Buffer::from_string("a,b,日本,👨\u{200d}👩\u{200d}👧,e\u{301},👨e\n");
+        let mut cursor = Cursor::default();
+        let f = CharSearch::from_keys("f,").unwrap();
+        assert!(cursor.move_to_char(f, 1, &buffer));
+        assert_eq!(cursor.col_idx(), 1);
+        assert!(cursor.move_to_char(f, 2, &buffer));
+        assert_eq!(cursor.col_idx(), 8);
+        assert!(!cursor.move_to_char(f, 3, &buffer));
+        assert_eq!(cursor.col_idx(), 8);
+        // a ZWJ sequence or a decomposed character is not matched by its first character
+        let f = CharSearch::from_keys("f👨").unwrap();
+        assert!(cursor.move_to_char(f, 1, &buffer));
+        assert_eq!(cursor.col_idx(), 14);
+        cursor.set_col_idx(8, &buffer);
+        let f = CharSearch::from_keys("fe").unwrap();
+        assert!(cursor.move_to_char(f, 1, &buffer));
+        assert_eq!(cursor.col_idx(), 16);
+        cursor.set_col_idx(8, &buffer);
+        let t = CharSearch::from_keys("T本").unwrap();
+        assert!(cursor.move_to_char(t, 1, &buffer));
+        assert_eq!(cursor.col_idx(), 8);
+        // `t` stops before the next one when repeated
+        let t = CharSearch::from_keys("t,").unwrap();
+        cursor.set_col_idx(0, &buffer);
+        assert!(cursor.move_to_char(t, 1, &buffer));
+        assert_eq!(cursor.col_idx(), 0);
+        assert!(cursor.move_to_char(t.again(), 1, &buffer));
+        assert_eq!(cursor.col_idx(), 2);
+        assert!(!cursor.move_to_char(t.again().reverse(), 1, &buffer));
+        assert_eq!(cursor.col_idx(), 2);
+        let search = CharSearch::from_keys("F<SPACE>").unwrap();
+        assert_eq!(search.target, ' ');
+        assert!(!search.forward);
+    }
+
+    #[test]
+    fn test_move_paragraph() {
+        let buffer = Buffer::from_string("a\nb\n\n  \nc\n\nd\n");
+        let mut cursor = Cursor::default();
+        cursor.move_next_paragraph(&buffer);
+        assert_eq!(cursor.position(), Position::new(2, 0));
+        cursor.move_next_paragraph(&buffer);
+        assert_eq!(cursor.position(), Position::new(5, 0));
+        cursor.move_next_paragraph(&buffer);
+        assert_eq!(cursor.position(), Position::new(6, 1));
+        cursor.move_prev_paragraph(&buffer);
+        assert_eq!(cursor.position(), Position::new(5, 0));
+        cursor.move_prev_paragraph(&buffer);
+        assert_eq!(cursor.position(), Position::new(3, 0));
+        cursor.move_prev_paragraph(&buffer);
+        assert_eq!(cursor.position(), Position::new(0, 0));
+    }
 }
@@ -0,0 +1,491 @@
+use super::buffer::Buffer;
+use super::indent::shift_line;
+use super::options::Options;
+use super::position::Position;
+use super::search::{find_match, SearchDirection};
+use regex::Regex;
+use std::ops::Range;
+
+// the full name, the shortest abbreviation, and whether a range is accepted
+const COMMANDS: [(&str, &str, bool); 16] = [
+    ("colorscheme", "colo", false),
+    ("copy", "co", true),
+    ("delete", "d", true),
+    ("echo", "ec", false),
+    ("join", "j", true),
+    ("move", "m", true),
+    ("normal", "norm", true),
+    ("quit", "q", false),
+    ("set", "se", false),
+    ("substitute", "s", true),
+    ("t", "t", true),
+    ("wq", "wq", false),
+    ("write", "w", false),
+    ("yank", "y", true),
+    (">", ">", true),
+    ("<", "<", true),
+];
+
+// what the addresses refer to
+pub struct AddressContext<'a> {
+    pub buffer: &'a Buffer,
+    pub current_line_idx: usize,
+    pub last_search: Option<&'a str>,
+}
+
+// a parsed command line like `:'a,.+2d x`
+#[derive(Debug, Eq, PartialEq)]
+pub struct ExCommand {
+    // None if the command line has no range
+    pub range: Option<Range<usize>>,
+    // the full name in COMMANDS
+    pub name: &'static str,
+    pub bang: bool,
+    pub args: String,
+}
+
+impl ExCommand {
+    pub fn range_or_current(&self, current_line_idx: usize) -> Range<usize> {
+        self.range
+            .clone()
+            .unwrap_or(current_line_idx..current_line_idx.saturating_add(1))
+    }
+}
+
+pub fn parse_command(input: &str, context: &AddressContext) -> Result<ExCommand, String> {
+    let (range, rest) = parse_range(input.trim_start_matches([':', ' ']), context)?;
+    let rest = rest.trim_start();
+    let name_len = match rest.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len()),
+        Some(c @ ('>' | '<')) => c.len_utf8(),
+        _ => 0,
+    };
+    let (name, rest) = rest.split_at(name_len);
+    if name.is_empty() {
+        if !rest.is_empty() {
+            return Err(format!("Not an editor command: {rest}"));
+        }
+        // only a range, like `:5`
+        return Ok(ExCommand {
+            range,
+            name: "",
+            bang: false,
+            args: String::new(),
+        });
+    }
+    let (full_name, _, range_allowed) = COMMANDS
+        .iter()
+        .find(|(full_name, abbreviation, _)| {
+            full_name.starts_with(name) && name.starts_with(abbreviation)
+        })
+        .ok_or(format!("Unknown command: {name}"))?;
+    if range.is_some() && !range_allowed {
+        return Err("Range is not allowed".to_string());
+    }
+    let (bang, rest) = match rest.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    Ok(ExCommand {
+        range,
+        name: full_name,
+        bang,
+        args: rest.trim_start().to_string(),
+    })
+}
+
+// parse a line range at the head of an ex command like `%`, `3,$`, `'a,.`
+// returns the range of line indices and the rest of the input
+// the range is None if the input has no range
+pub fn parse_range<'a>(
+    input: &'a str,
+    context: &AddressContext,
+) -> Result<(Option<Range<usize>>, &'a str), String> {
+    let lines_count = context.buffer.get_lines_count();
+    if let Some(rest) = input.strip_prefix('%') {
+        return Ok((Some(0..lines_count), rest));
+    }
+    let (first, rest) = parse_address(input, context)?;
+    let (last, rest) =
+        if let Some(separator) = rest.chars().next().filter(|c| [',', ';'].contains(c)) {
+            let rest = &rest[separator.len_utf8()..];
+            // with `;` the second address is relative to the first one
+            let current_line_idx = match (separator, first) {
+                (';', Some(first)) => first.saturating_sub(1),
+                _ => context.current_line_idx,
+            };
+            let context = AddressContext {
+                current_line_idx,
+                ..*context
+            };
+            let (last, rest) = parse_address(rest, &context)?;
+            let current_line_number = context.current_line_idx.saturating_add(1);
+            (Some(last.or(first).unwrap_or(current_line_number)), rest)
+        } else {
+            (first, rest)
+        };
+    let Some(last) = last else {
+        return Ok((None, rest));
+    };
+    let first = first.unwrap_or(context.current_line_idx.saturating_add(1));
+    if first.max(last) > lines_count.max(1) {
+        return Err("Invalid range".to_string());
+    }
+    // line 0 is treated as the first line
+    let start = first.min(last).saturating_sub(1);
+    let end = first.max(last).max(1);
+    Ok((Some(start..end), rest))
+}
+
+// parse a single address with offsets like `.+2`, `/pat/-1`
+// returns the line number, which is 1-based and 0 means before the first line
+pub fn parse_address<'a>(
+    input: &'a str,
+    context: &AddressContext,
+) -> Result<(Option<usize>, &'a str), String> {
+    let buffer = context.buffer;
+    let current_line_number = context.current_line_idx.saturating_add(1);
+    let mut chars = input.chars();
+    let (base, mut rest) = match chars.next() {
+        Some('.') => (Some(current_line_number), chars.as_str()),
+        Some('$') => (Some(buffer.get_lines_count()), chars.as_str()),
+        Some('\'') => {
+            let name = chars.next().ok_or("Invalid mark")?;
+            let mark = buffer.get_mark(name).ok_or("Mark not set")?;
+            (Some(mark.line_idx.saturating_add(1)), chars.as_str())
+        }
+        Some(delimiter @ ('/' | '?')) => {
+            let (pattern, rest) = take_pattern(chars.as_str(), delimiter);
+            let direction = if delimiter == '/' {
+                SearchDirection::Forward
+            } else {
+                SearchDirection::Backward
+            };
+            let line_idx = search_line(&pattern, context, direction)?;
+            (Some(line_idx.saturating_add(1)), rest)
+        }
+        Some(c) if c.is_ascii_digit() => {
+            let (line_number, rest) = take_number(input);
+            (Some(line_number.ok_or("Invalid range")?), rest)
+        }
+        _ => (None, input),
+    };
+    let mut line_number = base;
+    while let Some(sign @ ('+' | '-')) = rest.chars().next() {
+        let (offset, next) = take_number(&rest[1..]);
+        // a sign without a number means 1
+        let offset = offset.unwrap_or(1);
+        let base = line_number.unwrap_or(current_line_number);
+        line_number = Some(if sign == '+' {
+            base.saturating_add(offset)
+        } else {
+            base.checked_sub(offset).ok_or("Invalid range")?
+        });
+        rest = next;
+    }
+    Ok((line_number, rest))
+}
+
+fn take_number(input: &str) -> (Option<usize>, &str) {
+    let digits_len = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (digits, rest) = input.split_at(digits_len);
+    (digits.parse().ok(), rest)
+}
+
+// take a pattern up to the unescaped delimiter, `\` followed by the delimiter is the delimiter itself
+fn take_pattern(input: &str, delimiter: char) -> (String, &str) {
+    let mut pattern = String::new();
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        if c == delimiter {
+            break;
+        }
+        if c == '\\' {
+            match chars.next() {
+                Some(next) if next == delimiter => pattern.push(next),
+                Some(next) => {
+                    pattern.push(c);
+                    pattern.push(next);
+                }
+                None => pattern.push(c),
+            }
+        } else {
+            pattern.push(c);
+        }
+    }
+    (pattern, chars.as_str())
+}
+
+// the nearest line with a match, the current line is checked last
+fn search_line(
+    pattern: &str,
+    context: &AddressContext,
+    direction: SearchDirection,
+) -> Result<usize, String> {
+    let pattern = if pattern.is_empty() {
+        context
+            .last_search
+            .ok_or("No previous regular expression")?
+    } else {
+        pattern
+    };
+    let regex = Regex::new(pattern).map_err(|_| format!("Invalid pattern: {pattern}"))?;
+    let col_idx = match direction {
+        SearchDirection::Forward => usize::MAX,
+        SearchDirection::Backward => 0,
+    };
+    let from = Position {
+        line_idx: context.current_line_idx,
+        col_idx,
+    };
+    find_match(context.buffer, &regex, from, direction)
+        .map(|found| found.position.line_idx)
+        .ok_or(format!("Pattern not found: {pattern}"))
+}
+
+// parse `[x] [count]` of `:d` and `:y`
+pub fn parse_register_and_count(args: &str) -> Result<(Option<char>, Option<usize>), String> {
+    let mut chars = args.chars();
+    let register = chars.next().filter(|c| !c.is_ascii_digit());
+    let rest = if register.is_some() {
+        chars.as_str().trim_start()
+    } else {
+        args
+    };
+    Ok((register, parse_count(rest)?))
+}
+
+pub fn parse_count(args: &str) -> Result<Option<usize>, String> {
+    match take_number(args.trim()) {
+        (_, rest) if !rest.is_empty() => Err(format!("Trailing characters: {rest}")),
+        (Some(0), _) => Err("Positive count required".to_string()),
+        (count, _) => Ok(count),
+    }
+}
+
+// `{count}` lines from the last line of the range
+pub fn apply_count(range: Range<usize>, count: Option<usize>, lines_count: usize) -> Range<usize> {
+    match count {
+        Some(count) => {
+            let start = range.end.saturating_sub(1);
+            start..start.saturating_add(count).min(lines_count)
+        }
+        None => range,
+    }
+}
+
+// move lines after the line number `target`, returns the index of the last moved line
+pub fn move_lines(
+    buffer: &mut Buffer,
+    range: Range<usize>,
+    target: usize,
+) -> Result<usize, String> {
+    if range.start < target && target < range.end {
+        return Err("Cannot move a range of lines into itself".to_string());
+    }
+    let count = range.len();
+    if target == range.end || target == range.start {
+        // already there
+        return Ok(range.end.saturating_sub(1));
+    }
+    let lines = buffer.delete_lines(range.clone());
+    let line_idx = if target > range.start {
+        target.saturating_sub(count)
+    } else {
+        target
+    };
+    buffer.insert_lines(&lines, line_idx);
+    Ok(line_idx.saturating_add(count).saturating_sub(1))
+}
+
+// copy lines after the line number `target`, returns the index of the last copied line
+pub fn copy_lines(buffer: &mut Buffer, range: Range<usize>, target: usize) -> usize {
+    let lines = buffer.get_lines(range);
+    buffer.insert_lines(&lines, target);
+    target.saturating_add(lines.len()).saturating_sub(1)
+}
+
+// join the lines into the first one, with a space between them unless `without_spaces`
+pub fn join_lines(buffer: &mut Buffer, range: Range<usize>, without_spaces: bool) {
+    let lines = buffer.get_lines(range.clone());
+    if lines.len() < 2 {
+        return;
+    }
+    let joined = lines
+        .iter()
+        .skip(1)
+        .fold(lines[0].clone(), |mut joined, line| {
+            if without_spaces {
+                joined.push_str(line);
+                return joined;
+            }
+            let line = line.trim_start();
+            if !line.is_empty() && !joined.is_empty() && !joined.ends_with([' ', '\t']) {
+                joined.push(' ');
+            }
+            joined.push_str(line);
+            joined
+        });
+    buffer.delete_lines(range.start.saturating_add(1)..range.end);
+    buffer.set_line(&joined, range.start);
+}
+
+// move the indent by `shiftwidth` per level
+pub fn shift_lines(
+    buffer: &mut Buffer,
+    range: Range<usize>,
+    levels: usize,
+    right: bool,
+    options: &Options,
+) {
+    for line_idx in range {
+        let Some(line) = buffer.lines.get(line_idx) else {
+            break;
+        };
+        let content = line.content();
+        // empty lines are not indented
+        if content.is_empty() {
+            continue;
+        }
+        let shifted = shift_line(line, levels, right, options);
+        if shifted != content {
+            buffer.set_line(&shifted, line_idx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::line::Line;
+    use super::*;
+
+    fn context(buffer: &Buffer, current_line_idx: usize) -> AddressContext<'_> {
+        AddressContext {
+            buffer,
+            current_line_idx,
+            last_search: Some("c"),
+        }
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let mut buffer = Buffer::from_string("a\nb\nc\nd\n");
+        let c1 = context(&buffer, 1);
+        let c0 = context(&buffer, 0);
+        assert_eq!(parse_range("s/a/b/", &c1), Ok((None, "s/a/b/")));
+        assert_eq!(parse_range("%s", &c1), Ok((Some(0..4), "s")));
+        assert_eq!(parse_range(".s", &c1), Ok((Some(1..2), "s")));
+        assert_eq!(parse_range("2,$s", &c0), Ok((Some(1..4), "s")));
+        assert_eq!(parse_range("3,1d", &c0), Ok((Some(0..3), "d")));
+        assert_eq!(parse_range("3,d", &c0), Ok((Some(2..3), "d")));
+        assert_eq!(parse_range(".+1,+2d", &c0), Ok((Some(1..3), "d")));
+        assert_eq!(parse_range("$-1d", &c0), Ok((Some(2..3), "d")));
+        assert_eq!(parse_range("-,.d", &c1), Ok((Some(0..2), "d")));
+        assert_eq!(parse_range("2;+1d", &c0), Ok((Some(1..3), "d")));
+        assert_eq!(parse_range("/c/d", &c0), Ok((Some(2..3), "d")));
+        assert_eq!(parse_range("?b?,/d/d", &c0), Ok((Some(1..4), "d")));
+        assert_eq!(parse_range("//d", &c0), Ok((Some(2..3), "d")));
+        assert_eq!(parse_range("/a/d", &c0), Ok((Some(0..1), "d")));
+        assert!(parse_range("/x/d", &c0).is_err());
+        assert!(parse_range("5s", &c0).is_err());
+        assert!(parse_range(".-2d", &c0).is_err());
+        assert!(parse_range("'a,.s", &c0).is_err());
+        buffer.set_mark('a', Position::new(3, 0));
+        buffer.set_mark('<', Position::new(1, 0));
+        buffer.set_mark('>', Position::new(2, 0));
+        assert_eq!(
+            parse_range("'a,.s", &context(&buffer, 1)),
+            Ok((Some(1..4), "s"))
+        );
+        assert_eq!(
+            parse_range("'<,'>d", &context(&buffer, 0)),
+            Ok((Some(1..3), "d"))
+        );
+    }
+
+    #[test]
+    fn test_parse_command() {
+        let buffer = Buffer::from_string("a\nb\nc\n");
+        let c0 = context(&buffer, 0);
+        assert_eq!(
+            parse_command("1,2d x", &c0),
+            Ok(ExCommand {
+                range: Some(0..2),
+                name: "delete",
+                bang: false,
+                args: "x".to_string(),
+            })
+        );
+        assert_eq!(parse_command("%j!", &c0).unwrap().name, "join");
+        assert!(parse_command("%j!", &c0).unwrap().bang);
+        assert_eq!(parse_command("co$", &c0).unwrap().args, "$");
+        assert_eq!(parse_command(">>", &c0).unwrap().args, ">");
+        assert_eq!(parse_command("norm x", &c0).unwrap().name, "normal");
+        assert_eq!(parse_command("s/a/b/", &c0).unwrap().args, "/a/b/");
+        assert_eq!(parse_command("wq", &c0).unwrap().name, "wq");
+        assert!(parse_command("nor x", &c0).is_err());
+        assert!(parse_command("1,2w", &c0).is_err());
+        assert_eq!(parse_command("1,2", &c0).unwrap().name, "");
+        assert_eq!(parse_command("", &c0).unwrap().range, None);
+    }
+
+    #[test]
+    fn test_parse_register_and_count() {
+        assert_eq!(parse_register_and_count(""), Ok((None, None)));
+        assert_eq!(parse_register_and_count("a"), Ok((Some('a'), None)));
+        assert_eq!(parse_register_and_count("a 3"), Ok((Some('a'), Some(3))));
+        assert_eq!(parse_register_and_count("3"), Ok((None, Some(3))));
+        assert!(parse_register_and_count("a b").is_err());
+        assert_eq!(apply_count(0..2, Some(3), 10), 1..4);
+        assert_eq!(apply_count(0..2, Some(30), 10), 1..10);
+    }
+
+    fn contents(buffer: &Buffer) -> Vec<&str> {
+        buffer.lines.iter().map(Line::content).collect()
+    }
+
+    #[test]
+    fn test_move_and_copy_lines() {
+        let mut buffer = Buffer::from_string("a\nb\nc\nd\n");
+        assert_eq!(move_lines(&mut buffer, 0..2, 3), Ok(2));
+        assert_eq!(contents(&buffer), vec!["c", "a", "b", "d"]);
+        assert_eq!(move_lines(&mut buffer, 3..4, 0), Ok(0));
+        assert_eq!(contents(&buffer), vec!["d", "c", "a", "b"]);
+        assert_eq!(move_lines(&mut buffer, 1..3, 3), Ok(2));
+        assert_eq!(contents(&buffer), vec!["d", "c", "a", "b"]);
+        assert!(move_lines(&mut buffer, 0..3, 2).is_err());
+        assert_eq!(copy_lines(&mut buffer, 0..2, 4), 5);
+        assert_eq!(contents(&buffer), vec!["d", "c", "a", "b", "d", "c"]);
+        assert_eq!(copy_lines(&mut buffer, 2..3, 0), 0);
+        assert_eq!(contents(&buffer), vec!["a", "d", "c", "a", "b", "d", "c"]);
+    }
+
+    #[test]
+    fn test_join_lines() {
+        let mut buffer = Buffer::from_string("a\n  b\n\nc \nd\n");
+        join_lines(&mut buffer, 0..5, false);
+        assert_eq!(contents(&buffer), vec!["a b c d"]);
+        let mut buffer = Buffer::from_string("a\n  b\nc\n");
+        join_lines(&mut buffer, 0..2, true);
+        assert_eq!(contents(&buffer), vec!["a  b", "c"]);
+    }
+
+    #[test]
+    fn test_shift_lines() {
+        let mut buffer = Buffer::from_string("a\n\n  b\n\t\tc\n");
+        let mut options = Options::default();
+        shift_lines(&mut buffer, 0..4, 1, true, &options);
+        assert_eq!(contents(&buffer), vec!["\ta", "", "\t  b", "\t\t\tc"]);
+        shift_lines(&mut buffer, 0..4, 2, false, &options);
+        assert_eq!(contents(&buffer), vec!["a", "", "b", "\tc"]);
+        // the tabs are replaced with spaces of the same width
+        options.expandtab = true;
+        options.shiftwidth = 2;
+        shift_lines(&mut buffer, 2..4, 1, true, &options);
+        assert_eq!(contents(&buffer), vec!["a", "", "  b", "      c"]);
+    }
+}
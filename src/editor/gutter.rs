@@ -0,0 +1,133 @@
+use super::annotation::{AnnotationKind, Span};
+use super::options::Options;
+use super::wrap::ScreenRow;
+
+// a column on the left of the text, sign columns go next to the numbers
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GutterColumn {
+    // absolute with `number`, relative with `relativenumber`, hybrid with both
+    Number,
+}
+
+// the columns shown on the left of the text
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Gutter {
+    columns: Vec<GutterColumn>,
+    number: bool,
+    relativenumber: bool,
+    lines_count: usize,
+    cursor_line: usize,
+}
+
+impl Gutter {
+    pub fn new(options: &Options, lines_count: usize, cursor_line: usize) -> Self {
+        let mut columns = Vec::new();
+        if options.number || options.relativenumber {
+            columns.push(GutterColumn::Number);
+        }
+        Self {
+            columns,
+            number: options.number,
+            relativenumber: options.relativenumber,
+            lines_count,
+            cursor_line,
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.columns
+            .iter()
+            .map(|column| self.column_width(*column))
+            .sum()
+    }
+    fn column_width(&self, column: GutterColumn) -> usize {
+        match column {
+            // the digits of the last line and a space, it grows at each power of ten
+            GutterColumn::Number => digits(self.lines_count).saturating_add(1),
+        }
+    }
+    // the gutter of a row, the continued rows of a wrapped line are blank
+    pub fn spans(&self, row: &ScreenRow, continued: bool) -> Vec<Span> {
+        self.columns
+            .iter()
+            .map(|column| {
+                let width = self.column_width(*column);
+                if continued {
+                    return Span::plain(&" ".repeat(width));
+                }
+                match column {
+                    GutterColumn::Number => self.number_span(row.line_idx, width),
+                }
+            })
+            .collect()
+    }
+    fn number_span(&self, line_idx: usize, width: usize) -> Span {
+        let digits_width = width.saturating_sub(1);
+        let is_cursor_line = line_idx == self.cursor_line;
+        let text = if !self.relativenumber {
+            format!("{:>digits_width$} ", line_idx.saturating_add(1))
+        } else if is_cursor_line && self.number {
+            // the hybrid numbering shows the cursor line on the left
+            format!("{:<digits_width$} ", line_idx.saturating_add(1))
+        } else {
+            format!("{:>digits_width$} ", line_idx.abs_diff(self.cursor_line))
+        };
+        Span {
+            text,
+            kind: Some(if is_cursor_line {
+                AnnotationKind::CursorLineNumber
+            } else {
+                AnnotationKind::LineNumber
+            }),
+        }
+    }
+}
+
+fn digits(number: usize) -> usize {
+    number
+        .checked_ilog10()
+        .and_then(|log| usize::try_from(log).ok())
+        .map_or(1, |log| log.saturating_add(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(gutter: &Gutter, line_idx: usize) -> String {
+        let row = ScreenRow {
+            line_idx,
+            ..ScreenRow::default()
+        };
+        gutter
+            .spans(&row, false)
+            .into_iter()
+            .map(|span| span.text)
+            .collect()
+    }
+
+    #[test]
+    fn test_gutter() {
+        let mut options = Options::default();
+        assert_eq!(Gutter::new(&options, 5, 0).width(), 0);
+        options.number = true;
+        assert_eq!(Gutter::new(&options, 9, 0).width(), 2);
+        assert_eq!(Gutter::new(&options, 10, 0).width(), 3);
+        assert_eq!(Gutter::new(&options, 100, 0).width(), 4);
+        let gutter = Gutter::new(&options, 12, 3);
+        assert_eq!(texts(&gutter, 0), " 1 ");
+        assert_eq!(texts(&gutter, 11), "12 ");
+        let row = ScreenRow::default();
+        assert_eq!(gutter.spans(&row, true), vec![Span::plain("   ")]);
+        // hybrid
+        options.relativenumber = true;
+        let gutter = Gutter::new(&options, 12, 3);
+        assert_eq!(texts(&gutter, 3), "4  ");
+        assert_eq!(texts(&gutter, 0), " 3 ");
+        assert_eq!(texts(&gutter, 11), " 8 ");
+        // relative
+        options.number = false;
+        let gutter = Gutter::new(&options, 12, 3);
+        assert_eq!(texts(&gutter, 3), " 0 ");
+        assert_eq!(texts(&gutter, 4), " 1 ");
+    }
+}
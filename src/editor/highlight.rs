@@ -0,0 +1,327 @@
+use super::annotation::{Annotation, AnnotationKind};
+use super::file_info::FileType;
+use super::line::Line;
+use super::syntax::{Region, RegionEnd, Syntax, TokenKind};
+use regex::Regex;
+use std::borrow::Cow;
+use std::ops::Range;
+
+// a region not closed at the end of a line, the next line starts inside it
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct OpenRegion {
+    region_idx: usize,
+    // the first group of the start, for the end pattern
+    group: String,
+    depth: usize,
+}
+
+// the tokens of a line in col_idx, and the region open at its end
+struct HighlightedLine {
+    tokens: Vec<(Range<usize>, TokenKind)>,
+    end_state: Option<OpenRegion>,
+}
+
+// the tokens of the lines from the top are kept until a line is edited
+#[derive(Default)]
+pub struct Highlighter {
+    syntax: Option<Syntax>,
+    lines: Vec<HighlightedLine>,
+}
+
+impl Highlighter {
+    pub fn new(file_type: Option<FileType>) -> Self {
+        Self {
+            syntax: file_type.and_then(Syntax::for_file_type),
+            lines: Vec::new(),
+        }
+    }
+    // the lines from the edited one have to be highlighted again
+    pub fn invalidate(&mut self, line_idx: usize) {
+        self.lines.truncate(line_idx);
+    }
+    // highlight the lines up to `end`, continuing from the last highlighted line
+    pub fn update(&mut self, lines: &[Line], end: usize) {
+        let Some(syntax) = &self.syntax else {
+            return;
+        };
+        let end = end.min(lines.len());
+        while self.lines.len() < end {
+            let line_idx = self.lines.len();
+            let Some(line) = lines.get(line_idx) else {
+                break;
+            };
+            let state = self
+                .lines
+                .last()
+                .and_then(|highlighted| highlighted.end_state.clone());
+            let (tokens, end_state) = tokenize(syntax, line.content(), state);
+            let tokens = tokens
+                .into_iter()
+                .map(|(range, kind)| {
+                    let cols =
+                        line.byte_idx_to_col_idx(range.start)..line.byte_idx_to_col_idx(range.end);
+                    (cols, kind)
+                })
+                .collect();
+            self.lines.push(HighlightedLine { tokens, end_state });
+        }
+    }
+    // the tokens of the highlighted lines in the range
+    pub fn annotations(&self, range: Range<usize>) -> Vec<Annotation> {
+        let mut annotations = Vec::new();
+        for line_idx in range {
+            let Some(highlighted) = self.lines.get(line_idx) else {
+                break;
+            };
+            annotations.extend(highlighted.tokens.iter().map(|(cols, kind)| Annotation {
+                kind: AnnotationKind::Syntax(*kind),
+                line_idx,
+                range: cols.clone(),
+            }));
+        }
+        annotations
+    }
+    #[cfg(test)]
+    pub fn highlighted_count(&self) -> usize {
+        self.lines.len()
+    }
+}
+
+// the tokens of a line in byte ranges, starting in the open region of the line above
+fn tokenize(
+    syntax: &Syntax,
+    text: &str,
+    state: Option<OpenRegion>,
+) -> (Vec<(Range<usize>, TokenKind)>, Option<OpenRegion>) {
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    let mut open = state.map(|open| (0, open));
+    loop {
+        if let Some((start, open_region)) = open.take() {
+            let Some(region) = syntax.regions.get(open_region.region_idx) else {
+                break;
+            };
+            match find_region_end(region, text, pos, open_region) {
+                Ok(end) => {
+                    tokens.push((start..end, region.kind));
+                    pos = end;
+                }
+                Err(open_region) => {
+                    tokens.push((start..text.len(), region.kind));
+                    return (tokens, Some(open_region));
+                }
+            }
+        }
+        let region_match = syntax
+            .regions
+            .iter()
+            .enumerate()
+            .filter_map(|(region_idx, region)| {
+                let captures = region.start.captures_at(text, pos)?;
+                let whole = captures.get(0)?;
+                let group = captures.get(1).map_or("", |group| group.as_str());
+                Some((whole.range(), region_idx, group.to_string()))
+            })
+            .min_by_key(|(range, _, _)| range.start);
+        let token_match = syntax
+            .tokens
+            .iter()
+            .filter_map(|(regex, kind)| {
+                let captures = regex.captures_at(text, pos)?;
+                let range = captures.get(1).or_else(|| captures.get(0))?.range();
+                (!range.is_empty()).then_some((range, *kind))
+            })
+            .min_by_key(|(range, _)| range.start);
+        match (region_match, token_match) {
+            (Some((range, region_idx, group)), token)
+                if token
+                    .as_ref()
+                    .is_none_or(|(token_range, _)| range.start <= token_range.start) =>
+            {
+                pos = range.end;
+                open = Some((
+                    range.start,
+                    OpenRegion {
+                        region_idx,
+                        group,
+                        depth: 1,
+                    },
+                ));
+            }
+            (_, Some((range, kind))) => {
+                pos = range.end;
+                tokens.push((range, kind));
+            }
+            _ => break,
+        }
+    }
+    (tokens, None)
+}
+
+// the end of the region after `pos`, or the region still open at the end of the line
+fn find_region_end(
+    region: &Region,
+    text: &str,
+    pos: usize,
+    open: OpenRegion,
+) -> Result<usize, OpenRegion> {
+    let Some(end_regex) = end_regex(region, &open.group) else {
+        return Err(open);
+    };
+    let mut depth = open.depth;
+    let mut at = pos;
+    loop {
+        let rest = text.get(at..).unwrap_or_default();
+        let Some(end) = end_regex.find(rest) else {
+            return Err(OpenRegion { depth, ..open });
+        };
+        if region.nested {
+            if let Some(start) = region.start.find(rest) {
+                if start.start() < end.start() {
+                    depth = depth.saturating_add(1);
+                    at = at.saturating_add(start.end());
+                    continue;
+                }
+            }
+        }
+        depth = depth.saturating_sub(1);
+        at = at.saturating_add(end.end());
+        if depth == 0 {
+            return Ok(at);
+        }
+    }
+}
+
+fn end_regex<'a>(region: &'a Region, group: &str) -> Option<Cow<'a, Regex>> {
+    match &region.end {
+        RegionEnd::Pattern(regex) => Some(Cow::Borrowed(regex)),
+        RegionEnd::Template(template) => {
+            let pattern = template.replace(r"\1", &regex::escape(group));
+            Regex::new(&pattern).ok().map(Cow::Owned)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(highlighter: &Highlighter, line_idx: usize) -> Vec<(Range<usize>, TokenKind)> {
+        highlighter
+            .annotations(line_idx..line_idx.saturating_add(1))
+            .into_iter()
+            .filter_map(|annotation| match annotation.kind {
+                AnnotationKind::Syntax(kind) => Some((annotation.range, kind)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn highlight(lines: &[&str]) -> (Highlighter, Vec<Line>) {
+        let lines: Vec<Line> = lines.iter().map(|line| Line::from(line)).collect();
+        let mut highlighter = Highlighter::new(Some(FileType::Rust));
+        highlighter.update(&lines, lines.len());
+        (highlighter, lines)
+    }
+
+    #[test]
+    fn test_rust() {
+        let (highlighter, _) = highlight(&[
+            "fn main() { // 日本語🇯🇵",
+            "    let s: &'static str = \"a\\\"b\";",
+            "    println!(\"{}\", 'x');",
+            "#[test]",
+        ]);
+        assert_eq!(
+            tokens(&highlighter, 0),
+            vec![
+                (0..2, TokenKind::Keyword),
+                (3..7, TokenKind::Function),
+                (12..23, TokenKind::Comment)
+            ]
+        );
+        assert_eq!(
+            tokens(&highlighter, 1),
+            vec![
+                (4..7, TokenKind::Keyword),
+                (12..19, TokenKind::Special),
+                (20..23, TokenKind::Type),
+                (26..32, TokenKind::String)
+            ]
+        );
+        assert_eq!(
+            tokens(&highlighter, 2),
+            vec![
+                (4..12, TokenKind::Macro),
+                (13..17, TokenKind::String),
+                (19..22, TokenKind::String)
+            ]
+        );
+        assert_eq!(tokens(&highlighter, 3), vec![(0..7, TokenKind::Attribute)]);
+    }
+
+    #[test]
+    fn test_multi_line() {
+        let (highlighter, _) = highlight(&[
+            "let a = 1; /* x /* nested */",
+            "still */ let",
+            "r##\"raw \"# text",
+            "end\"## 2",
+            "\"a string",
+            "ends\" fn",
+        ]);
+        assert_eq!(
+            tokens(&highlighter, 0),
+            vec![
+                (0..3, TokenKind::Keyword),
+                (8..9, TokenKind::Number),
+                (11..28, TokenKind::Comment)
+            ]
+        );
+        assert_eq!(
+            tokens(&highlighter, 1),
+            vec![(0..8, TokenKind::Comment), (9..12, TokenKind::Keyword)]
+        );
+        assert_eq!(tokens(&highlighter, 2), vec![(0..15, TokenKind::String)]);
+        assert_eq!(
+            tokens(&highlighter, 3),
+            vec![(0..6, TokenKind::String), (7..8, TokenKind::Number)]
+        );
+        assert_eq!(tokens(&highlighter, 4), vec![(0..9, TokenKind::String)]);
+        assert_eq!(
+            tokens(&highlighter, 5),
+            vec![(0..5, TokenKind::String), (6..8, TokenKind::Keyword)]
+        );
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let (mut highlighter, mut lines) = highlight(&["/*", "a", "*/ fn", "fn"]);
+        assert_eq!(tokens(&highlighter, 3), vec![(0..2, TokenKind::Keyword)]);
+        // closing the comment on the first line changes the lines below
+        lines[0] = Line::from("/**/");
+        highlighter.invalidate(0);
+        assert_eq!(highlighter.highlighted_count(), 0);
+        highlighter.update(&lines, 2);
+        assert_eq!(highlighter.highlighted_count(), 2);
+        highlighter.update(&lines, lines.len());
+        assert_eq!(tokens(&highlighter, 1), vec![]);
+        assert_eq!(tokens(&highlighter, 2), vec![(3..5, TokenKind::Keyword)]);
+        // an edit keeps the lines above
+        highlighter.invalidate(2);
+        assert_eq!(highlighter.highlighted_count(), 2);
+    }
+
+    #[test]
+    fn test_columns() {
+        // the tab and the control character are wider than their bytes
+        let (highlighter, _) = highlight(&["\tfn\u{1}fn"]);
+        assert_eq!(
+            tokens(&highlighter, 0),
+            vec![(4..6, TokenKind::Keyword), (8..10, TokenKind::Keyword)]
+        );
+        assert!(Highlighter::new(Some(FileType::Text))
+            .annotations(0..1)
+            .is_empty());
+    }
+}
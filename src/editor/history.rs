@@ -0,0 +1,137 @@
+use super::position::Position;
+
+// an edit replaces `before` lines with `after` lines, starting at `line_idx`
+// applying it backwards restores the buffer, so every change can be undone
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Edit {
+    pub line_idx: usize,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+    // where the change happened, used to restore the cursor
+    pub at: Position,
+}
+
+// one undo step is a group of edits, e.g. one insert session or one normal command
+pub type Step = Vec<Edit>;
+
+pub struct History {
+    undo_stack: Vec<Step>,
+    redo_stack: Vec<Step>,
+    pending: Step,
+    // undo_stack length when the buffer was saved
+    // None means the saved state is not reachable anymore
+    saved_at: Option<usize>,
+}
+
+impl History {
+    pub fn push(&mut self, edit: Edit) {
+        if edit.before == edit.after {
+            return;
+        }
+        if self
+            .saved_at
+            .is_some_and(|saved_at| saved_at > self.undo_stack.len())
+        {
+            // the saved state is in the redo stack, which is discarded below
+            self.saved_at = None;
+        }
+        self.redo_stack.clear();
+        self.pending.push(edit);
+    }
+    // close the current step, returns true if something is committed
+    pub fn commit(&mut self) -> bool {
+        if self.pending.is_empty() {
+            return false;
+        }
+        let step = std::mem::take(&mut self.pending);
+        self.undo_stack.push(step);
+        true
+    }
+    pub fn undo(&mut self) -> Option<Step> {
+        self.commit();
+        let step = self.undo_stack.pop()?;
+        self.redo_stack.push(step.clone());
+        Some(step)
+    }
+    pub fn redo(&mut self) -> Option<Step> {
+        self.commit();
+        let step = self.redo_stack.pop()?;
+        self.undo_stack.push(step.clone());
+        Some(step)
+    }
+    pub fn mark_saved(&mut self) {
+        self.commit();
+        self.saved_at = Some(self.undo_stack.len());
+    }
+    // distance from the saved state in undo steps, the pending edits are one step
+    pub fn modified_count(&self) -> usize {
+        let len = self
+            .undo_stack
+            .len()
+            .saturating_add(usize::from(!self.pending.is_empty()));
+        self.saved_at
+            .map_or_else(|| len.saturating_add(1), |saved_at| saved_at.abs_diff(len))
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            pending: Vec::new(),
+            saved_at: Some(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(before: &str, after: &str) -> Edit {
+        Edit {
+            line_idx: 0,
+            before: vec![before.to_string()],
+            after: vec![after.to_string()],
+            at: Position::default(),
+        }
+    }
+
+    #[test]
+    fn test_modified_count() {
+        let mut history = History::default();
+        assert_eq!(history.modified_count(), 0);
+        history.push(edit("a", "ab"));
+        history.push(edit("ab", "abc"));
+        // the edits not committed yet are one step
+        assert_eq!(history.modified_count(), 1);
+        assert!(history.commit());
+        assert!(!history.commit());
+        assert_eq!(history.modified_count(), 1);
+        history.mark_saved();
+        assert_eq!(history.modified_count(), 0);
+        assert!(history.undo().is_some());
+        assert_eq!(history.modified_count(), 1);
+        assert!(history.undo().is_none());
+        assert!(history.redo().is_some());
+        assert_eq!(history.modified_count(), 0);
+
+        // the saved state is discarded with the redo stack
+        assert!(history.undo().is_some());
+        history.push(edit("a", "x"));
+        history.commit();
+        assert!(history.redo().is_none());
+        assert_eq!(history.modified_count(), 2);
+        assert!(history.undo().is_some());
+        assert_ne!(history.modified_count(), 0);
+    }
+
+    #[test]
+    fn test_ignore_no_change() {
+        let mut history = History::default();
+        history.push(edit("a", "a"));
+        assert!(!history.commit());
+        assert_eq!(history.modified_count(), 0);
+    }
+}
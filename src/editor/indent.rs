@@ -0,0 +1,247 @@
+use super::buffer::Buffer;
+use super::file_info::FileType;
+use super::line::Line;
+use super::options::Options;
+use super::position::Position;
+use std::ops::Range;
+
+pub const OPEN_BRACKETS: [char; 3] = ['(', '[', '{'];
+pub const CLOSE_BRACKETS: [char; 3] = [')', ']', '}'];
+
+// how the indent of a line is found, by the file type
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Indenter {
+    // the indent of the line above
+    Copy,
+    // a level for each bracket left open above, the brackets in literals and comments are skipped
+    Brackets,
+}
+
+impl Indenter {
+    pub fn for_file_type(file_type: Option<FileType>) -> Self {
+        match file_type {
+            Some(FileType::Rust) => Self::Brackets,
+            _ => Self::Copy,
+        }
+    }
+}
+
+// spaces and tabs from a column to another, tabs up to the last tab stop unless `expandtab`
+pub fn fill_whitespace(from: usize, to: usize, options: &Options) -> String {
+    let mut text = String::new();
+    let mut col_idx = from;
+    if !options.expandtab {
+        loop {
+            // modulo operation is necessary in this case
+            #[allow(clippy::arithmetic_side_effects)]
+            let next_stop = col_idx.saturating_add(options.tabstop - col_idx % options.tabstop);
+            if next_stop > to {
+                break;
+            }
+            text.push('\t');
+            col_idx = next_stop;
+        }
+    }
+    text.push_str(&" ".repeat(to.saturating_sub(col_idx)));
+    text
+}
+
+// the indent width of each line in the range, the earlier lines count with their new indent
+// None for a line starting in a string or a comment of many lines, it must be kept as it is
+pub fn compute_indents(
+    buffer: &Buffer,
+    range: Range<usize>,
+    indenter: Indenter,
+    options: &Options,
+) -> Vec<Option<usize>> {
+    if indenter == Indenter::Brackets {
+        return bracket_indents(buffer, range, options);
+    }
+    // every line gets the indent of the last line with text above the range
+    let indent = buffer
+        .lines
+        .get(..range.start)
+        .unwrap_or_default()
+        .iter()
+        .rev()
+        .find(|line| !line.content().trim().is_empty())
+        .map_or(0, Line::indent_width);
+    vec![Some(indent); range.len()]
+}
+
+fn bracket_indents(buffer: &Buffer, range: Range<usize>, options: &Options) -> Vec<Option<usize>> {
+    // the literals are found once for each edit of the buffer
+    let scan = buffer.text_scan();
+    // the indent of the line of each open bracket
+    let mut open: Vec<usize> = Vec::new();
+    let mut indents = Vec::with_capacity(range.len());
+    for (line_idx, line) in buffer.lines.iter().enumerate().take(range.end) {
+        let start = scan.text.offset(
+            Position {
+                line_idx,
+                col_idx: 0,
+            },
+            buffer,
+        );
+        let mut indent_width = line.indent_width();
+        if range.contains(&line_idx) {
+            let indent = if scan
+                .literal_at(start)
+                .is_some_and(|literal| literal.start < start)
+            {
+                None
+            } else {
+                // closing brackets first on the line go back to the line opening them
+                let closing = line
+                    .content()
+                    .trim_start()
+                    .chars()
+                    .take_while(|c| CLOSE_BRACKETS.contains(c))
+                    .count();
+                Some(if closing > 0 {
+                    open.get(open.len().saturating_sub(closing))
+                        .copied()
+                        .unwrap_or_default()
+                } else {
+                    open.last()
+                        .map_or(0, |indent| indent.saturating_add(options.shift_width()))
+                })
+            };
+            indent_width = indent.unwrap_or(indent_width);
+            indents.push(indent);
+        }
+        for (idx, c) in line.content().char_indices() {
+            if scan.literal_at(start.saturating_add(idx)).is_some() {
+                continue;
+            }
+            if OPEN_BRACKETS.contains(&c) {
+                open.push(indent_width);
+            } else if CLOSE_BRACKETS.contains(&c) {
+                open.pop();
+            }
+        }
+    }
+    // the lines below the last one are not content
+    indents.resize(range.len(), Some(0));
+    indents
+}
+
+// `=`, the lines with text are content by the rules of the file type
+pub fn reindent_lines(buffer: &mut Buffer, range: Range<usize>, options: &Options) {
+    let indenter = Indenter::for_file_type(buffer.file_info.get_file_type());
+    let indents = compute_indents(buffer, range.clone(), indenter, options);
+    for (line_idx, indent) in range.zip(indents) {
+        let Some(line) = buffer.lines.get(line_idx) else {
+            break;
+        };
+        let Some(indent) = indent.filter(|_| !line.content().trim().is_empty()) else {
+            continue;
+        };
+        let content = with_indent(line, indent, options);
+        if content != line.content() {
+            buffer.set_line(&content, line_idx);
+        }
+    }
+}
+
+// the line with the indent of the width, made of tabs and spaces again
+pub fn with_indent(line: &Line, width: usize, options: &Options) -> String {
+    let body = line.content().trim_start_matches([' ', '\t']);
+    format!("{}{body}", fill_whitespace(0, width, options))
+}
+
+// the line with its indent moved by `shiftwidth` per level
+// a mixed indent of tabs and spaces is made again from its width
+pub fn shift_line(line: &Line, levels: usize, right: bool, options: &Options) -> String {
+    let shift = levels.saturating_mul(options.shift_width());
+    let indent_width = line.indent_width();
+    let width = if right {
+        indent_width.saturating_add(shift)
+    } else {
+        indent_width.saturating_sub(shift)
+    };
+    with_indent(line, width, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::file_info::FileInfo;
+    use super::*;
+
+    #[test]
+    fn test_fill_whitespace() {
+        let mut options = Options::default();
+        assert_eq!(fill_whitespace(0, 10, &options), "\t\t  ");
+        // the first tab reaches the next tab stop
+        assert_eq!(fill_whitespace(3, 9, &options), "\t\t ");
+        assert_eq!(fill_whitespace(1, 3, &options), "  ");
+        assert_eq!(fill_whitespace(5, 4, &options), "");
+        options.expandtab = true;
+        assert_eq!(fill_whitespace(3, 9, &options), "      ");
+    }
+
+    #[test]
+    fn test_shift_line() {
+        let mut options = Options::default();
+        let line = Line::from(" \t  a b");
+        assert_eq!(shift_line(&line, 1, true, &options), "\t\t  a b");
+        assert_eq!(shift_line(&line, 1, false, &options), "  a b");
+        assert_eq!(shift_line(&line, 3, false, &options), "a b");
+        options.shiftwidth = 2;
+        options.expandtab = true;
+        assert_eq!(shift_line(&line, 2, true, &options), "          a b");
+        assert_eq!(shift_line(&line, 1, false, &options), "    a b");
+    }
+
+    #[test]
+    fn test_compute_indents() {
+        let options = Options::default();
+        let mut buffer =
+            Buffer::from_string("fn a() {\nlet s = \"(\n  \";\n    if b {\nc(\nd, ')');\n}\n}\n");
+        buffer.file_info = FileInfo::from("a.rs");
+        let indenter = Indenter::for_file_type(buffer.file_info.get_file_type());
+        assert_eq!(indenter, Indenter::Brackets);
+        // the line in the string is kept, the brackets in literals are skipped
+        assert_eq!(
+            compute_indents(&buffer, 0..8, indenter, &options),
+            vec![
+                Some(0),
+                Some(4),
+                None,
+                Some(4),
+                Some(8),
+                Some(12),
+                Some(4),
+                Some(0)
+            ]
+        );
+        // the lines above the range count with their own indent
+        assert_eq!(
+            compute_indents(&buffer, 4..6, indenter, &options),
+            vec![Some(8), Some(12)]
+        );
+        assert_eq!(
+            compute_indents(&buffer, 8..10, indenter, &options),
+            vec![Some(0), Some(0)]
+        );
+        // the indent of the last line with text above
+        let buffer = Buffer::from_string("  a\n\nb\n");
+        assert_eq!(
+            compute_indents(&buffer, 1..3, Indenter::Copy, &options),
+            vec![Some(2), Some(2)]
+        );
+    }
+
+    #[test]
+    fn test_reindent_lines() {
+        let mut options = Options::default();
+        let mut buffer = Buffer::from_string("fn a() {\n  b();\n\n\t\t}\n");
+        buffer.file_info = FileInfo::from("a.rs");
+        reindent_lines(&mut buffer, 0..4, &options);
+        assert_eq!(buffer.get_lines(0..4), vec!["fn a() {", "\tb();", "", "}"]);
+        options.expandtab = true;
+        options.shiftwidth = 2;
+        reindent_lines(&mut buffer, 1..2, &options);
+        assert_eq!(buffer.get_lines(0..4), vec!["fn a() {", "  b();", "", "}"]);
+    }
+}
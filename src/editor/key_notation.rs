@@ -0,0 +1,112 @@
+use super::terminal::{KeyCode, KeyModifiers};
+
+// parse key notation like `dd<C-R>:w<CR>` into key events
+// the inverse of `Editor::key_to_string`, a literal `<` is written as `<LT>`
+pub fn parse_keys(notation: &str) -> Vec<(KeyCode, KeyModifiers)> {
+    let mut keys = Vec::new();
+    let mut rest = notation;
+    while let Some(c) = rest.chars().next() {
+        if c == '<' {
+            let special = rest
+                .find('>')
+                .and_then(|end| Some((parse_special_key(&rest[1..end])?, end)));
+            if let Some((key, end)) = special {
+                keys.push(key);
+                rest = &rest[end.saturating_add(1)..];
+                continue;
+            }
+        }
+        // a newline in a linewise register works as <CR>
+        let code = if c == '\n' {
+            KeyCode::Enter
+        } else {
+            KeyCode::Char(c)
+        };
+        keys.push((code, KeyModifiers::NONE));
+        rest = &rest[c.len_utf8()..];
+    }
+    keys
+}
+
+// the name between `<` and `>`, like `C-R`, `S-LEFT`, `ESC`
+fn parse_special_key(name: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut name = name;
+    loop {
+        let (modifier, rest) = match name.split_at_checked(2) {
+            Some(("C-", rest)) => (KeyModifiers::CONTROL, rest),
+            Some(("A-", rest)) => (KeyModifiers::ALT, rest),
+            Some(("S-", rest)) => (KeyModifiers::SHIFT, rest),
+            _ => break,
+        };
+        modifiers.insert(modifier);
+        name = rest;
+    }
+    let code = match name {
+        "SPACE" => KeyCode::Char(' '),
+        "LT" => KeyCode::Char('<'),
+        "CR" => KeyCode::Enter,
+        "TAB" => KeyCode::Tab,
+        "BS" => KeyCode::Backspace,
+        "DEL" => KeyCode::Delete,
+        "ESC" => KeyCode::Esc,
+        "LEFT" => KeyCode::Left,
+        "RIGHT" => KeyCode::Right,
+        "UP" => KeyCode::Up,
+        "DOWN" => KeyCode::Down,
+        "HOME" => KeyCode::Home,
+        "END" => KeyCode::End,
+        "PAGEUP" => KeyCode::PageUp,
+        "PAGEDOWN" => KeyCode::PageDown,
+        "INSERT" => KeyCode::Insert,
+        "BACKTAB" => KeyCode::BackTab,
+        _ => {
+            // a character with modifiers, which is uppercased in the notation
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if !modifiers.is_empty() => KeyCode::Char(c.to_ascii_lowercase()),
+                _ => return None,
+            }
+        }
+    };
+    Some((code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(
+            parse_keys("d<C-R><ESC><S-LEFT><SPACE>"),
+            vec![
+                (KeyCode::Char('d'), KeyModifiers::NONE),
+                (KeyCode::Char('r'), KeyModifiers::CONTROL),
+                (KeyCode::Esc, KeyModifiers::NONE),
+                (KeyCode::Left, KeyModifiers::SHIFT),
+                (KeyCode::Char(' '), KeyModifiers::NONE),
+            ]
+        );
+        // `<` which does not start a key name is literal
+        assert_eq!(
+            parse_keys("<<ESC>a<b<LT>"),
+            vec![
+                (KeyCode::Char('<'), KeyModifiers::NONE),
+                (KeyCode::Esc, KeyModifiers::NONE),
+                (KeyCode::Char('a'), KeyModifiers::NONE),
+                (KeyCode::Char('<'), KeyModifiers::NONE),
+                (KeyCode::Char('b'), KeyModifiers::NONE),
+                (KeyCode::Char('<'), KeyModifiers::NONE),
+            ]
+        );
+        assert_eq!(
+            parse_keys("日<CR>\n"),
+            vec![
+                (KeyCode::Char('日'), KeyModifiers::NONE),
+                (KeyCode::Enter, KeyModifiers::NONE),
+                (KeyCode::Enter, KeyModifiers::NONE),
+            ]
+        );
+    }
+}
@@ -1,30 +1,49 @@
-use super::text_fragment::TextFragment;
+use super::annotation::{Annotation, AnnotationKind, Span};
+use super::list_chars::ListChars;
+use super::text_fragment::{TextFragment, TAB_WIDTH};
 use std::ops::Range;
 use unicode_segmentation::{Graphemes, UnicodeSegmentation};
 
-// https://rust-lang.github.io/rust-clippy/master/index.html#/format_collect
-use std::fmt::Write;
-
-const ELLIPSIS_LEFT: &str = "«";
-const ELLIPSIS_RIGHT: &str = "»";
-
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct Line {
     fragments: Vec<TextFragment>,
     string: String,
     col_width: usize,
+    tab_width: usize,
+}
+
+impl Default for Line {
+    fn default() -> Self {
+        Self {
+            fragments: Vec::new(),
+            string: String::new(),
+            col_width: 0,
+            tab_width: TAB_WIDTH,
+        }
+    }
 }
 
 impl Line {
     pub fn from(string: &str) -> Self {
+        Self::with_tab_width(string, TAB_WIDTH)
+    }
+    pub fn with_tab_width(string: &str, tab_width: usize) -> Self {
         debug_assert!(string.is_empty() || string.lines().count() == 1);
         let mut line = Self {
             string: String::from(string),
+            tab_width,
             ..Self::default()
         };
         line.rebuild_fragments();
         line
     }
+    // the columns of the tabs change, and everything after them
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        if tab_width != self.tab_width {
+            self.tab_width = tab_width;
+            self.rebuild_fragments();
+        }
+    }
     pub fn string_to_graphemes(string: &str) -> Graphemes<'_> {
         string.graphemes(true)
     }
@@ -32,7 +51,7 @@ impl Line {
         let mut left_col_width = 0;
         self.fragments = Self::string_to_graphemes(&self.string)
             .map(|grapheme| {
-                let fragment = TextFragment::new(grapheme, left_col_width);
+                let fragment = TextFragment::new(grapheme, left_col_width, self.tab_width);
                 left_col_width = left_col_width.saturating_add(fragment.width());
                 fragment
             })
@@ -43,6 +62,9 @@ impl Line {
     pub fn content(&self) -> &str {
         &self.string
     }
+    pub fn fragments(&self) -> &[TextFragment] {
+        &self.fragments
+    }
     pub fn grapheme_count(&self) -> usize {
         self.fragments.len()
     }
@@ -57,9 +79,27 @@ impl Line {
     pub fn get_str(&self) -> String {
         self.get_str_by_col_range(0..self.col_width)
     }
+    #[cfg(test)]
     pub fn get_str_by_col_range(&self, range: Range<usize>) -> String {
+        self.get_spans_by_col_range(range, &[], Some(&ListChars::default()))
+            .iter()
+            .map(|span| span.text.as_str())
+            .collect()
+    }
+    // the visible part of the line, split by annotations
+    // when annotations overlap, the last one wins
+    // the whitespace is shown with `list_chars`, and the end of the line with its `eol`
+    pub fn get_spans_by_col_range(
+        &self,
+        range: Range<usize>,
+        annotations: &[&Annotation],
+        list_chars: Option<&ListChars>,
+    ) -> Vec<Span> {
+        let eol = list_chars
+            .and_then(|list_chars| list_chars.eol)
+            .filter(|_| range.start <= self.col_width && self.col_width <= range.end);
         if range.start == range.end {
-            return String::default();
+            return eol.map_or_else(Vec::new, |eol| vec![Span::non_text(&eol.to_string())]);
         }
         // Range<usize> must have start and end
         let mut acc = 0;
@@ -87,17 +127,45 @@ impl Line {
             }
         }
         // println!("start: {start}, end: {end}, acc: {acc}");
-        format!(
-            "{}{}{}",
-            if ellipsis_start { ELLIPSIS_LEFT } else { "" },
-            self.fragments[start..end]
+        let mut spans: Vec<Span> = Vec::new();
+        // a wide character cut at an edge
+        let ellipsis = |marker: Option<char>| Span::non_text(&marker.unwrap_or(' ').to_string());
+        if ellipsis_start {
+            spans.push(ellipsis(
+                list_chars.and_then(|list_chars| list_chars.precedes),
+            ));
+        }
+        // the spaces from here are trailing
+        let trail_start = self
+            .fragments
+            .iter()
+            .rposition(|fragment| fragment.grapheme() != " ")
+            .map_or(0, |idx| idx.saturating_add(1));
+        for (idx, fragment) in self.fragments.iter().enumerate().take(end).skip(start) {
+            let col_idx = fragment.left_col_width();
+            let replacement = fragment.replacement(list_chars, idx >= trail_start);
+            let kind = annotations
                 .iter()
-                .fold(String::new(), |mut output, fragment| {
-                    let _ = write!(output, "{fragment}");
-                    output
+                .rev()
+                .find(|annotation| annotation.range.contains(&col_idx))
+                .map(|annotation| annotation.kind)
+                .or_else(|| replacement.is_some().then_some(AnnotationKind::NonText));
+            let text = replacement.as_deref().unwrap_or(fragment.grapheme());
+            match spans.last_mut() {
+                Some(span) if span.kind == kind => span.text.push_str(text),
+                _ => spans.push(Span {
+                    text: text.to_string(),
+                    kind,
                 }),
-            if ellipsis_end { ELLIPSIS_RIGHT } else { "" },
-        )
+            }
+        }
+        if ellipsis_end {
+            spans.push(ellipsis(
+                list_chars.and_then(|list_chars| list_chars.extends),
+            ));
+        }
+        spans.extend(eol.map(|eol| Span::non_text(&eol.to_string())));
+        spans
     }
     pub fn get_fragment_by_col_idx(&self, col_idx: usize) -> Option<&TextFragment> {
         let mut acc: usize = 0;
@@ -128,11 +196,51 @@ impl Line {
             acc = acc.saturating_add(fragment.width());
             grapheme_idx = grapheme_idx.saturating_add(1);
         }
-        return grapheme_idx;
+        grapheme_idx
     }
     pub fn col_width(&self) -> usize {
         self.col_width
     }
+    // col_idx of the fragment which contains the byte
+    pub fn byte_idx_to_col_idx(&self, byte_idx: usize) -> usize {
+        let mut acc: usize = 0;
+        for fragment in &self.fragments {
+            acc = acc.saturating_add(fragment.byte_len());
+            if acc > byte_idx {
+                return fragment.left_col_width();
+            }
+        }
+        self.col_width
+    }
+    // byte offset of the fragment boundary at col_idx
+    // col_idx in the middle of a wide character is rounded down
+    pub fn col_idx_to_byte_idx(&self, col_idx: usize) -> usize {
+        self.fragments
+            .iter()
+            .take_while(|fragment| {
+                fragment.left_col_width().saturating_add(fragment.width()) <= col_idx
+            })
+            .map(TextFragment::byte_len)
+            .sum()
+    }
+    // raw content between the columns, unlike get_str_by_col_range
+    pub fn get_content_by_col_range(&self, range: Range<usize>) -> &str {
+        let start = self.col_idx_to_byte_idx(range.start);
+        let end = self.col_idx_to_byte_idx(range.end).max(start);
+        &self.string[start..end]
+    }
+    // byte range of the fragments overlapping the columns
+    // a wide character on the edge is included as a whole
+    pub fn get_byte_range_by_col_range(&self, range: Range<usize>) -> Range<usize> {
+        let start = self.col_idx_to_byte_idx(range.start);
+        let end: usize = self
+            .fragments
+            .iter()
+            .take_while(|fragment| fragment.left_col_width() < range.end)
+            .map(TextFragment::byte_len)
+            .sum();
+        start..end.max(start)
+    }
     // TODO: needs performance improvement... obviously not efficient
     pub fn split_off(&mut self, at_col_idx: usize) -> Self {
         if at_col_idx == 0 {
@@ -153,7 +261,7 @@ impl Line {
         }
         let remainder = self.string.split_off(byte_len);
         self.rebuild_fragments();
-        Self::from(&remainder)
+        Self::with_tab_width(&remainder, self.tab_width)
     }
     pub fn insert(&mut self, at_col_idx: usize, string: &str) {
         if at_col_idx < self.col_width {
@@ -172,15 +280,8 @@ impl Line {
                 }
                 self.fragments[0..end]
                     .iter()
-                    .fold(String::new(), |mut output, fragment| {
-                        if fragment.grapheme() == "\t" {
-                            let _ = write!(output, "\t");
-                        } else {
-                            let _ = write!(output, "{fragment}");
-                        }
-                        output
-                    })
-                    .to_string()
+                    .map(TextFragment::grapheme)
+                    .collect()
             };
             self.string.insert_str(substr.len(), string);
         } else {
@@ -225,6 +326,7 @@ impl Line {
 
 #[cfg(test)]
 mod tests {
+    use super::super::annotation::AnnotationKind;
     use super::*;
 
     #[test]
@@ -235,13 +337,11 @@ mod tests {
         assert_eq!(line.col_width(), 9);
         assert_eq!(line.grapheme_idx_to_col_idx(5), 5);
         assert_eq!(
-            line.get_fragment_by_col_idx(4)
-                .map_or("", |f| &f.grapheme()),
+            line.get_fragment_by_col_idx(4).map_or("", |f| f.grapheme()),
             "_"
         );
         assert_eq!(
-            line.get_fragment_by_col_idx(5)
-                .map_or("", |f| &f.grapheme()),
+            line.get_fragment_by_col_idx(5).map_or("", |f| f.grapheme()),
             "f"
         );
         assert_eq!(line.get_str_by_col_range(0..0), "");
@@ -262,13 +362,11 @@ mod tests {
         assert_eq!(line.col_width(), 10);
         assert_eq!(line.grapheme_idx_to_col_idx(2), 4);
         assert_eq!(
-            line.get_fragment_by_col_idx(4)
-                .map_or("", |f| &f.grapheme()),
+            line.get_fragment_by_col_idx(4).map_or("", |f| f.grapheme()),
             "に"
         );
         assert_eq!(
-            line.get_fragment_by_col_idx(5)
-                .map_or("", |f| &f.grapheme()),
+            line.get_fragment_by_col_idx(5).map_or("", |f| f.grapheme()),
             "に"
         );
         assert_eq!(line.get_str_by_col_range(2..6), "んに");
@@ -335,6 +433,136 @@ mod tests {
         assert_eq!(line.grapheme_idx_to_col_idx(2), 4);
     }
 
+    #[test]
+    fn test_get_byte_range_by_col_range() {
+        let line = Line::from("aあ\tb");
+        assert_eq!(line.get_byte_range_by_col_range(0..1), 0..1);
+        assert_eq!(line.get_byte_range_by_col_range(2..3), 1..4);
+        assert_eq!(line.get_byte_range_by_col_range(1..4), 1..5);
+        assert_eq!(line.get_byte_range_by_col_range(4..5), 5..6);
+        assert_eq!(line.get_byte_range_by_col_range(8..10), 6..6);
+    }
+
+    #[test]
+    fn test_get_content_by_col_range() {
+        let line = Line::from("a\tこんにちは");
+        assert_eq!(line.col_idx_to_byte_idx(0), 0);
+        assert_eq!(line.col_idx_to_byte_idx(1), 1);
+        assert_eq!(line.col_idx_to_byte_idx(4), 2);
+        assert_eq!(line.col_idx_to_byte_idx(6), 5);
+        assert_eq!(line.col_idx_to_byte_idx(7), 5);
+        assert_eq!(line.col_idx_to_byte_idx(100), line.content().len());
+        assert_eq!(line.get_content_by_col_range(0..4), "a\t");
+        assert_eq!(line.get_content_by_col_range(4..8), "こん");
+        assert_eq!(line.get_content_by_col_range(8..100), "にちは");
+        assert_eq!(line.get_content_by_col_range(8..8), "");
+    }
+
+    #[test]
+    fn test_byte_idx_to_col_idx() {
+        let line = Line::from("a\tこんにちは🇯🇵!");
+        assert_eq!(line.byte_idx_to_col_idx(0), 0);
+        assert_eq!(line.byte_idx_to_col_idx(1), 1);
+        assert_eq!(line.byte_idx_to_col_idx(2), 4);
+        assert_eq!(line.byte_idx_to_col_idx(3), 4);
+        assert_eq!(line.byte_idx_to_col_idx(5), 6);
+        // inside of the flag emoji
+        assert_eq!(line.byte_idx_to_col_idx(21), 14);
+        assert_eq!(line.byte_idx_to_col_idx(25), 16);
+        assert_eq!(line.byte_idx_to_col_idx(26), 17);
+    }
+
+    #[test]
+    fn test_get_spans_by_col_range() {
+        let line = Line::from("a\tこんにちは");
+        let search = Annotation {
+            kind: AnnotationKind::SearchMatch,
+            line_idx: 0,
+            range: 4..8,
+        };
+        let list_chars = ListChars::default();
+        let list = Some(&list_chars);
+        let spans = line.get_spans_by_col_range(0..14, &[&search], list);
+        assert_eq!(
+            spans,
+            vec![
+                Span::plain("a"),
+                Span::non_text("→  "),
+                Span {
+                    text: "こん".to_string(),
+                    kind: Some(AnnotationKind::SearchMatch)
+                },
+                Span::plain("にちは"),
+            ]
+        );
+        let spans = line.get_spans_by_col_range(5..9, &[&search], list);
+        assert_eq!(
+            spans,
+            vec![
+                Span::non_text("«"),
+                Span {
+                    text: "ん".to_string(),
+                    kind: Some(AnnotationKind::SearchMatch)
+                },
+                Span::non_text("»"),
+            ]
+        );
+        // without `list` the markers are blank
+        let spans = line.get_spans_by_col_range(0..9, &[], None);
+        assert_eq!(
+            spans,
+            vec![
+                Span::plain("a"),
+                Span::non_text("   "),
+                Span::plain("こん"),
+                Span::non_text(" "),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_chars() {
+        let list_chars = ListChars::parse("trail:-,eol:$").unwrap();
+        let list = Some(&list_chars);
+        let line = Line::from("a b  ");
+        assert_eq!(
+            line.get_spans_by_col_range(0..5, &[], list),
+            vec![
+                Span::plain("a b"),
+                Span::non_text("--"),
+                Span::non_text("$"),
+            ]
+        );
+        // the end of the line is not shown in a row before it
+        assert_eq!(
+            line.get_spans_by_col_range(0..3, &[], list),
+            vec![Span::plain("a b")]
+        );
+        assert_eq!(
+            Line::from("").get_spans_by_col_range(0..0, &[], list),
+            vec![Span::non_text("$")]
+        );
+        assert_eq!(
+            line.get_spans_by_col_range(0..5, &[], None),
+            vec![Span::plain("a b  ")]
+        );
+    }
+
+    #[test]
+    fn test_set_tab_width() {
+        let mut line = Line::from("\ta\tb");
+        assert_eq!(line.col_width(), 9);
+        line.set_tab_width(8);
+        assert_eq!(line.col_width(), 17);
+        assert_eq!(line.byte_idx_to_col_idx(2), 9);
+        line.set_tab_width(2);
+        assert_eq!(line.col_width(), 5);
+        // the remainder keeps the tab width
+        let remainder = line.split_off(3);
+        assert_eq!(remainder.content(), "\tb");
+        assert_eq!(remainder.col_width(), 3);
+    }
+
     #[test]
     fn test_split_off() {
         let mut line = Line::from("qwert");
@@ -0,0 +1,123 @@
+use std::fmt;
+use unicode_width::UnicodeWidthChar;
+
+// the characters shown for whitespace and invisible characters with `list`
+// an item without a character shows the text as is
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ListChars {
+    // the first column of a tab and the character filling the rest of it
+    pub tab: Option<(char, char)>,
+    // spaces at the end of the line
+    pub trail: Option<char>,
+    // U+00A0 and U+202F
+    pub nbsp: Option<char>,
+    // the full-width space U+3000, followed by a space to fill its width
+    pub ideosp: Option<char>,
+    // characters with no width like U+200B, a space without `list`
+    pub zerowidth: Option<char>,
+    pub eol: Option<char>,
+    // a wide character cut at the right or the left edge of the screen
+    pub extends: Option<char>,
+    pub precedes: Option<char>,
+}
+
+impl Default for ListChars {
+    fn default() -> Self {
+        Self {
+            tab: Some(('→', ' ')),
+            trail: None,
+            nbsp: Some('␣'),
+            ideosp: None,
+            zerowidth: Some('·'),
+            eol: None,
+            extends: Some('»'),
+            precedes: Some('«'),
+        }
+    }
+}
+
+impl ListChars {
+    // `tab:→\ ,trail:·`, the items not given are not shown
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid argument: listchars={value}");
+        let mut list_chars = Self {
+            tab: None,
+            trail: None,
+            nbsp: None,
+            ideosp: None,
+            zerowidth: None,
+            eol: None,
+            extends: None,
+            precedes: None,
+        };
+        for item in value.split(',').filter(|item| !item.is_empty()) {
+            let (name, chars) = item.split_once(':').ok_or_else(invalid)?;
+            let chars: Vec<char> = chars.chars().collect();
+            // every character takes one column
+            if chars.iter().any(|c| c.width() != Some(1)) {
+                return Err(invalid());
+            }
+            match (name, chars.as_slice()) {
+                ("tab", [head, fill]) => list_chars.tab = Some((*head, *fill)),
+                ("trail", [c]) => list_chars.trail = Some(*c),
+                ("nbsp", [c]) => list_chars.nbsp = Some(*c),
+                ("ideosp", [c]) => list_chars.ideosp = Some(*c),
+                ("zerowidth", [c]) => list_chars.zerowidth = Some(*c),
+                ("eol", [c]) => list_chars.eol = Some(*c),
+                ("extends", [c]) => list_chars.extends = Some(*c),
+                ("precedes", [c]) => list_chars.precedes = Some(*c),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(list_chars)
+    }
+}
+
+impl fmt::Display for ListChars {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let mut items = Vec::new();
+        if let Some((head, fill)) = self.tab {
+            items.push(format!("tab:{head}{fill}"));
+        }
+        for (name, c) in [
+            ("trail", self.trail),
+            ("nbsp", self.nbsp),
+            ("ideosp", self.ideosp),
+            ("zerowidth", self.zerowidth),
+            ("eol", self.eol),
+            ("extends", self.extends),
+            ("precedes", self.precedes),
+        ] {
+            if let Some(c) = c {
+                items.push(format!("{name}:{c}"));
+            }
+        }
+        write!(formatter, "{}", items.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let list_chars = ListChars::parse("tab:>-,trail:·,eol:$").unwrap();
+        assert_eq!(list_chars.tab, Some(('>', '-')));
+        assert_eq!(list_chars.trail, Some('·'));
+        assert_eq!(list_chars.eol, Some('$'));
+        assert_eq!(list_chars.nbsp, None);
+        assert_eq!(list_chars.to_string(), "tab:>-,trail:·,eol:$");
+        assert_eq!(
+            ListChars::default().to_string(),
+            "tab:→ ,nbsp:␣,zerowidth:·,extends:»,precedes:«"
+        );
+        assert_eq!(ListChars::parse("").unwrap().tab, None);
+        // a tab needs two characters, and the others one
+        assert!(ListChars::parse("tab:>").is_err());
+        assert!(ListChars::parse("eol:$$").is_err());
+        assert!(ListChars::parse("trail:語").is_err());
+        assert!(ListChars::parse("space:.").is_err());
+        assert!(ListChars::parse("eol").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use super::position::Position;
+
+// the jumplist keeps this many positions
+const MAX_ENTRIES: usize = 100;
+
+// the position after `removed` lines from `line_idx` are replaced with `added` lines
+// None if the line of the position is removed
+pub fn adjust_position(
+    position: Position,
+    line_idx: usize,
+    removed: usize,
+    added: usize,
+) -> Option<Position> {
+    if position.line_idx < line_idx.saturating_add(removed.min(added)) {
+        return Some(position);
+    }
+    if position.line_idx < line_idx.saturating_add(removed) {
+        return None;
+    }
+    Some(Position {
+        line_idx: position
+            .line_idx
+            .saturating_add(added)
+            .saturating_sub(removed),
+        col_idx: position.col_idx,
+    })
+}
+
+// positions to go back and forth, the jumplist and the changelist
+#[derive(Default)]
+pub struct PositionList {
+    entries: Vec<Position>,
+    // the entry of the last `<C-O>` or `g;`, the length of the entries if none
+    idx: usize,
+}
+
+impl PositionList {
+    // a jump forgets the older entries on the same line
+    pub fn push_jump(&mut self, position: Position) {
+        self.entries
+            .retain(|entry| entry.line_idx != position.line_idx);
+        self.entries.push(position);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.idx = self.entries.len();
+    }
+    // changes on the same line are merged
+    pub fn push_change(&mut self, position: Position) {
+        match self.entries.last_mut() {
+            Some(last) if last.line_idx == position.line_idx => *last = position,
+            _ => self.entries.push(position),
+        }
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        self.idx = self.entries.len();
+    }
+    // `<C-O>` remembers the current position first, so that `<C-I>` can come back
+    pub fn jump_older(&mut self, current: Position, count: usize) -> Option<Position> {
+        if self.idx >= self.entries.len() {
+            self.push_jump(current);
+            self.idx = self.entries.len().saturating_sub(1);
+        }
+        self.older(count)
+    }
+    pub fn older(&mut self, count: usize) -> Option<Position> {
+        self.idx = self.idx.checked_sub(count)?;
+        self.entries.get(self.idx).copied()
+    }
+    pub fn newer(&mut self, count: usize) -> Option<Position> {
+        let idx = self.idx.saturating_add(count);
+        let entry = self.entries.get(idx).copied()?;
+        self.idx = idx;
+        Some(entry)
+    }
+    pub fn adjust(&mut self, line_idx: usize, removed: usize, added: usize) {
+        let mut idx = self.idx;
+        let entries = std::mem::take(&mut self.entries);
+        for (entry_idx, entry) in entries.into_iter().enumerate() {
+            match adjust_position(entry, line_idx, removed, added) {
+                Some(position) => self.entries.push(position),
+                None if entry_idx < self.idx => idx = idx.saturating_sub(1),
+                None => (),
+            }
+        }
+        self.idx = idx;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adjust_position() {
+        let position = Position::new(5, 3);
+        assert_eq!(adjust_position(position, 7, 1, 0), Some(position));
+        assert_eq!(
+            adjust_position(position, 2, 0, 2),
+            Some(Position::new(7, 3))
+        );
+        assert_eq!(
+            adjust_position(position, 2, 2, 0),
+            Some(Position::new(3, 3))
+        );
+        assert_eq!(adjust_position(position, 4, 2, 0), None);
+        assert_eq!(adjust_position(position, 4, 3, 1), None);
+        assert_eq!(adjust_position(position, 5, 1, 2), Some(position));
+    }
+
+    #[test]
+    fn test_jump() {
+        let mut jumps = PositionList::default();
+        jumps.push_jump(Position::new(1, 0));
+        jumps.push_jump(Position::new(5, 0));
+        jumps.push_jump(Position::new(1, 2));
+        assert_eq!(
+            jumps.jump_older(Position::new(9, 0), 1),
+            Some(Position::new(1, 2))
+        );
+        assert_eq!(jumps.older(1), Some(Position::new(5, 0)));
+        assert_eq!(jumps.older(1), None);
+        assert_eq!(jumps.newer(2), Some(Position::new(9, 0)));
+        assert_eq!(jumps.newer(1), None);
+    }
+
+    #[test]
+    fn test_change() {
+        let mut changes = PositionList::default();
+        changes.push_change(Position::new(1, 0));
+        changes.push_change(Position::new(1, 4));
+        changes.push_change(Position::new(3, 0));
+        assert_eq!(changes.older(1), Some(Position::new(3, 0)));
+        assert_eq!(changes.older(1), Some(Position::new(1, 4)));
+        assert_eq!(changes.older(1), None);
+        changes.adjust(0, 0, 2);
+        assert_eq!(changes.newer(1), Some(Position::new(5, 0)));
+    }
+}
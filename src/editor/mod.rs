@@ -1,26 +1,64 @@
 // 日本語🇯🇵の表示テスト
 use std::io::Error;
 use terminal::{CursorStyle, Event, KeyCode, KeyEvent, KeyModifiers, Terminal};
+mod annotation;
+mod bracket;
 mod terminal;
 use buffer::Buffer;
 mod buffer;
+use char_class::CharClass;
+mod char_class;
 use position::Position;
 mod position;
 use command_bar::CommandBar;
 mod command_bar;
 mod size;
-use line::Line;
+use cursor::CharSearch;
 use size::Size;
 use view::{MoveCode, ScrollCode, View};
 mod cursor;
 use status_bar::{DocumentStatus, StatusBar};
 mod file_info;
+mod gutter;
 use file_info::FileType;
+mod highlight;
+mod history;
+mod indent;
+mod key_notation;
+mod marks;
+use operator::{Operator, TextRange};
+mod operator;
+mod options;
+use registers::{Register, RegisterKind, Registers};
+mod registers;
+use screen::Screen;
+mod screen;
+use search::SearchDirection;
+mod search;
+use selection::{next_position, Selection, SelectionKind};
+mod selection;
+use substitute::Substitution;
+mod ex;
+use ex::{AddressContext, ExCommand};
+mod substitute;
+mod syntax;
+use theme::{ColorDepth, HighlightGroup, Theme};
+mod theme;
 use regex::Regex;
 mod line;
+mod list_chars;
 mod status_bar;
 mod text_fragment;
+mod text_object;
 mod view;
+mod wrap;
+
+// a macro which runs itself is stopped at this depth
+const MAX_MACRO_DEPTH: usize = 100;
+// keys starting a motion of two keys like `gg`, `fx` or `'a`
+const MOTION_PREFIXES: [&str; 7] = ["g", "f", "F", "t", "T", "'", "`"];
+// keys starting a text object like `iw` after an operator or in the visual mode
+const OBJECT_PREFIXES: [&str; 2] = ["i", "a"];
 
 pub const NAME: &str = env!("CARGO_PKG_NAME");
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -34,6 +72,17 @@ pub enum Mode {
     Insert,
     Command,
     Search,
+    Visual,
+    VisualLine,
+    VisualBlock,
+}
+
+// the state of the insert session started by `c` in the blockwise visual mode
+struct BlockInsert {
+    lines: std::ops::Range<usize>,
+    col_idx: usize,
+    // the first line at the start of the insert session
+    original: String,
 }
 
 // 将来的にはEditorは複数のViewとBufferを持つ
@@ -47,11 +96,49 @@ pub struct Editor {
     current_view_idx: usize,
     mode: Mode,
     size: Size,
-    #[allow(dead_code)]
-    register: Option<String>,
+    // keys waiting for the next key, like "{register}
+    pending_prefix: Option<String>,
+    pending_operator: Option<Operator>,
+    pending_register: Option<char>,
+    // the count typed before the command, and the count typed before the operator
+    pending_count: Option<usize>,
+    operator_count: Option<usize>,
+    // the count of the command in progress, remembered for `.` with the keys
+    command_count: Option<usize>,
+    registers: Registers,
+    search_direction: SearchDirection,
+    // the cursor position when the search started
+    search_origin: Position,
+    // :s with the confirm flag waiting for the answer
+    substitution: Option<Substitution>,
+    // keys of :normal are running, the whole command is one undo step
+    running_normal: bool,
+    // keys of the command in progress
+    command_keys: Vec<(KeyCode, KeyModifiers)>,
+    // keys of the last command which changed the buffer, replayed by `.`
+    last_change: Vec<(KeyCode, KeyModifiers)>,
+    last_change_count: Option<usize>,
+    // `.` is running
+    repeating: bool,
+    // the register a macro is being recorded into, and the keys recorded so far
+    recording: Option<char>,
+    recorded_keys: String,
+    // the register of the last `@`, for `@@`
+    last_macro: Option<char>,
+    // nesting of the running macros
+    macro_depth: usize,
+    // the last `f`, `F`, `t` or `T`, repeated by `;` and `,`
+    last_char_search: Option<CharSearch>,
+    // the change of a block, the text inserted to the first line is copied to the other lines
+    block_insert: Option<BlockInsert>,
+    // the insert session started with a count, the typed keys are inserted count times
+    // the flag is set when each repetition goes to a new line like `3o`
+    insert_repeat: Option<(usize, bool)>,
+    insert_keys: Vec<(KeyCode, KeyModifiers)>,
     message: Option<String>,
     command_bar: Option<CommandBar>,
     status_bar: StatusBar,
+    screen: Screen,
 }
 
 impl Editor {
@@ -89,6 +176,8 @@ impl Editor {
         editor.size = size;
         editor.message = message;
         editor.status_bar = StatusBar::new(size.width);
+        editor.screen.set_color_depth(ColorDepth::detect());
+        editor.screen.resize(size);
         Ok(editor)
     }
 
@@ -115,13 +204,7 @@ impl Editor {
                 Ok(Event::Key(KeyEvent {
                     code, modifiers, ..
                 })) => {
-                    match self.mode {
-                        Mode::Normal => self.handle_key_event_normal(code, modifiers),
-                        Mode::Insert => self.handle_key_event_insert(code, modifiers),
-                        Mode::Command | Mode::Search => {
-                            self.handle_key_event_command(code, modifiers)
-                        }
-                    }
+                    self.handle_key_event(code, modifiers);
                     // if last_cursor != self.current_view().cursor {
                     //     cursor moved
                     // }
@@ -140,6 +223,76 @@ impl Editor {
         }
     }
 
+    fn handle_key_event(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        if !self.repeating {
+            // a key in normal mode with nothing pending starts a new command
+            if self.mode == Mode::Normal
+                && self.pending_prefix.is_none()
+                && self.pending_operator.is_none()
+                && self.pending_register.is_none()
+                && self.pending_count.is_none()
+            {
+                self.command_keys.clear();
+            }
+            self.command_keys.push((code, modifiers));
+        }
+        // keys replayed by `.` and `:normal` are already in the recording
+        if self.recording.is_some()
+            && self.macro_depth == 0
+            && !self.repeating
+            && !self.running_normal
+        {
+            match Editor::key_to_string(code, modifiers).as_str() {
+                "<" => self.recorded_keys.push_str("<LT>"),
+                key_repr => self.recorded_keys.push_str(key_repr),
+            }
+        }
+        match self.mode {
+            Mode::Normal => self.handle_key_event_normal(code, modifiers),
+            Mode::Insert => self.handle_key_event_insert(code, modifiers),
+            Mode::Command | Mode::Search => {
+                self.handle_key_event_command(code, modifiers);
+            }
+            Mode::Visual | Mode::VisualLine | Mode::VisualBlock => {
+                self.handle_key_event_visual(code, modifiers);
+            }
+        }
+    }
+
+    // close the current undo step, unless :normal is running
+    // returns true if the step has changes
+    fn commit_history(&mut self) -> bool {
+        !self.running_normal && self.current_view_mut().buffer.commit_history()
+    }
+
+    // the end of a normal, visual or insert command
+    // the keys of the command are remembered for `.` if it changed the buffer
+    fn finish_command(&mut self) {
+        let keys = std::mem::take(&mut self.command_keys);
+        let count = self.command_count.take();
+        if self.commit_history() && !self.repeating {
+            self.last_change = keys;
+            self.last_change_count = count;
+        }
+    }
+
+    // replay the last change at the cursor
+    // a count replaces the count of the last change
+    fn repeat_last_change(&mut self, count: Option<usize>) {
+        if self.repeating {
+            return;
+        }
+        if count.is_some() {
+            self.last_change_count = count;
+        }
+        self.repeating = true;
+        self.pending_count = self.last_change_count;
+        for (code, modifiers) in self.last_change.clone() {
+            self.handle_key_event(code, modifiers);
+        }
+        self.repeating = false;
+    }
+
     fn key_to_string(code: KeyCode, modifiers: KeyModifiers) -> String {
         let mut result = match code {
             KeyCode::Char(' ') => "Space".to_string(),
@@ -151,7 +304,8 @@ impl Editor {
             _ => format!("{code:?}"),
         };
 
-        if modifiers.contains(KeyModifiers::SHIFT) && result.len() > 1 {
+        // count characters, not bytes, so that a key like `本` stays as it is
+        if modifiers.contains(KeyModifiers::SHIFT) && result.chars().count() > 1 {
             result.insert_str(0, "S-");
         }
         if modifiers.contains(KeyModifiers::ALT) {
@@ -161,7 +315,7 @@ impl Editor {
             result.insert_str(0, "C-");
         }
 
-        if result.len() > 1 {
+        if result.chars().count() > 1 {
             format!("<{}>", result.to_uppercase())
         } else {
             result
@@ -172,16 +326,21 @@ impl Editor {
         self.mode = mode;
 
         match self.mode {
-            Mode::Normal => {
+            Mode::Normal | Mode::Visual | Mode::VisualLine | Mode::VisualBlock => {
                 Terminal::set_cursor_style(CursorStyle::DefaultUserShape).unwrap();
                 self.command_bar = None;
             }
             Mode::Insert => {
                 Terminal::set_cursor_style(CursorStyle::SteadyBar).unwrap();
             }
-            Mode::Command | Mode::Search => {
+            Mode::Command => {
+                Terminal::set_cursor_style(CursorStyle::SteadyBar).unwrap();
+                self.command_bar = Some(CommandBar::new(self.mode, ":"));
+            }
+            Mode::Search => {
                 Terminal::set_cursor_style(CursorStyle::SteadyBar).unwrap();
-                self.command_bar = Some(CommandBar::new(self.mode));
+                let prompt = self.search_direction.prompt();
+                self.command_bar = Some(CommandBar::new(self.mode, prompt));
             }
         }
     }
@@ -206,169 +365,821 @@ impl Editor {
         ));
     }
 
+    // digits before a command are its count, `0` is a motion unless a count is pending
+    // returns true if the key is a part of the count
+    fn accumulate_count(&mut self, key_repr: &str) -> bool {
+        let mut chars = key_repr.chars();
+        let Some(digit) = (match (chars.next(), chars.next()) {
+            (Some(c), None) => c.to_digit(10),
+            _ => None,
+        }) else {
+            return false;
+        };
+        if digit == 0 && self.pending_count.is_none() {
+            return false;
+        }
+        let digit = usize::try_from(digit).unwrap_or_default();
+        self.pending_count = Some(
+            self.pending_count
+                .unwrap_or_default()
+                .saturating_mul(10)
+                .saturating_add(digit),
+        );
+        // `.` replays the keys without the count, so that a new count can replace it
+        if !self.repeating {
+            self.command_keys.pop();
+        }
+        true
+    }
+
+    // the count of the command, the counts before and after the operator are multiplied
+    fn take_count(&mut self) -> Option<usize> {
+        let count = match (self.operator_count.take(), self.pending_count.take()) {
+            (Some(a), Some(b)) => Some(a.saturating_mul(b)),
+            (a, b) => a.or(b),
+        };
+        if count.is_some() {
+            self.command_count = count;
+        }
+        count
+    }
+
+    // the keys of the command in progress, shown in the status bar
+    fn pending_keys(&self) -> String {
+        let mut keys = String::new();
+        if let Some(name) = self.pending_register {
+            keys.push('"');
+            keys.push(name);
+        }
+        if let Some(count) = self.operator_count {
+            keys.push_str(&count.to_string());
+        }
+        if let Some(operator) = self.pending_operator {
+            keys.push_str(operator.key());
+        }
+        if let Some(count) = self.pending_count {
+            keys.push_str(&count.to_string());
+        }
+        if let Some(prefix) = &self.pending_prefix {
+            keys.push_str(prefix);
+        }
+        keys
+    }
+
     fn handle_key_event_normal(&mut self, code: KeyCode, modifiers: KeyModifiers) {
         let key_repr: &str = &Editor::key_to_string(code, modifiers);
+        if let Some(prefix) = self.pending_prefix.take() {
+            match prefix.as_str() {
+                "\"" => {
+                    // the register is used by the following command
+                    self.select_register(key_repr);
+                    return;
+                }
+                "m" => self.set_mark(key_repr),
+                "q" => self.start_recording(key_repr),
+                "@" => self.play_macro(key_repr),
+                "z" => {
+                    let count = self.take_count();
+                    self.scroll_cursor_line(key_repr, count);
+                }
+                "g" | "f" | "F" | "t" | "T" | "'" | "`" | "i" | "a" => {
+                    let keys = format!("{prefix}{key_repr}");
+                    if let Some(operator) = self.pending_operator.take() {
+                        self.handle_key_event_operator_pending(operator, &keys);
+                    } else {
+                        let count = self.take_count();
+                        self.handle_key_event_normal_move(&keys, count);
+                    }
+                }
+                _ => (),
+            }
+        } else if self.accumulate_count(key_repr) {
+            return;
+        } else if let Some(operator) = self.pending_operator.take() {
+            self.handle_key_event_operator_pending(operator, key_repr);
+        } else if let Some(operator) = Operator::from_key(key_repr) {
+            self.pending_operator = Some(operator);
+            self.operator_count = self.pending_count.take();
+        } else {
+            let count = self.take_count();
+            self.handle_key_event_normal_command(key_repr, count);
+        }
+        if self.pending_prefix.is_some() || self.pending_operator.is_some() {
+            return;
+        }
+        self.pending_register = None;
+        self.pending_count = None;
+        self.operator_count = None;
+        // one normal command is one undo step
+        // but the step continues to the end of the insert session
+        if self.mode == Mode::Normal {
+            self.finish_command();
+        }
+    }
+
+    // an uppercase mark is a file mark, which is in only one buffer
+    fn set_mark(&mut self, key_repr: &str) {
+        let mut chars = key_repr.chars();
+        let (Some(name), None) = (chars.next(), chars.next()) else {
+            return;
+        };
+        if !name.is_ascii_alphabetic() && !"'`[]<>".contains(name) {
+            return;
+        }
+        if name.is_ascii_uppercase() {
+            for view in &mut self.views {
+                view.buffer.remove_mark(name);
+            }
+        }
+        let position = self.current_view().cursor.position();
+        self.current_view_mut().buffer.set_mark(name, position);
+    }
+
+    // `'A` goes to the buffer which has the file mark
+    fn switch_to_file_mark(&mut self, key_repr: &str) {
+        let mut chars = key_repr.chars();
+        if let (Some('\'' | '`'), Some(name @ 'A'..='Z'), None) =
+            (chars.next(), chars.next(), chars.next())
+        {
+            if let Some(idx) = self
+                .views
+                .iter()
+                .position(|view| view.buffer.get_mark(name).is_some())
+            {
+                self.current_view_idx = idx;
+            }
+        }
+    }
+
+    // remember the position before a jump for `<C-O>` and `` ` ``
+    fn push_jump(&mut self, from: Position) {
+        let buffer = &mut self.current_view_mut().buffer;
+        buffer.set_mark('`', from);
+        buffer.jumplist.push_jump(from);
+    }
+
+    // `<C-O>`, `<C-I>`, `g;` and `g,`
+    fn go_to_older_or_newer(&mut self, key_repr: &str, count: usize) {
+        let view = self.current_view_mut();
+        let cursor = view.cursor.position();
+        let (target, message) = match key_repr {
+            "<C-O>" => (view.buffer.jumplist.jump_older(cursor, count), None),
+            "g;" => (
+                view.buffer.changelist.older(count),
+                Some("At start of changelist"),
+            ),
+            "g," => (
+                view.buffer.changelist.newer(count),
+                Some("At end of changelist"),
+            ),
+            _ => (view.buffer.jumplist.newer(count), None),
+        };
+        match (target, message) {
+            (Some(target), _) => view.set_cursor_position(target),
+            (None, Some(message)) => self.set_message(message),
+            (None, None) => (),
+        }
+    }
+
+    // `[` and `]` are the first and the last character of the changed or yanked text
+    fn set_change_marks(&mut self, range: &TextRange) {
+        let (start, last) = match range {
+            TextRange::Charwise { start, end } => (
+                *start,
+                Position {
+                    line_idx: end.line_idx,
+                    col_idx: end.col_idx.saturating_sub(1),
+                },
+            ),
+            TextRange::Linewise(lines) => (
+                range.start(),
+                Position {
+                    line_idx: lines.end.saturating_sub(1),
+                    col_idx: usize::MAX,
+                },
+            ),
+            TextRange::Blockwise { lines, cols } => (
+                range.start(),
+                Position {
+                    line_idx: lines.end.saturating_sub(1),
+                    col_idx: cols.end.saturating_sub(1),
+                },
+            ),
+        };
+        let buffer = &mut self.current_view_mut().buffer;
+        buffer.set_mark('[', start);
+        buffer.set_mark(']', last);
+    }
+
+    fn start_recording(&mut self, key_repr: &str) {
+        let mut chars = key_repr.chars();
+        if let (Some(name), None) = (chars.next(), chars.next()) {
+            if name.is_ascii_alphanumeric() || name == registers::UNNAMED {
+                self.recording = Some(name);
+                self.recorded_keys.clear();
+            }
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        let Some(name) = self.recording.take() else {
+            return;
+        };
+        // the `q` which stops the recording is not a part of the macro
+        let keys = std::mem::take(&mut self.recorded_keys);
+        let keys = keys.strip_suffix('q').unwrap_or(&keys);
+        self.registers.record(name, keys);
+    }
+
+    // run the keys in the register, `@@` runs the last one again
+    fn play_macro(&mut self, key_repr: &str) {
+        let mut chars = key_repr.chars();
+        let name = match (chars.next(), chars.next()) {
+            (Some('@'), None) => self.last_macro,
+            (Some(name), None) => Some(name),
+            _ => None,
+        };
+        let Some(name) = name else {
+            self.set_message("No previously used register");
+            return;
+        };
+        let Some(register) = self.get_register(name) else {
+            self.set_message(&format!("Nothing in register {name}"));
+            return;
+        };
+        if self.macro_depth >= MAX_MACRO_DEPTH {
+            self.set_message("Macro nested too deeply");
+            return;
+        }
+        self.last_macro = Some(name);
+        let count = self.take_count().unwrap_or(1);
+        self.macro_depth = self.macro_depth.saturating_add(1);
+        for _ in 0..count {
+            if name == registers::LAST_COMMAND {
+                // `@:` runs the last command line again
+                self.run_command(&register.content);
+            } else {
+                for (code, modifiers) in key_notation::parse_keys(&register.content) {
+                    self.handle_key_event(code, modifiers);
+                }
+            }
+        }
+        self.macro_depth = self.macro_depth.saturating_sub(1);
+    }
+
+    fn select_register(&mut self, key_repr: &str) {
+        let mut chars = key_repr.chars();
+        match (chars.next(), chars.next()) {
+            (Some(name), None) if Registers::is_valid_name(name) => {
+                self.pending_register = Some(name);
+            }
+            _ => self.pending_register = None,
+        }
+    }
+
+    fn get_register(&self, name: char) -> Option<Register> {
+        if name == registers::FILE_NAME {
+            return self
+                .current_view()
+                .buffer
+                .file_info
+                .get_file_name()
+                .map(|file_name| Register::charwise(&file_name));
+        }
+        self.registers.get(name)
+    }
+
+    // put the register content after the cursor, or before the cursor if `after` is false
+    fn put(&mut self, after: bool, count: usize) {
+        let name = self.pending_register.unwrap_or(registers::UNNAMED);
+        let Some(register) = self.get_register(name) else {
+            self.set_message(&format!("Nothing in register {name}"));
+            return;
+        };
+        let register = register.repeat(count);
+        let view = self.current_view_mut();
+        let cursor = view.cursor.position();
+        // the position to put charwise text
+        let at = if after {
+            view.get_move_target(MoveCode::Right).min(Position {
+                line_idx: cursor.line_idx,
+                col_idx: view.buffer.get_line_col_width(cursor.line_idx),
+            })
+        } else {
+            cursor
+        };
+        let lines = register.lines();
+        let put_range = match register.kind {
+            RegisterKind::Linewise => {
+                let line_idx = if after {
+                    cursor.line_idx.saturating_add(1)
+                } else {
+                    cursor.line_idx
+                };
+                view.buffer.insert_lines(&lines, line_idx);
+                view.set_cursor_position(Position {
+                    line_idx,
+                    col_idx: 0,
+                });
+                view.move_position(MoveCode::FirstNonBlank);
+                TextRange::Linewise(line_idx..line_idx.saturating_add(lines.len()))
+            }
+            RegisterKind::Blockwise => {
+                view.buffer.insert_block(&lines, at);
+                view.set_cursor_position(at);
+                let width = lines
+                    .iter()
+                    .map(|line| view.buffer.new_line(line).col_width())
+                    .max()
+                    .unwrap_or(0);
+                TextRange::Blockwise {
+                    lines: at.line_idx..at.line_idx.saturating_add(lines.len()),
+                    cols: at.col_idx..at.col_idx.saturating_add(width),
+                }
+            }
+            RegisterKind::Charwise => {
+                let end = view.buffer.insert_text(&register.content, at);
+                if end.line_idx == at.line_idx {
+                    // on the last character of the inserted text
+                    view.set_cursor_position(Position {
+                        line_idx: end.line_idx,
+                        col_idx: end.col_idx.saturating_sub(1),
+                    });
+                } else {
+                    view.set_cursor_position(at);
+                }
+                TextRange::Charwise { start: at, end }
+            }
+        };
+        self.set_change_marks(&put_range);
+    }
+
+    fn handle_key_event_normal_command(&mut self, key_repr: &str, count: Option<usize>) {
+        let times = count.unwrap_or(1);
         match key_repr {
-            "q" => self.quit_with_confirm(),
+            "q" if self.recording.is_some() => self.stop_recording(),
             "Q" => self.should_quit = true,
-            "i" => self.set_mode(Mode::Insert),
+            "i" => self.start_insert(count, false),
             "a" => {
                 self.current_view_mut().move_position(MoveCode::Right);
-                self.set_mode(Mode::Insert);
+                self.start_insert(count, false);
             }
             "I" => {
                 self.current_view_mut().move_position(MoveCode::FirstChar);
-                self.set_mode(Mode::Insert);
+                self.start_insert(count, false);
             }
             "A" => {
                 self.current_view_mut().move_position(MoveCode::LastChar);
-                self.set_mode(Mode::Insert);
+                self.start_insert(count, false);
             }
             "o" => {
                 self.current_view_mut().move_position(MoveCode::LastChar);
-                self.set_mode(Mode::Insert);
+                self.start_insert(count, true);
                 self.current_view_mut().insert_char('\n');
             }
             "O" => {
                 self.current_view_mut().move_position(MoveCode::FirstChar);
-                self.set_mode(Mode::Insert);
+                self.start_insert(count, true);
                 self.current_view_mut().insert_char_without_move('\n');
             }
-            "c" => {
-                // TODO: implement c{motion}
-                // it should be cc
-                let line_idx = self.current_view_mut().cursor.line_idx();
-                self.current_view_mut().buffer.set_line("", line_idx);
-                self.current_view_mut().move_position(MoveCode::FirstChar);
-                self.set_mode(Mode::Insert);
-            }
-            "d" => {
-                // TODO: implement d{motion}
-                // it should be dd
-                let line_idx = self.current_view_mut().cursor.line_idx();
-                self.current_view_mut().buffer.set_line("", line_idx);
-                self.current_view_mut().move_position(MoveCode::FirstChar);
-            }
-            "C" => {
-                let at = self.current_view_mut().cursor.position();
-                self.current_view_mut().buffer.cutoff_line(at);
-                self.current_view_mut().move_position(MoveCode::LastChar);
-                self.set_mode(Mode::Insert);
+            "C" | "D" | "x" => {
+                let view = self.current_view();
+                let from = view.cursor.position();
+                let (operator, code) = match key_repr {
+                    "C" => (Operator::Change, MoveCode::LastChar),
+                    "D" => (Operator::Delete, MoveCode::LastChar),
+                    _ => (Operator::Delete, MoveCode::Right),
+                };
+                let mut to = view.get_move_target_by(code, times);
+                if code == MoveCode::Right {
+                    // `3x` does not go beyond the end of the line
+                    to = to.min(Position {
+                        line_idx: from.line_idx,
+                        col_idx: view.buffer.get_line_col_width(from.line_idx),
+                    });
+                }
+                let range = TextRange::charwise(from, to);
+                self.apply_operator(operator, &range);
             }
-            "D" => {
-                let at = self.current_view_mut().cursor.position();
-                self.current_view_mut().buffer.cutoff_line(at);
-                self.current_view_mut().move_position(MoveCode::LastChar);
+            "v" => self.start_visual(SelectionKind::Charwise),
+            "V" => self.start_visual(SelectionKind::Linewise),
+            "<C-V>" => self.start_visual(SelectionKind::Blockwise),
+            "." => self.repeat_last_change(count),
+            "p" => self.put(true, times),
+            "P" => self.put(false, times),
+            "\"" | "m" | "q" | "@" | "z" | "g" | "f" | "F" | "t" | "T" | "'" | "`" => {
+                self.pending_prefix = Some(key_repr.to_string());
+                // the count is used by the command after the prefix like `3@a`
+                self.pending_count = count;
             }
-            "p" => {
-                let str = "sample_str";
-                self.current_view_mut().move_position(MoveCode::Right);
-                let at = self.current_view_mut().cursor.position();
-                self.current_view_mut().buffer.insert(str, at);
-                let count = Line::string_to_graphemes(str).count();
-                for _ in 0..count.saturating_sub(1) {
-                    self.current_view_mut().move_position(MoveCode::Right);
+            "u" => {
+                if !(0..times).fold(false, |undone, _| self.current_view_mut().undo() || undone) {
+                    self.set_message("Already at oldest change");
                 }
             }
-            "P" => {
-                let str = "sample_str";
-                let at = self.current_view_mut().cursor.position();
-                self.current_view_mut().buffer.insert(str, at);
-                let count = Line::string_to_graphemes(str).count();
-                for _ in 0..count {
-                    self.current_view_mut().move_position(MoveCode::Right);
+            "<C-R>" => {
+                if !(0..times).fold(false, |redone, _| self.current_view_mut().redo() || redone) {
+                    self.set_message("Already at newest change");
                 }
             }
-            "x" => self.current_view_mut().remove_char(),
             "<C-G>" => self.show_cursor_info(),
             "<C-S>" => self.save(),
-            ":" => self.set_mode(Mode::Command),
-            "/" => self.set_mode(Mode::Search),
-            "n" => {
-                self.set_message("TODO: search next");
+            ":" => {
+                self.set_mode(Mode::Command);
+                // `3:` starts with the range of 3 lines
+                if let Some(count) = count {
+                    let range = if count > 1 {
+                        format!(".,.+{}", count.saturating_sub(1))
+                    } else {
+                        ".".to_string()
+                    };
+                    if let Some(command_bar) = self.command_bar.as_mut() {
+                        range.chars().for_each(|c| command_bar.insert(c));
+                    }
+                }
+            }
+            "/" => self.start_search(SearchDirection::Forward),
+            "?" => self.start_search(SearchDirection::Backward),
+            "n" => (0..times).for_each(|_| self.search_next(self.search_direction)),
+            "N" => (0..times).for_each(|_| self.search_next(self.search_direction.reverse())),
+            // TODO this should be in user local config
+            "<CR>"
+                if self.current_view().buffer.file_info.get_file_type()
+                    == Some(FileType::Gitcommit) =>
+            {
+                self.start_gitcommit_message();
+            }
+            _ => self.handle_key_event_normal_move(key_repr, count),
+        }
+    }
+
+    // start the insert mode, the typed text is repeated on <Esc> if the count is given
+    fn start_insert(&mut self, count: Option<usize>, new_line: bool) {
+        self.insert_repeat = count
+            .filter(|count| *count > 1)
+            .map(|count| (count, new_line));
+        self.insert_keys.clear();
+        let view = self.current_view_mut();
+        let cursor = view.cursor.position();
+        view.buffer.set_mark('[', cursor);
+        self.set_mode(Mode::Insert);
+    }
+
+    // `3ix<Esc>` inserts the typed keys 2 more times
+    fn repeat_insert(&mut self) {
+        let Some((count, new_line)) = self.insert_repeat.take() else {
+            return;
+        };
+        let keys = std::mem::take(&mut self.insert_keys);
+        for _ in 1..count {
+            if new_line {
+                self.handle_key_event_insert(KeyCode::Enter, KeyModifiers::NONE);
+            }
+            for (code, modifiers) in &keys {
+                self.handle_key_event_insert(*code, *modifiers);
+            }
+        }
+    }
+
+    fn handle_key_event_operator_pending(&mut self, operator: Operator, key_repr: &str) {
+        if MOTION_PREFIXES.contains(&key_repr) || OBJECT_PREFIXES.contains(&key_repr) {
+            // wait for the rest of the motion like `dgg` or `dfx`, or the text object like `diw`
+            self.pending_operator = Some(operator);
+            self.pending_prefix = Some(key_repr.to_string());
+            return;
+        }
+        let count = self.take_count();
+        let from = self.current_view().cursor.position();
+        let range = if Operator::from_key(key_repr) == Some(operator) {
+            let view = self.current_view();
+            // doubled operator like dd, cc, yy works on the current line and count-1 lines below
+            let last_line_idx = view.buffer.get_lines_count().saturating_sub(1);
+            let to = from
+                .line_idx
+                .saturating_add(count.unwrap_or(1).saturating_sub(1))
+                .min(last_line_idx);
+            TextRange::linewise(from.line_idx, to)
+        } else if let Some(range) = text_object::select(
+            key_repr,
+            from,
+            &self.current_view().buffer,
+            count.unwrap_or(1),
+        ) {
+            range
+        } else if let Some(code) = self.key_to_move_code(key_repr, count) {
+            let view = self.current_view();
+            let code = match code {
+                // `cw` on a word works like `ce`, but stays in a word of one character
+                MoveCode::NextWordStart(bigword)
+                    if operator == Operator::Change
+                        && view.get_fragment_by_position(from).is_some_and(|fragment| {
+                            !CharClass::of(fragment.grapheme(), bigword).is_blank()
+                        }) =>
+                {
+                    MoveCode::CurrentWordEnd(bigword)
+                }
+                code => code,
+            };
+            let mut to = view.get_move_target_by(code, count.unwrap_or(1));
+            if code == MoveCode::MatchingBracket && to == from {
+                // `d%` without a bracket does nothing
+                return;
+            }
+            if let MoveCode::FindChar(search) = code {
+                let mut cursor = view.cursor;
+                if !cursor.move_to_char(search, count.unwrap_or(1), &view.buffer) {
+                    // `dfz` without a `z` does nothing
+                    return;
+                }
+            }
+            if code.is_linewise() {
+                TextRange::linewise(from.line_idx, to.line_idx)
+            } else {
+                if matches!(code, MoveCode::NextWordStart(_))
+                    && to.line_idx > from.line_idx
+                    && view
+                        .get_line(to.line_idx)
+                        .is_some_and(|line| to.col_idx <= line.indent_width())
+                {
+                    // `dw` on the last word of the line stops at the end of the line
+                    let line_idx = to.line_idx.saturating_sub(1);
+                    to = Position {
+                        line_idx,
+                        col_idx: view.buffer.get_line_col_width(line_idx),
+                    };
+                }
+                let (start, end) = (from.min(to), from.max(to));
+                let end = if code.is_inclusive() {
+                    next_position(end, &view.buffer)
+                } else {
+                    end
+                };
+                TextRange::Charwise { start, end }
+            }
+        } else {
+            // <Esc> or any other key cancels the operator
+            return;
+        };
+        self.apply_operator(operator, &range);
+    }
+
+    fn apply_operator(&mut self, operator: Operator, range: &TextRange) {
+        if matches!(
+            operator,
+            Operator::ShiftRight | Operator::ShiftLeft | Operator::Reindent
+        ) {
+            let lines = range.lines();
+            if operator == Operator::Reindent {
+                self.reindent_lines(lines.clone());
+            } else {
+                self.shift_lines(lines.clone(), 1, operator == Operator::ShiftRight);
+            }
+            self.set_change_marks(&TextRange::Linewise(lines.clone()));
+            self.go_to_line(lines.start);
+            return;
+        }
+        let cursor = self.current_view().cursor.position();
+        let buffer = &self.current_view().buffer;
+        let register = match range {
+            TextRange::Charwise { start, end } => {
+                Register::charwise(&buffer.get_text(*start, *end))
+            }
+            TextRange::Linewise(lines) => Register::linewise(&buffer.get_lines(lines.clone())),
+            TextRange::Blockwise { lines, cols } => {
+                Register::blockwise(&buffer.get_block(lines.clone(), cols.clone()))
+            }
+        };
+        let name = self.pending_register.take();
+        let stored = if operator == Operator::Yank {
+            self.registers.yank(name, register)
+        } else {
+            self.registers.delete(name, register)
+        };
+        if !stored {
+            self.set_message("Cannot write to the read-only register");
+            return;
+        }
+        if operator == Operator::Yank {
+            self.set_change_marks(range);
+        } else {
+            let start = range.start();
+            self.set_change_marks(&TextRange::charwise(start, start));
+        }
+        let start = range.start();
+        let view = self.current_view_mut();
+        match (operator, range) {
+            (Operator::Yank, TextRange::Charwise { .. } | TextRange::Blockwise { .. }) => {
+                view.set_cursor_position(start);
+            }
+            (Operator::Yank, TextRange::Linewise(_)) => {
+                view.set_cursor_position(Position {
+                    line_idx: start.line_idx,
+                    col_idx: cursor.col_idx,
+                });
             }
-            "N" => {
-                self.set_message("TODO: search prev");
+            (Operator::Delete | Operator::Change, TextRange::Charwise { start, end }) => {
+                view.buffer.delete_range(*start, *end);
+                view.set_cursor_position(*start);
+            }
+            (Operator::Delete, TextRange::Linewise(lines)) => {
+                view.buffer.delete_lines(lines.clone());
+                view.set_cursor_position(start);
+                view.move_position(MoveCode::FirstNonBlank);
+            }
+            (Operator::Delete, TextRange::Blockwise { lines, cols }) => {
+                view.buffer.delete_block(lines.clone(), cols.clone());
+                view.set_cursor_position(start);
+            }
+            (Operator::Change, TextRange::Blockwise { lines, cols }) => {
+                view.buffer.delete_block(lines.clone(), cols.clone());
+                view.set_cursor_position(start);
+                let original = view
+                    .get_line(lines.start)
+                    .map(|line| line.content().to_string())
+                    .unwrap_or_default();
+                self.block_insert = Some(BlockInsert {
+                    lines: lines.clone(),
+                    col_idx: cols.start,
+                    original,
+                });
+            }
+            (Operator::Change, TextRange::Linewise(lines)) => {
+                // leave one empty line to insert
+                view.buffer
+                    .delete_lines(lines.start.saturating_add(1)..lines.end);
+                view.buffer.set_line("", lines.start);
+                view.set_cursor_position(start);
+            }
+            // shifted above without a register
+            (Operator::ShiftRight | Operator::ShiftLeft | Operator::Reindent, _) => (),
+        }
+        if operator == Operator::Change {
+            self.set_mode(Mode::Insert);
+        }
+    }
+
+    // `G` and `gg` with a count go to the line of the count
+    // `f`, `F`, `t` and `T` are remembered for `;` and `,`
+    fn key_to_move_code(&mut self, key_repr: &str, count: Option<usize>) -> Option<MoveCode> {
+        let line = count.map(|count| MoveCode::Line(count.saturating_sub(1)));
+        if let Some(search) = CharSearch::from_keys(key_repr) {
+            self.last_char_search = Some(search);
+            return Some(MoveCode::FindChar(search));
+        }
+        match key_repr {
+            "<LEFT>" | "h" => Some(MoveCode::Left),
+            "<HOME>" | "0" => Some(MoveCode::FirstChar),
+            "^" => Some(MoveCode::FirstNonBlank),
+            "<DOWN>" | "j" => Some(MoveCode::Down),
+            "<UP>" | "k" => Some(MoveCode::Up),
+            "<RIGHT>" | "l" => Some(MoveCode::Right),
+            "<END>" | "$" => Some(MoveCode::LastChar),
+            "H" | "M" | "L" => {
+                // `3H` is the third line of the screen, `3L` the third from the bottom
+                let (top, bottom) = self.current_view().visible_lines();
+                let nth = count.unwrap_or(1).saturating_sub(1);
+                #[allow(clippy::integer_division)]
+                let line_idx = match key_repr {
+                    "H" => top.saturating_add(nth).min(bottom),
+                    "M" => top.saturating_add(bottom.saturating_sub(top) / 2),
+                    _ => bottom.saturating_sub(nth).max(top),
+                };
+                Some(MoveCode::Line(line_idx))
+            }
+            "}" => Some(MoveCode::NextParagraph),
+            "{" => Some(MoveCode::PrevParagraph),
+            ")" => Some(MoveCode::NextSentence),
+            "(" => Some(MoveCode::PrevSentence),
+            "gg" => Some(line.unwrap_or(MoveCode::FirstLine)),
+            "G" => Some(line.unwrap_or(MoveCode::LastLine)),
+            "w" => Some(MoveCode::NextWordStart(false)),
+            "W" => Some(MoveCode::NextWordStart(true)),
+            "e" => Some(MoveCode::NextWordEnd(false)),
+            "E" => Some(MoveCode::NextWordEnd(true)),
+            "b" => Some(MoveCode::PrevWordStart(false)),
+            "B" => Some(MoveCode::PrevWordStart(true)),
+            "ge" => Some(MoveCode::PrevWordEnd(false)),
+            "gE" => Some(MoveCode::PrevWordEnd(true)),
+            "gj" | "g<DOWN>" => Some(MoveCode::DisplayDown),
+            "gk" | "g<UP>" => Some(MoveCode::DisplayUp),
+            "%" => Some(match count {
+                // `50%` goes to the line at 50% of the buffer
+                Some(percent) => {
+                    let lines_count = self.current_view().buffer.get_lines_count();
+                    #[allow(clippy::integer_division)]
+                    let line_number = percent.saturating_mul(lines_count).saturating_add(99) / 100;
+                    MoveCode::Line(line_number.saturating_sub(1))
+                }
+                None => MoveCode::MatchingBracket,
+            }),
+            _ if key_repr.starts_with(['\'', '`']) => {
+                let mut chars = key_repr.chars();
+                let (Some(kind), Some(name), None) = (chars.next(), chars.next(), chars.next())
+                else {
+                    return None;
+                };
+                if self.current_view().buffer.get_mark(name).is_none() {
+                    self.set_message("Mark not set");
+                    return None;
+                }
+                // `'a` goes to the line of the mark, `` `a `` to the position
+                Some(if kind == '\'' {
+                    MoveCode::MarkLine(name)
+                } else {
+                    MoveCode::Mark(name)
+                })
             }
+            ";" => self
+                .last_char_search
+                .map(|search| MoveCode::FindChar(search.again())),
+            "," => self
+                .last_char_search
+                .map(|search| MoveCode::FindChar(search.again().reverse())),
+            _ => None,
+        }
+    }
 
+    fn handle_key_event_normal_move(&mut self, key_repr: &str, count: Option<usize>) {
+        let times = count.unwrap_or(1);
+        self.switch_to_file_mark(key_repr);
+        if let Some(code) = self.key_to_move_code(key_repr, count) {
+            let from = self.current_view().cursor.position();
+            self.current_view_mut().move_position_by(code, times);
+            if code.is_jump() && self.current_view().cursor.position() != from {
+                self.push_jump(from);
+            }
+            return;
+        }
+        match key_repr {
+            "<C-O>" | "<TAB>" | "<C-I>" | "g;" | "g," => {
+                self.go_to_older_or_newer(key_repr, times);
+            }
             "<S-LEFT>" => {
-                self.current_view_mut().scroll_screen(ScrollCode::Left(1));
+                self.current_view_mut()
+                    .scroll_screen(ScrollCode::Left(times));
             }
             "<S-RIGHT>" => {
-                self.current_view_mut().scroll_screen(ScrollCode::Right(1));
+                self.current_view_mut()
+                    .scroll_screen(ScrollCode::Right(times));
             }
             "<S-DOWN>" => {
-                self.current_view_mut().scroll_screen(ScrollCode::Down(1));
+                self.current_view_mut()
+                    .scroll_screen(ScrollCode::Down(times));
             }
             "<S-UP>" => {
-                self.current_view_mut().scroll_screen(ScrollCode::Up(1));
+                self.current_view_mut().scroll_screen(ScrollCode::Up(times));
             }
             "<PAGEDOWN>" | "<C-F>" => {
-                let height = self.current_view().height();
+                let height = self.current_view().height().saturating_mul(times);
                 self.current_view_mut()
                     .scroll_screen(ScrollCode::Down(height));
             }
             "<PAGEUP>" | "<C-B>" => {
-                let height = self.current_view().height();
+                let height = self.current_view().height().saturating_mul(times);
                 self.current_view_mut()
                     .scroll_screen(ScrollCode::Up(height));
             }
-            "<LEFT>" | "h" => {
-                self.current_view_mut().move_position(MoveCode::Left);
-            }
-            "<HOME>" | "0" => {
-                self.current_view_mut().move_position(MoveCode::FirstChar);
-            }
-            "H" => {
-                self.current_view_mut()
-                    .move_position(MoveCode::FirstNonBlank);
-            }
-            "<DOWN>" | "j" => {
-                self.current_view_mut().move_position(MoveCode::Down);
-            }
-            "<UP>" | "k" => {
-                self.current_view_mut().move_position(MoveCode::Up);
-            }
-            "<RIGHT>" | "l" => {
-                self.current_view_mut().move_position(MoveCode::Right);
-            }
-            "<END>" | "L" => {
-                self.current_view_mut().move_position(MoveCode::LastChar);
-            }
-            "g" => {
-                self.current_view_mut().move_position(MoveCode::FirstLine);
-            }
-            "G" => {
-                self.current_view_mut().move_position(MoveCode::LastLine);
-            }
-            "<CR>" => {
-                // TODO this should be in user local config
-                if self.current_view().buffer.file_info.get_file_type() == Some(FileType::Gitcommit)
-                {
-                    let line_idx = self.current_view().cursor.line_idx();
-                    if let Some(line) = self.current_view().get_line(line_idx) {
-                        let re = Regex::new(r"^# (\w+)\s+.*$").unwrap();
-                        let content = re.replace(line.content(), "$1: ").to_string();
-                        let height = self.current_view().height();
-                        self.current_view_mut()
-                            .scroll_screen(ScrollCode::Up(height));
-                        // remove line until line.content() is empty
-                        let lines_count = self.current_view().buffer.get_lines_count();
-                        for _ in 0..lines_count {
-                            if let Some(l) = self.current_view().get_line(0) {
-                                if l.content().is_empty() {
-                                    break;
-                                }
-                            }
-                            self.current_view_mut().buffer.remove_line(0);
-                        }
-                        self.current_view_mut().buffer.set_line(&content, 0);
-                        self.current_view_mut().move_position(MoveCode::LastChar);
-                        self.set_mode(Mode::Insert);
+            _ => (),
+        }
+    }
+
+    // `zt`, `zz` and `zb` scroll the screen without moving the cursor
+    // with a count the cursor goes to that line first like `10zt`
+    fn scroll_cursor_line(&mut self, key_repr: &str, count: Option<usize>) {
+        let code = match key_repr {
+            "t" => ScrollCode::CursorTop,
+            "z" => ScrollCode::CursorMiddle,
+            "b" => ScrollCode::CursorBottom,
+            _ => return,
+        };
+        let view = self.current_view_mut();
+        if let Some(count) = count {
+            view.move_position(MoveCode::Line(count.saturating_sub(1)));
+        }
+        view.scroll_screen(code);
+    }
+
+    fn start_gitcommit_message(&mut self) {
+        let line_idx = self.current_view().cursor.line_idx();
+        if let Some(line) = self.current_view().get_line(line_idx) {
+            let re = Regex::new(r"^# (\w+)\s+.*$").unwrap();
+            let content = re.replace(line.content(), "$1: ").to_string();
+            let height = self.current_view().height();
+            self.current_view_mut()
+                .scroll_screen(ScrollCode::Up(height));
+            // remove line until line.content() is empty
+            let lines_count = self.current_view().buffer.get_lines_count();
+            for _ in 0..lines_count {
+                if let Some(l) = self.current_view().get_line(0) {
+                    if l.content().is_empty() {
+                        break;
                     }
                 }
+                self.current_view_mut().buffer.remove_line(0);
             }
-            _ => (),
+            self.current_view_mut().buffer.set_line(&content, 0);
+            self.current_view_mut().move_position(MoveCode::LastChar);
+            self.set_mode(Mode::Insert);
         }
     }
 
@@ -391,7 +1202,7 @@ impl Editor {
         self.set_message(message);
     }
     fn quit_with_confirm(&mut self) {
-        if self.current_view().buffer.modified_count == 0 {
+        if self.current_view().buffer.modified_count() == 0 {
             self.should_quit = true;
         } else {
             self.set_message("Unsaved changes.");
@@ -403,6 +1214,10 @@ impl Editor {
         let width = width16 as usize;
         let height = height16 as usize;
         self.size = Size { width, height };
+        self.screen.resize(self.size);
+        if let Some(command_bar) = self.command_bar.as_mut() {
+            command_bar.ensure_redraw();
+        }
         let view_size = Size {
             width,
             // -1 for status bar
@@ -416,43 +1231,68 @@ impl Editor {
         if self.size.width == 0 || self.size.height == 0 {
             return;
         }
-        let _ = Terminal::hide_caret();
-        let _ = self.current_view_mut().render();
-        let _ = self.status_bar.render(self.size.height.saturating_sub(2));
-        if self.command_bar.is_some() {
-            let bottom_line = self.size.height.saturating_sub(1);
-            let command_bar = self.command_bar.as_mut().unwrap();
-            command_bar.render(bottom_line).unwrap();
-            Terminal::move_caret_to(Position {
+        // everything is drawn into the screen first, and only the changes are printed
+        if let Some(view) = self.views.get_mut(self.current_view_idx) {
+            view.render(&mut self.screen);
+        }
+        self.status_bar
+            .render(&mut self.screen, self.size.height.saturating_sub(2));
+        let bottom_line = self.size.height.saturating_sub(1);
+        let caret = if let Some(command_bar) = self.command_bar.as_mut() {
+            command_bar.render(&mut self.screen, bottom_line);
+            Position {
                 col_idx: command_bar.caret_col,
                 line_idx: bottom_line,
-            })
-            .unwrap();
+            }
         } else {
             if let Some(line_text) = &self.message {
-                let bottom_line = self.size.height.saturating_sub(1);
-                Terminal::print_row(bottom_line, line_text).unwrap();
+                self.screen
+                    .put_row(bottom_line, line_text, HighlightGroup::Normal);
             }
-            self.move_caret();
-        }
+            self.current_view().caret_screen_position()
+        };
+        let _ = Terminal::hide_caret();
+        let _ = self.screen.flush();
+        let _ = Terminal::move_caret_to(caret);
         let _ = Terminal::show_caret();
         let _ = Terminal::execute();
     }
-    fn move_caret(&self) {
-        Terminal::move_caret_to(self.current_view().caret_screen_position()).unwrap();
-    }
 
     // NOTE: easy version
     fn handle_key_event_insert(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        if self.insert_repeat.is_some() && code != KeyCode::Esc {
+            self.insert_keys.push((code, modifiers));
+        }
         match (code, modifiers) {
             (KeyCode::Esc, _) => {
+                self.repeat_insert();
+                self.finish_block_insert();
+                self.current_view_mut().remove_unused_indent();
+                // `^` is where the insert mode is stopped, and `]` is the last inserted character
+                let view = self.current_view_mut();
+                let cursor = view.cursor.position();
+                view.buffer.set_mark('^', cursor);
+                view.buffer.set_mark(
+                    ']',
+                    Position {
+                        line_idx: cursor.line_idx,
+                        col_idx: cursor.col_idx.saturating_sub(1),
+                    },
+                );
+                self.finish_command();
                 self.set_mode(Mode::Normal);
             }
             (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
-                self.current_view_mut().insert_char(c);
+                let view = self.current_view_mut();
+                view.insert_char(c);
+                view.indent_closing_bracket(c);
+            }
+            (KeyCode::Tab, KeyModifiers::NONE) => self.current_view_mut().insert_tab(),
+            (KeyCode::Char('t'), KeyModifiers::CONTROL) => {
+                self.current_view_mut().shift_cursor_line(true);
             }
-            (KeyCode::Tab, KeyModifiers::NONE) => {
-                self.current_view_mut().insert_char('\t');
+            (KeyCode::Char('d'), KeyModifiers::CONTROL) => {
+                self.current_view_mut().shift_cursor_line(false);
             }
             (KeyCode::Enter, KeyModifiers::NONE) => {
                 self.current_view_mut().insert_char('\n');
@@ -460,15 +1300,17 @@ impl Editor {
             (KeyCode::Delete, KeyModifiers::NONE) => {
                 self.current_view_mut().remove_char();
             }
+            // just detect if the caret is at the beginning of the buffer
+            // so we don't need to use caret_screen_position() here
             (KeyCode::Backspace, KeyModifiers::NONE)
-            | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
-                // just detect if the caret is at the beginning of the buffer
-                // so we don't need to use caret_screen_position() here
+            | (KeyCode::Char('h'), KeyModifiers::CONTROL)
                 if self.current_view().cursor.col_idx() > 0
-                    || self.current_view().cursor.line_idx() > 0
-                {
-                    self.current_view_mut().move_position(MoveCode::Left);
-                    self.current_view_mut().remove_char();
+                    || self.current_view().cursor.line_idx() > 0 =>
+            {
+                let view = self.current_view_mut();
+                if !view.remove_soft_tab() {
+                    view.move_position(MoveCode::Left);
+                    view.remove_char();
                 }
             }
             _ => (),
@@ -476,54 +1318,704 @@ impl Editor {
     }
 
     fn handle_key_event_command(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        if self.substitution.is_some() {
+            self.handle_key_event_confirm(code);
+            return;
+        }
         match (code, modifiers) {
             (KeyCode::Esc, _) => {
+                if self.mode == Mode::Search {
+                    self.cancel_search();
+                }
                 self.set_mode(Mode::Normal);
             }
             (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
                 self.command_bar.as_mut().unwrap().insert(c);
+                if self.mode == Mode::Search {
+                    self.update_incremental_search();
+                }
             }
             (KeyCode::Enter, KeyModifiers::NONE) => {
                 let value = self.command_bar.as_ref().unwrap().value().to_string();
                 if self.command_bar.as_ref().unwrap().mode == Mode::Command {
-                    self.run_command(&value.trim());
+                    self.set_mode(Mode::Normal);
+                    self.registers.set_last_command(value.trim());
+                    self.run_command(value.trim());
+                    // the whole command is one undo step
+                    if self.substitution.is_none() {
+                        self.commit_history();
+                    }
                 } else {
-                    self.current_view().search(&value);
-                    self.set_message(&format!("search: {value}"));
+                    self.finish_search(&value);
+                    self.set_mode(Mode::Normal);
                 }
-                self.set_mode(Mode::Normal);
             }
             (KeyCode::Backspace, KeyModifiers::NONE)
             | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
                 self.command_bar.as_mut().unwrap().delete_backward();
+                if self.mode == Mode::Search {
+                    self.update_incremental_search();
+                }
             }
             _ => (),
         }
     }
 
-    fn run_command(&mut self, prompt: &str) {
-        let (command, args) = prompt.split_once(' ').unwrap_or((prompt, ""));
+    fn start_search(&mut self, direction: SearchDirection) {
+        self.search_direction = direction;
+        self.search_origin = self.current_view().cursor.position();
+        self.set_mode(Mode::Search);
+    }
 
-        match command {
-            "q" | "quit" => self.quit_with_confirm(),
-            "q!" | "quit!" => self.should_quit = true,
-            "w" | "write" => {
-                if args.is_empty() {
-                    self.save();
-                } else {
-                    self.save_as(args);
-                }
-            }
-            "wq" => {
-                if args.is_empty() {
-                    self.save();
-                } else {
+    // move the cursor to the match while typing the pattern
+    fn update_incremental_search(&mut self) {
+        let pattern = self.command_bar.as_ref().unwrap().value();
+        let regex = if pattern.is_empty() {
+            None
+        } else {
+            Regex::new(pattern).ok()
+        };
+        let origin = self.search_origin;
+        let direction = self.search_direction;
+        let view = self.current_view_mut();
+        view.set_cursor_position(origin);
+        if let Some(regex) = &regex {
+            view.search(regex, origin, direction);
+        }
+        view.set_highlight_pattern(regex);
+    }
+
+    fn cancel_search(&mut self) {
+        let origin = self.search_origin;
+        let regex = self
+            .registers
+            .get(registers::LAST_SEARCH)
+            .and_then(|register| Regex::new(&register.content).ok());
+        let view = self.current_view_mut();
+        view.set_cursor_position(origin);
+        view.set_highlight_pattern(regex);
+    }
+
+    fn finish_search(&mut self, value: &str) {
+        // an empty pattern repeats the last search
+        let pattern = if value.is_empty() {
+            let Some(register) = self.registers.get(registers::LAST_SEARCH) else {
+                self.cancel_search();
+                self.set_message("No previous regular expression");
+                return;
+            };
+            register.content
+        } else {
+            value.to_string()
+        };
+        self.registers.set_last_search(&pattern);
+        let origin = self.search_origin;
+        self.current_view_mut().set_cursor_position(origin);
+        self.run_search(&pattern, origin, self.search_direction);
+    }
+
+    fn search_next(&mut self, direction: SearchDirection) {
+        let Some(register) = self.registers.get(registers::LAST_SEARCH) else {
+            self.set_message("No previous regular expression");
+            return;
+        };
+        let from = self.current_view().cursor.position();
+        self.run_search(&register.content, from, direction);
+    }
+
+    fn run_search(&mut self, pattern: &str, from: Position, direction: SearchDirection) {
+        let Ok(regex) = Regex::new(pattern) else {
+            self.set_message(&format!("Invalid pattern: {pattern}"));
+            return;
+        };
+        let view = self.current_view_mut();
+        view.set_highlight_pattern(Some(regex.clone()));
+        let found = view.search(&regex, from, direction);
+        if found.is_some() {
+            self.push_jump(from);
+        }
+        match found {
+            Some(found) if found.wrapped => self.set_message(direction.wrap_message()),
+            Some(_) => self.set_message(&format!("{}{pattern}", direction.prompt())),
+            None => self.set_message(&format!("Pattern not found: {pattern}")),
+        }
+    }
+
+    fn run_command(&mut self, prompt: &str) {
+        let last_search = self
+            .registers
+            .get(registers::LAST_SEARCH)
+            .map(|register| register.content);
+        let view = self.current_view();
+        let context = AddressContext {
+            buffer: &view.buffer,
+            current_line_idx: view.cursor.line_idx(),
+            last_search: last_search.as_deref(),
+        };
+        let result = match ex::parse_command(prompt, &context) {
+            Ok(command) => self.run_ex_command(&command),
+            Err(message) => Err(message),
+        };
+        if let Err(message) = result {
+            self.set_message(&message);
+        }
+    }
+}
+
+impl Editor {
+    fn run_ex_command(&mut self, command: &ExCommand) -> Result<(), String> {
+        let current_line_idx = self.current_view().cursor.line_idx();
+        let range = command.range_or_current(current_line_idx);
+        let args = command.args.as_str();
+        match command.name {
+            "" => {
+                if let Some(range) = &command.range {
+                    let from = self.current_view().cursor.position();
+                    self.go_to_line(range.end.saturating_sub(1));
+                    self.push_jump(from);
+                }
+            }
+            "quit" if command.bang => self.should_quit = true,
+            "quit" => self.quit_with_confirm(),
+            "write" | "wq" => {
+                if args.is_empty() {
+                    self.save();
+                } else {
                     self.save_as(args);
                 }
-                self.should_quit = true;
+                if command.name == "wq" {
+                    self.should_quit = true;
+                }
             }
             "echo" => self.set_message(args),
-            _ => self.set_message(&format!("Unknown command: {command}")),
+            "substitute" => self.start_substitution(args, range),
+            "delete" | "yank" => self.ex_delete_or_yank(command.name == "delete", range, args)?,
+            "move" | "copy" | "t" => self.ex_move_or_copy(command.name == "move", range, args)?,
+            "join" => {
+                let count = ex::parse_count(args)?;
+                let lines_count = self.current_view().buffer.get_lines_count();
+                let mut range = ex::apply_count(range, count, lines_count);
+                // a single line is joined with the next line
+                if range.len() < 2 {
+                    range.end = range.start.saturating_add(2).min(lines_count);
+                }
+                ex::join_lines(
+                    &mut self.current_view_mut().buffer,
+                    range.clone(),
+                    command.bang,
+                );
+                self.go_to_line(range.start);
+            }
+            ">" | "<" => self.ex_shift(command.name, range, args)?,
+            "normal" => self.ex_normal(command.range.clone(), args),
+            "colorscheme" => self.set_colorscheme(args)?,
+            "set" => {
+                if let Some(values) = self.current_view_mut().set_options(args)? {
+                    self.set_message(&values);
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    // without a name the current one is shown
+    fn set_colorscheme(&mut self, name: &str) -> Result<(), String> {
+        if name.is_empty() {
+            let message = self.screen.theme_name().to_string();
+            self.set_message(&message);
+            return Ok(());
+        }
+        let theme = Theme::load(name)?;
+        self.screen.set_theme(theme);
+        self.current_view_mut().ensure_redraw();
+        self.status_bar.ensure_redraw();
+        if let Some(command_bar) = self.command_bar.as_mut() {
+            command_bar.ensure_redraw();
+        }
+        Ok(())
+    }
+
+    // move the cursor to the first non-blank character of the line
+    fn go_to_line(&mut self, line_idx: usize) {
+        let view = self.current_view_mut();
+        let line_idx = line_idx.min(view.buffer.get_lines_count().saturating_sub(1));
+        view.set_cursor_position(Position {
+            line_idx,
+            col_idx: 0,
+        });
+        view.move_position(MoveCode::FirstNonBlank);
+    }
+
+    // parse the destination of :m and :t
+    fn parse_ex_address(&self, input: &str) -> Result<usize, String> {
+        let view = self.current_view();
+        let context = AddressContext {
+            buffer: &view.buffer,
+            current_line_idx: view.cursor.line_idx(),
+            last_search: None,
+        };
+        match ex::parse_address(input, &context)? {
+            (Some(line_number), "") if line_number <= view.buffer.get_lines_count() => {
+                Ok(line_number)
+            }
+            (Some(_), "") => Err("Invalid range".to_string()),
+            (Some(_), rest) => Err(format!("Trailing characters: {rest}")),
+            (None, _) => Err("Invalid address".to_string()),
+        }
+    }
+
+    fn ex_delete_or_yank(
+        &mut self,
+        delete: bool,
+        range: std::ops::Range<usize>,
+        args: &str,
+    ) -> Result<(), String> {
+        let (name, count) = ex::parse_register_and_count(args)?;
+        let buffer = &self.current_view().buffer;
+        let range = ex::apply_count(range, count, buffer.get_lines_count());
+        let register = Register::linewise(&buffer.get_lines(range.clone()));
+        let stored = if delete {
+            self.registers.delete(name, register)
+        } else {
+            self.registers.yank(name, register)
+        };
+        if !stored {
+            return Err("Cannot write to the read-only register".to_string());
+        }
+        let lines_count = range.len();
+        if delete {
+            self.current_view_mut().buffer.delete_lines(range.clone());
+            self.go_to_line(range.start);
+        }
+        // report only changes of many lines
+        if lines_count > 2 {
+            let message = if delete {
+                format!("{lines_count} fewer lines")
+            } else {
+                format!("{lines_count} lines yanked")
+            };
+            self.set_message(&message);
+        }
+        Ok(())
+    }
+
+    fn ex_move_or_copy(
+        &mut self,
+        move_lines: bool,
+        range: std::ops::Range<usize>,
+        args: &str,
+    ) -> Result<(), String> {
+        let target = self.parse_ex_address(args)?;
+        let lines_count = range.len();
+        let buffer = &mut self.current_view_mut().buffer;
+        let last_line_idx = if move_lines {
+            ex::move_lines(buffer, range, target)?
+        } else {
+            ex::copy_lines(buffer, range, target)
+        };
+        self.go_to_line(last_line_idx);
+        if lines_count > 2 {
+            let message = if move_lines {
+                format!("{lines_count} lines moved")
+            } else {
+                format!("{lines_count} more lines")
+            };
+            self.set_message(&message);
+        }
+        Ok(())
+    }
+
+    // `:>>` shifts twice
+    fn ex_shift(
+        &mut self,
+        name: &str,
+        range: std::ops::Range<usize>,
+        args: &str,
+    ) -> Result<(), String> {
+        let repeat = args.chars().take_while(|c| name.starts_with(*c)).count();
+        let count = ex::parse_count(&args[repeat..])?;
+        let lines_count = self.current_view().buffer.get_lines_count();
+        let range = ex::apply_count(range, count, lines_count);
+        self.shift_lines(range.clone(), repeat.saturating_add(1), name == ">");
+        self.go_to_line(range.end.saturating_sub(1));
+        Ok(())
+    }
+
+    // shift by `shiftwidth` per level, more than two lines are reported
+    fn shift_lines(&mut self, lines: std::ops::Range<usize>, levels: usize, right: bool) {
+        let view = self.current_view_mut();
+        let lines = lines.start..lines.end.min(view.buffer.get_lines_count());
+        ex::shift_lines(
+            &mut view.buffer,
+            lines.clone(),
+            levels,
+            right,
+            &view.options,
+        );
+        if lines.len() > 2 {
+            let name = if right { ">" } else { "<" };
+            let times = if levels == 1 { "time" } else { "times" };
+            self.set_message(&format!("{} lines {name}ed {levels} {times}", lines.len()));
+        }
+    }
+
+    fn reindent_lines(&mut self, lines: std::ops::Range<usize>) {
+        let view = self.current_view_mut();
+        let lines = lines.start..lines.end.min(view.buffer.get_lines_count());
+        indent::reindent_lines(&mut view.buffer, lines.clone(), &view.options);
+        if lines.len() > 2 {
+            self.set_message(&format!("{} lines indented", lines.len()));
+        }
+    }
+
+    // run the keys in normal mode, on each line of the range from its start
+    fn ex_normal(&mut self, range: Option<std::ops::Range<usize>>, keys: &str) {
+        self.running_normal = true;
+        if let Some(range) = range {
+            for line_idx in range {
+                if line_idx >= self.current_view().buffer.get_lines_count() {
+                    break;
+                }
+                self.current_view_mut().set_cursor_position(Position {
+                    line_idx,
+                    col_idx: 0,
+                });
+                self.run_normal_keys(keys);
+            }
+        } else {
+            self.run_normal_keys(keys);
+        }
+        self.running_normal = false;
+    }
+
+    fn run_normal_keys(&mut self, keys: &str) {
+        for c in keys.chars() {
+            self.handle_key_event(KeyCode::Char(c), KeyModifiers::NONE);
+        }
+        // an incomplete command is canceled like <Esc>
+        if self.mode != Mode::Normal {
+            self.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        }
+        self.pending_prefix = None;
+        self.pending_operator = None;
+        self.pending_register = None;
+    }
+}
+
+impl Editor {
+    fn start_substitution(&mut self, args: &str, range: std::ops::Range<usize>) {
+        let last_search = self.registers.get(registers::LAST_SEARCH);
+        let (substitution, pattern) =
+            match Substitution::parse(args, range, last_search.map(|r| r.content).as_deref()) {
+                Ok(parsed) => parsed,
+                Err(message) => {
+                    self.set_message(&message);
+                    return;
+                }
+            };
+        self.registers.set_last_search(&pattern);
+        self.current_view_mut()
+            .set_highlight_pattern(Some(substitution.regex().clone()));
+        self.substitution = Some(substitution);
+        if self.substitution.as_ref().is_some_and(|s| s.confirm) {
+            self.confirm_next_substitution();
+        } else {
+            let view = self.views.get_mut(self.current_view_idx).unwrap();
+            if let Some(substitution) = self.substitution.as_mut() {
+                substitution.replace_all(&mut view.buffer);
+            }
+            self.finish_substitution(&pattern);
+        }
+    }
+
+    // move to the next match and ask, or finish if there is no more match
+    fn confirm_next_substitution(&mut self) {
+        let view = self.views.get_mut(self.current_view_idx).unwrap();
+        let Some(substitution) = self.substitution.as_mut() else {
+            return;
+        };
+        if let Some(position) = substitution.find_next(&view.buffer) {
+            let prompt = substitution.prompt();
+            view.set_cursor_position(position);
+            self.set_mode(Mode::Command);
+            self.command_bar = Some(CommandBar::new(Mode::Command, &prompt));
+        } else {
+            let pattern = self
+                .registers
+                .get(registers::LAST_SEARCH)
+                .map(|register| register.content)
+                .unwrap_or_default();
+            self.finish_substitution(&pattern);
+        }
+    }
+
+    fn handle_key_event_confirm(&mut self, code: KeyCode) {
+        let view = self.views.get_mut(self.current_view_idx).unwrap();
+        let Some(substitution) = self.substitution.as_mut() else {
+            return;
+        };
+        match code {
+            KeyCode::Char('y') => substitution.replace(&mut view.buffer),
+            KeyCode::Char('n') => substitution.skip(&view.buffer),
+            KeyCode::Char('a') => {
+                substitution.replace(&mut view.buffer);
+                substitution.replace_all(&mut view.buffer);
+            }
+            KeyCode::Char('l') => {
+                substitution.replace(&mut view.buffer);
+                let pattern = substitution.regex().to_string();
+                self.finish_substitution(&pattern);
+                return;
+            }
+            KeyCode::Char('q') | KeyCode::Esc => {
+                let pattern = substitution.regex().to_string();
+                self.finish_substitution(&pattern);
+                return;
+            }
+            _ => return,
+        }
+        self.confirm_next_substitution();
+    }
+
+    fn finish_substitution(&mut self, pattern: &str) {
+        let Some(substitution) = self.substitution.take() else {
+            return;
+        };
+        self.set_mode(Mode::Normal);
+        self.set_message(&substitution.message(pattern));
+        if let Some(line_idx) = substitution.last_line_idx() {
+            self.go_to_line(line_idx);
+        }
+        self.commit_history();
+    }
+}
+
+impl Editor {
+    fn start_visual(&mut self, kind: SelectionKind) {
+        let anchor = self.current_view().cursor.position();
+        self.current_view_mut()
+            .set_selection(Some(Selection { kind, anchor }));
+        self.set_mode(Self::visual_mode(kind));
+    }
+
+    fn visual_mode(kind: SelectionKind) -> Mode {
+        match kind {
+            SelectionKind::Charwise => Mode::Visual,
+            SelectionKind::Linewise => Mode::VisualLine,
+            SelectionKind::Blockwise => Mode::VisualBlock,
+        }
+    }
+
+    // leave the visual mode, and remember the selection with the marks `<` and `>`
+    fn end_visual(&mut self) {
+        let view = self.current_view_mut();
+        if let Some(selection) = view.selection.take() {
+            let cursor = view.cursor.position();
+            view.buffer.set_mark('<', selection.anchor.min(cursor));
+            view.buffer.set_mark('>', selection.anchor.max(cursor));
+            view.ensure_redraw();
+        }
+        self.set_mode(Mode::Normal);
+    }
+
+    fn handle_key_event_visual(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        let mut key_repr = Editor::key_to_string(code, modifiers);
+        if let Some(prefix) = self.pending_prefix.take() {
+            if prefix == "\"" {
+                self.select_register(&key_repr);
+                return;
+            }
+            // the rest of a motion like `gg`
+            key_repr.insert_str(0, &prefix);
+        }
+        let key_repr: &str = &key_repr;
+        if self.accumulate_count(key_repr) {
+            return;
+        }
+        let count = self.take_count();
+        let view = self.current_view_mut();
+        let Some(selection) = view.selection else {
+            self.set_mode(Mode::Normal);
+            return;
+        };
+        match key_repr {
+            "<ESC>" => self.end_visual(),
+            "v" | "V" | "<C-V>" => {
+                let kind = match key_repr {
+                    "v" => SelectionKind::Charwise,
+                    "V" => SelectionKind::Linewise,
+                    _ => SelectionKind::Blockwise,
+                };
+                if kind == selection.kind {
+                    self.end_visual();
+                } else {
+                    view.set_selection(Some(Selection { kind, ..selection }));
+                    self.set_mode(Self::visual_mode(kind));
+                }
+            }
+            // go to the other end of the selection
+            "o" => {
+                let cursor = view.cursor.position();
+                view.set_cursor_position(selection.anchor);
+                view.set_selection(Some(Selection {
+                    anchor: cursor,
+                    ..selection
+                }));
+            }
+            "\"" | "g" | "f" | "F" | "t" | "T" | "'" | "`" | "i" | "a" => {
+                self.pending_prefix = Some(key_repr.to_string());
+                self.pending_count = count;
+            }
+            "d" | "x" | "c" | "s" | "y" | ">" | "<" | "=" | "~" | "J" | ":" => {
+                let range = selection.text_range(view.cursor.position(), &view.buffer);
+                self.end_visual();
+                self.apply_visual_operator(key_repr, &range);
+            }
+            "n" => (0..count.unwrap_or(1)).for_each(|_| self.search_next(self.search_direction)),
+            "N" => (0..count.unwrap_or(1))
+                .for_each(|_| self.search_next(self.search_direction.reverse())),
+            _ => {
+                let view = self.current_view();
+                let cursor = view.cursor.position();
+                if let Some(range) =
+                    text_object::select(key_repr, cursor, &view.buffer, count.unwrap_or(1))
+                {
+                    self.select_text_object(&range);
+                } else {
+                    self.handle_key_event_normal_move(key_repr, count);
+                }
+            }
+        }
+        // the selection follows the cursor
+        self.current_view_mut().ensure_redraw();
+        if self.mode == Mode::Normal {
+            self.pending_register = None;
+            self.finish_command();
+        }
+    }
+
+    // select the range of a text object like `viw`
+    fn select_text_object(&mut self, range: &TextRange) {
+        let (kind, anchor, end) = match range {
+            TextRange::Charwise { start, end } => (SelectionKind::Charwise, *start, *end),
+            TextRange::Linewise(lines) => (
+                SelectionKind::Linewise,
+                range.start(),
+                Position {
+                    line_idx: lines.end.saturating_sub(1),
+                    col_idx: 0,
+                },
+            ),
+            TextRange::Blockwise { .. } => return,
+        };
+        let view = self.current_view_mut();
+        view.set_selection(Some(Selection { kind, anchor }));
+        view.set_cursor_position(end);
+        if kind == SelectionKind::Charwise {
+            // the end of the range is exclusive
+            view.move_position(MoveCode::Left);
+        }
+        self.set_mode(Self::visual_mode(kind));
+    }
+
+    fn apply_visual_operator(&mut self, key_repr: &str, range: &TextRange) {
+        match key_repr {
+            "d" | "x" => self.apply_operator(Operator::Delete, range),
+            "c" | "s" => self.apply_operator(Operator::Change, range),
+            "y" => self.apply_operator(Operator::Yank, range),
+            ">" => self.apply_operator(Operator::ShiftRight, range),
+            "<" => self.apply_operator(Operator::ShiftLeft, range),
+            "=" => self.apply_operator(Operator::Reindent, range),
+            "~" => {
+                self.toggle_case(range);
+                self.current_view_mut().set_cursor_position(range.start());
+            }
+            "J" => {
+                let lines_count = self.current_view().buffer.get_lines_count();
+                let mut lines = range.lines();
+                if lines.len() < 2 {
+                    lines.end = lines.start.saturating_add(2).min(lines_count);
+                }
+                ex::join_lines(&mut self.current_view_mut().buffer, lines.clone(), false);
+                self.go_to_line(lines.start);
+            }
+            ":" => {
+                self.set_mode(Mode::Command);
+                if let Some(command_bar) = self.command_bar.as_mut() {
+                    "'<,'>".chars().for_each(|c| command_bar.insert(c));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn toggle_case(&mut self, range: &TextRange) {
+        let buffer = &mut self.current_view_mut().buffer;
+        for line_idx in range.lines() {
+            let Some(line) = buffer.lines.get(line_idx) else {
+                break;
+            };
+            let bytes =
+                line.get_byte_range_by_col_range(range.cols_in_line(line_idx, line.col_width()));
+            let content = line.content();
+            let toggled: String = content[bytes.clone()]
+                .chars()
+                .flat_map(|c| {
+                    if c.is_lowercase() {
+                        c.to_uppercase().collect::<Vec<char>>()
+                    } else {
+                        c.to_lowercase().collect::<Vec<char>>()
+                    }
+                })
+                .collect();
+            let new_content = format!(
+                "{}{toggled}{}",
+                &content[..bytes.start],
+                &content[bytes.end..]
+            );
+            if new_content != content {
+                buffer.set_line(&new_content, line_idx);
+            }
+        }
+    }
+
+    // copy the text inserted to the first line of the block to the other lines
+    fn finish_block_insert(&mut self) {
+        let Some(block_insert) = self.block_insert.take() else {
+            return;
+        };
+        let buffer = &mut self.current_view_mut().buffer;
+        let Some(line) = buffer.lines.get(block_insert.lines.start) else {
+            return;
+        };
+        let original = &block_insert.original;
+        let byte_idx = buffer
+            .new_line(original)
+            .col_idx_to_byte_idx(block_insert.col_idx);
+        let content = line.content();
+        let (head, tail) = original.split_at(byte_idx);
+        // the text must be inserted at the column without newlines
+        let inserted = content
+            .strip_prefix(head)
+            .and_then(|rest| rest.strip_suffix(tail))
+            .filter(|inserted| !inserted.is_empty())
+            .map(String::from);
+        let Some(inserted) = inserted else {
+            return;
+        };
+        for line_idx in block_insert.lines.skip(1) {
+            // lines which do not reach the block are skipped
+            if buffer.get_line_col_width(line_idx) < block_insert.col_idx {
+                continue;
+            }
+            buffer.insert(
+                &inserted,
+                Position {
+                    line_idx,
+                    col_idx: block_insert.col_idx,
+                },
+            );
         }
     }
 }
@@ -539,6 +2031,8 @@ impl Drop for Editor {
 
 #[cfg(test)]
 mod tests {
+    use super::file_info::FileInfo;
+    use super::line::Line;
     use super::*;
 
     #[test]
@@ -553,6 +2047,757 @@ mod tests {
         assert_eq!(editor.size, Size::new(10, 10));
     }
 
+    fn editor_from(str: &str) -> Editor {
+        let buffer = Buffer::from_string(str);
+        let view = View::new(buffer, Size::new(10, 10));
+        let mut editor = Editor::default();
+        editor.views = vec![view];
+        editor
+    }
+
+    fn press_keys(editor: &mut Editor, keys: &str) {
+        for c in keys.chars() {
+            editor.handle_key_event(KeyCode::Char(c), KeyModifiers::NONE);
+        }
+    }
+
+    fn contents(editor: &Editor) -> Vec<&str> {
+        editor
+            .current_view()
+            .buffer
+            .lines
+            .iter()
+            .map(Line::content)
+            .collect()
+    }
+
+    #[test]
+    fn test_operator() {
+        let mut editor = editor_from("this\nis\ntest.\n");
+        press_keys(&mut editor, "ldl");
+        assert_eq!(contents(&editor), vec!["tis", "is", "test."]);
+        assert_eq!(editor.registers.get('"'), Some(Register::charwise("h")));
+        press_keys(&mut editor, "dj");
+        assert_eq!(contents(&editor), vec!["test."]);
+        assert_eq!(editor.registers.get('1').unwrap().content, "tis\nis\n");
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor), vec!["tis", "is", "test."]);
+        press_keys(&mut editor, "jyy");
+        assert_eq!(editor.registers.get('0').unwrap().content, "is\n");
+        press_keys(&mut editor, "ld0");
+        assert_eq!(contents(&editor), vec!["tis", "s", "test."]);
+        assert_eq!(editor.current_view().cursor.position(), Position::new(1, 0));
+        press_keys(&mut editor, "dx");
+        assert_eq!(contents(&editor), vec!["tis", "s", "test."]);
+    }
+
+    #[test]
+    fn test_put() {
+        let mut editor = editor_from("this\nis\ntest.\n");
+        press_keys(&mut editor, "yyjp");
+        assert_eq!(contents(&editor), vec!["this", "is", "this", "test."]);
+        assert_eq!(editor.current_view().cursor.position(), Position::new(2, 0));
+        press_keys(&mut editor, "\"ayl\"bylP");
+        assert_eq!(contents(&editor), vec!["this", "is", "tthis", "test."]);
+        press_keys(&mut editor, "\"ap");
+        assert_eq!(contents(&editor), vec!["this", "is", "ttthis", "test."]);
+        assert_eq!(editor.current_view().cursor.position(), Position::new(2, 1));
+        press_keys(&mut editor, "\"_dd\"1P");
+        assert_eq!(contents(&editor), vec!["this", "is", "test."]);
+        press_keys(&mut editor, "x\"Ax\"ap");
+        assert_eq!(contents(&editor), vec!["this", "is", "stet."]);
+        assert_eq!(editor.current_view().cursor.position(), Position::new(2, 2));
+    }
+
+    fn type_command(editor: &mut Editor, keys: &str) {
+        for c in keys.chars() {
+            editor.handle_key_event_command(KeyCode::Char(c), KeyModifiers::NONE);
+        }
+        editor.handle_key_event_command(KeyCode::Enter, KeyModifiers::NONE);
+    }
+
+    #[test]
+    fn test_search() {
+        let mut editor = editor_from("foo\n日本語🇯🇵foo\n\tfoo\n");
+        press_keys(&mut editor, "/");
+        editor.handle_key_event_command(KeyCode::Char('f'), KeyModifiers::NONE);
+        assert_eq!(editor.current_view().cursor.position(), Position::new(1, 8));
+        editor.handle_key_event_command(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(editor.current_view().cursor.position(), Position::new(0, 0));
+
+        press_keys(&mut editor, "/");
+        type_command(&mut editor, "o+");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(0, 1));
+        press_keys(&mut editor, "n");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(1, 9));
+        press_keys(&mut editor, "n");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(2, 5));
+        press_keys(&mut editor, "n");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(0, 1));
+        assert_eq!(
+            editor.message.as_deref(),
+            Some("search hit BOTTOM, continuing at TOP")
+        );
+        press_keys(&mut editor, "N");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(2, 5));
+        assert_eq!(
+            editor.message.as_deref(),
+            Some("search hit TOP, continuing at BOTTOM")
+        );
+
+        press_keys(&mut editor, "?");
+        type_command(&mut editor, "");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(1, 9));
+        press_keys(&mut editor, "n");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(0, 1));
+        press_keys(&mut editor, "/");
+        type_command(&mut editor, "bar");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(0, 1));
+        assert_eq!(editor.message.as_deref(), Some("Pattern not found: bar"));
+    }
+
+    #[test]
+    fn test_substitute() {
+        let mut editor = editor_from("foo foo\nbar\nfoo\n");
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "%s/o/0/g");
+        assert_eq!(contents(&editor), vec!["f00 f00", "bar", "f00"]);
+        assert_eq!(
+            editor.message.as_deref(),
+            Some("6 substitutions on 2 lines")
+        );
+        assert_eq!(editor.current_view().cursor.position(), Position::new(2, 0));
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor), vec!["foo foo", "bar", "foo"]);
+
+        press_keys(&mut editor, "jma:");
+        type_command(&mut editor, "1,'as/(o+)/<\\1>/");
+        assert_eq!(contents(&editor), vec!["f<oo> foo", "bar", "foo"]);
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "s/z/y/");
+        assert_eq!(editor.message.as_deref(), Some("Pattern not found: z"));
+
+        press_keys(&mut editor, "u:");
+        type_command(&mut editor, "%s/foo/&&/gc");
+        assert_eq!(editor.mode, Mode::Command);
+        assert_eq!(editor.current_view().cursor.position(), Position::new(0, 0));
+        editor.handle_key_event_command(KeyCode::Char('n'), KeyModifiers::NONE);
+        assert_eq!(editor.current_view().cursor.position(), Position::new(0, 4));
+        editor.handle_key_event_command(KeyCode::Char('y'), KeyModifiers::NONE);
+        assert_eq!(editor.current_view().cursor.position(), Position::new(2, 0));
+        editor.handle_key_event_command(KeyCode::Char('q'), KeyModifiers::NONE);
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(contents(&editor), vec!["foo foofoo", "bar", "foo"]);
+        assert_eq!(editor.message.as_deref(), Some("1 substitution on 1 line"));
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor), vec!["foo foo", "bar", "foo"]);
+    }
+
+    #[test]
+    fn test_ex_commands() {
+        let mut editor = editor_from("a\nb\nc\nd\ne\n");
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "2,3d x");
+        assert_eq!(contents(&editor), vec!["a", "d", "e"]);
+        assert_eq!(editor.registers.get('x').unwrap().content, "b\nc\n");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(1, 0));
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "1m$");
+        assert_eq!(contents(&editor), vec!["d", "e", "a"]);
+        assert_eq!(editor.current_view().cursor.position(), Position::new(2, 0));
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "/d/t.");
+        assert_eq!(contents(&editor), vec!["d", "e", "a", "d"]);
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "1,2j");
+        assert_eq!(contents(&editor), vec!["d e", "a", "d"]);
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "%>");
+        assert_eq!(contents(&editor), vec!["\td e", "\ta", "\td"]);
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, ".-1,.<");
+        assert_eq!(contents(&editor), vec!["\td e", "a", "d"]);
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "1y");
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "2");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(1, 0));
+        press_keys(&mut editor, "p");
+        assert_eq!(contents(&editor), vec!["\td e", "a", "\td e", "d"]);
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "3d _");
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "m0");
+        assert_eq!(contents(&editor), vec!["d", "\td e", "a"]);
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "m");
+        assert_eq!(editor.message.as_deref(), Some("Invalid address"));
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "foo");
+        assert_eq!(editor.message.as_deref(), Some("Unknown command: foo"));
+
+        // :normal is one undo step
+        let mut editor = editor_from("a\nb\nc\n");
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "%norm Ax");
+        assert_eq!(contents(&editor), vec!["ax", "bx", "cx"]);
+        assert_eq!(editor.mode, Mode::Normal);
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "normal dd\"");
+        assert_eq!(contents(&editor), vec!["ax", "bx"]);
+        assert_eq!(editor.pending_prefix, None);
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor), vec!["ax", "bx", "cx"]);
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor), vec!["a", "b", "c"]);
+
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "se wrap sbr=+");
+        assert!(editor.current_view().options.wrap);
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "set wrap? sbr?");
+        assert_eq!(editor.message.as_deref(), Some("wrap showbreak=+"));
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "set foo");
+        assert_eq!(editor.message.as_deref(), Some("Unknown option: foo"));
+
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "colo");
+        assert_eq!(editor.message.as_deref(), Some("default"));
+        press_keys(&mut editor, ":");
+        type_command(
+            &mut editor,
+            concat!(
+                "colorscheme ",
+                env!("CARGO_MANIFEST_DIR"),
+                "/colors/dark.theme"
+            ),
+        );
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "colo");
+        assert!(editor
+            .message
+            .as_deref()
+            .is_some_and(|name| name.ends_with("dark.theme")));
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "colo ./missing.theme");
+        assert_eq!(
+            editor.message.as_deref(),
+            Some("Cannot find color scheme: ./missing.theme")
+        );
+    }
+
+    #[test]
+    fn test_visual() {
+        let mut editor = editor_from("abc def\nghi jkl\nmno\n");
+        press_keys(&mut editor, "lvjd");
+        assert_eq!(contents(&editor), vec!["ai jkl", "mno"]);
+        assert_eq!(editor.registers.get('"').unwrap().content, "bc def\ngh");
+        assert_eq!(editor.mode, Mode::Normal);
+        press_keys(&mut editor, "u");
+        press_keys(&mut editor, "Vjy");
+        assert_eq!(
+            editor.registers.get('"'),
+            Some(Register::linewise(&[
+                "abc def".to_string(),
+                "ghi jkl".to_string()
+            ]))
+        );
+        assert_eq!(
+            editor.current_view().buffer.get_mark('>').unwrap().line_idx,
+            1
+        );
+        press_keys(&mut editor, "vll~");
+        assert_eq!(contents(&editor), vec!["aBC def", "ghi jkl", "mno"]);
+        press_keys(&mut editor, "VjJ");
+        assert_eq!(contents(&editor), vec!["aBC def ghi jkl", "mno"]);
+        press_keys(&mut editor, "Vj>");
+        assert_eq!(contents(&editor), vec!["\taBC def ghi jkl", "\tmno"]);
+        press_keys(&mut editor, "vo<");
+        assert_eq!(contents(&editor), vec!["aBC def ghi jkl", "\tmno"]);
+        press_keys(&mut editor, "vjvvV");
+        assert_eq!(editor.mode, Mode::VisualLine);
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "d");
+        assert_eq!(contents(&editor), vec!["aBC def ghi jkl"]);
+    }
+
+    #[test]
+    fn test_shift_operator() {
+        let mut editor = editor_from("a\n  b\n\nc\nd\n");
+        press_keys(&mut editor, ">>");
+        assert_eq!(contents(&editor), vec!["\ta", "  b", "", "c", "d"]);
+        press_keys(&mut editor, "j2<<");
+        assert_eq!(contents(&editor), vec!["\ta", "b", "", "c", "d"]);
+        // the empty line is not indented
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "set et sw=2");
+        press_keys(&mut editor, ">G");
+        assert_eq!(contents(&editor), vec!["\ta", "  b", "", "  c", "  d"]);
+        assert_eq!(editor.message.as_deref(), Some("4 lines >ed 1 time"));
+        assert_eq!(editor.current_view().cursor.position(), Position::new(1, 2));
+        press_keys(&mut editor, "gg>j");
+        assert_eq!(
+            contents(&editor),
+            vec!["      a", "    b", "", "  c", "  d"]
+        );
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor), vec!["\ta", "  b", "", "  c", "  d"]);
+        press_keys(&mut editor, "j.");
+        assert_eq!(contents(&editor), vec!["\ta", "    b", "", "  c", "  d"]);
+    }
+
+    #[test]
+    fn test_insert_indent() {
+        let mut editor = editor_from("ab\n");
+        let key = |editor: &mut Editor, code: KeyCode| {
+            editor.handle_key_event(code, KeyModifiers::NONE);
+        };
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "set et sts=4");
+        press_keys(&mut editor, "a");
+        key(&mut editor, KeyCode::Tab);
+        assert_eq!(contents(&editor), vec!["a   b"]);
+        key(&mut editor, KeyCode::Tab);
+        assert_eq!(contents(&editor), vec!["a       b"]);
+        // back to the stop of `softtabstop`
+        key(&mut editor, KeyCode::Backspace);
+        assert_eq!(contents(&editor), vec!["a   b"]);
+        key(&mut editor, KeyCode::Backspace);
+        assert_eq!(contents(&editor), vec!["ab"]);
+        key(&mut editor, KeyCode::Backspace);
+        assert_eq!(contents(&editor), vec!["b"]);
+        key(&mut editor, KeyCode::Esc);
+        // the spaces become a tab at a tab stop without `expandtab`
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "set noet sts=2");
+        press_keys(&mut editor, "I");
+        key(&mut editor, KeyCode::Tab);
+        assert_eq!(contents(&editor), vec!["  b"]);
+        key(&mut editor, KeyCode::Tab);
+        assert_eq!(contents(&editor), vec!["\tb"]);
+        key(&mut editor, KeyCode::Backspace);
+        assert_eq!(contents(&editor), vec!["  b"]);
+        // <C-T> and <C-D> keep the cursor on the same character
+        editor.handle_key_event(KeyCode::Char('t'), KeyModifiers::CONTROL);
+        assert_eq!(contents(&editor), vec!["\t  b"]);
+        assert_eq!(editor.current_view().cursor.position(), Position::new(0, 6));
+        editor.handle_key_event(KeyCode::Char('d'), KeyModifiers::CONTROL);
+        editor.handle_key_event(KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert_eq!(contents(&editor), vec!["b"]);
+        assert_eq!(editor.current_view().cursor.position(), Position::new(0, 0));
+        key(&mut editor, KeyCode::Esc);
+        // a literal tab by default
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "set sts=0");
+        press_keys(&mut editor, "A");
+        key(&mut editor, KeyCode::Tab);
+        assert_eq!(contents(&editor), vec!["b\t"]);
+    }
+
+    #[test]
+    fn test_autoindent() {
+        let mut editor = editor_from("  a b\n");
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "set ai");
+        // the text after the cursor loses its own indent
+        press_keys(&mut editor, "fbi");
+        editor.handle_key_event(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(contents(&editor), vec!["  a ", "  b"]);
+        assert_eq!(editor.current_view().cursor.position(), Position::new(1, 2));
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        press_keys(&mut editor, "ox");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(contents(&editor), vec!["  a ", "  b", "  x"]);
+        // from the line below with `O`
+        press_keys(&mut editor, ">>Oy");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(contents(&editor), vec!["  a ", "  b", "\t  y", "\t  x"]);
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "set noai");
+        press_keys(&mut editor, "oz");
+        assert_eq!(contents(&editor)[3], "z");
+
+        // an indent with nothing typed after it is removed
+        let mut editor = editor_from("  a\n");
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "set ai");
+        press_keys(&mut editor, "o");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(contents(&editor), vec!["  a", ""]);
+        assert_eq!(editor.current_view().cursor.position(), Position::new(1, 0));
+        press_keys(&mut editor, "kA");
+        editor.handle_key_event(KeyCode::Enter, KeyModifiers::NONE);
+        editor.handle_key_event(KeyCode::Enter, KeyModifiers::NONE);
+        press_keys(&mut editor, "b");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(contents(&editor), vec!["  a", "", "  b", ""]);
+        press_keys(&mut editor, "O");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(contents(&editor), vec!["  a", "", "", "  b", ""]);
+    }
+
+    #[test]
+    fn test_smartindent() {
+        let mut editor = editor_from("fn a() {\n}\n");
+        editor.current_view_mut().buffer.file_info = FileInfo::from("a.rs");
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "set si et");
+        press_keys(&mut editor, "A");
+        editor.handle_key_event(KeyCode::Enter, KeyModifiers::NONE);
+        press_keys(&mut editor, "if b {");
+        editor.handle_key_event(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(
+            contents(&editor),
+            vec!["fn a() {", "    if b {", "        ", "}"]
+        );
+        // a closing bracket goes back to the line opening it
+        press_keys(&mut editor, "}");
+        assert_eq!(
+            contents(&editor),
+            vec!["fn a() {", "    if b {", "    }", "}"]
+        );
+        assert_eq!(editor.current_view().cursor.position(), Position::new(2, 5));
+        press_keys(&mut editor, "}");
+        assert_eq!(contents(&editor)[2], "    }}");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        press_keys(&mut editor, "ggOx");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(contents(&editor)[0], "x");
+    }
+
+    #[test]
+    fn test_reindent_operator() {
+        let mut editor = editor_from("fn a() {\nlet s = \"x\n  y\";\n  if b {\nc(\nd);\n}\n}\n");
+        editor.current_view_mut().buffer.file_info = FileInfo::from("a.rs");
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "set et");
+        press_keys(&mut editor, "jj=G");
+        assert_eq!(
+            contents(&editor),
+            vec![
+                "fn a() {",
+                "let s = \"x",
+                "  y\";",
+                "    if b {",
+                "        c(",
+                "            d);",
+                "    }",
+                "}"
+            ]
+        );
+        assert_eq!(editor.message.as_deref(), Some("6 lines indented"));
+        assert_eq!(editor.current_view().cursor.position(), Position::new(2, 2));
+        press_keys(&mut editor, "k==");
+        assert_eq!(contents(&editor)[1], "    let s = \"x");
+        // the line in the string is kept
+        press_keys(&mut editor, "Vj=");
+        assert_eq!(contents(&editor)[2], "  y\";");
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor)[1], "let s = \"x");
+        // the indent of the line above in a text file
+        let mut editor = editor_from("  a\nb\n\nc\n");
+        press_keys(&mut editor, "j=G");
+        assert_eq!(contents(&editor), vec!["  a", "  b", "", "  c"]);
+    }
+
+    #[test]
+    fn test_visual_block() {
+        let mut editor = editor_from("あいう\nabcdef\nxy\tz\n");
+        // from い to the tab
+        press_keys(&mut editor, "l");
+        editor.handle_key_event(KeyCode::Char('v'), KeyModifiers::CONTROL);
+        assert_eq!(editor.mode, Mode::VisualBlock);
+        press_keys(&mut editor, "jjy");
+        assert_eq!(
+            editor.registers.get('"'),
+            Some(Register::blockwise(&[
+                "い".to_string(),
+                "cd".to_string(),
+                "\t".to_string()
+            ]))
+        );
+        assert_eq!(editor.current_view().cursor.position(), Position::new(0, 2));
+        editor.handle_key_event(KeyCode::Char('v'), KeyModifiers::CONTROL);
+        press_keys(&mut editor, "jyP");
+        assert_eq!(contents(&editor), vec!["あいいう", "abcdcdef", "xy\tz"]);
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor), vec!["あいう", "abcdef", "xy\tz"]);
+        press_keys(&mut editor, "0l");
+        editor.handle_key_event(KeyCode::Char('v'), KeyModifiers::CONTROL);
+        press_keys(&mut editor, "jc");
+        assert_eq!(contents(&editor), vec!["あう", "abef", "xy\tz"]);
+        press_keys(&mut editor, "x");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(contents(&editor), vec!["あxう", "abxef", "xy\tz"]);
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor), vec!["あいう", "abcdef", "xy\tz"]);
+    }
+
+    #[test]
+    fn test_repeat() {
+        let mut editor = editor_from("abc\ndef\nghi\njkl\nmno\n");
+        press_keys(&mut editor, "A!");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        press_keys(&mut editor, "j.");
+        assert_eq!(contents(&editor), vec!["abc!", "def!", "ghi", "jkl", "mno"]);
+        // motions and yanks are not changes
+        press_keys(&mut editor, "jyyj0.");
+        assert_eq!(
+            contents(&editor),
+            vec!["abc!", "def!", "ghi", "jkl!", "mno"]
+        );
+        press_keys(&mut editor, "\"add.");
+        assert_eq!(contents(&editor), vec!["abc!", "def!", "ghi"]);
+        assert_eq!(editor.registers.get('a').unwrap().content, "mno\n");
+        press_keys(&mut editor, "kkk0vld.");
+        assert_eq!(contents(&editor), vec!["", "def!", "ghi"]);
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor), vec!["c!", "def!", "ghi"]);
+        press_keys(&mut editor, "jccx");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        press_keys(&mut editor, "j.");
+        assert_eq!(contents(&editor), vec!["c!", "x", "x"]);
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor), vec!["c!", "x", "ghi"]);
+    }
+
+    #[test]
+    fn test_macro() {
+        let mut editor = editor_from("a\nb\nc\nd\n");
+        press_keys(&mut editor, "qaA<x");
+        assert_eq!(editor.recording, Some('a'));
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        press_keys(&mut editor, "jq");
+        assert_eq!(editor.recording, None);
+        assert_eq!(editor.registers.get('a').unwrap().content, "A<LT>x<ESC>j");
+        press_keys(&mut editor, "@a@@");
+        assert_eq!(contents(&editor), vec!["a<x", "b<x", "c<x", "d"]);
+        // each command in the macro is an undo step
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor), vec!["a<x", "b<x", "c", "d"]);
+
+        // recorded macros can be edited as text and put back
+        press_keys(&mut editor, "qb0xq");
+        assert_eq!(contents(&editor), vec!["a<x", "b<x", "", "d"]);
+        press_keys(&mut editor, "\"bp");
+        assert_eq!(contents(&editor), vec!["a<x", "b<x", "0x", "d"]);
+        press_keys(&mut editor, "0\"cy$j@c");
+        assert_eq!(contents(&editor), vec!["a<x", "b<x", "0x", ""]);
+
+        // a macro calling itself stops at the depth limit
+        press_keys(&mut editor, "qdq");
+        editor.registers.record('d', "@d");
+        press_keys(&mut editor, "@d");
+        assert_eq!(editor.message.as_deref(), Some("Macro nested too deeply"));
+
+        press_keys(&mut editor, ":");
+        type_command(&mut editor, "echo hi");
+        editor.message = None;
+        press_keys(&mut editor, "@:");
+        assert_eq!(editor.message.as_deref(), Some("hi"));
+
+        // the keys replayed by `.` and `:normal` are not recorded again
+        let mut editor = editor_from("a\nb\nc\nd\n");
+        press_keys(&mut editor, "A!");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        press_keys(&mut editor, "qaj.q");
+        assert_eq!(editor.registers.get('a').unwrap().content, "j.");
+        press_keys(&mut editor, "@a");
+        assert_eq!(contents(&editor), vec!["a!", "b!", "c!", "d"]);
+        press_keys(&mut editor, "0qb:normal x");
+        editor.handle_key_event(KeyCode::Enter, KeyModifiers::NONE);
+        press_keys(&mut editor, "q");
+        assert_eq!(
+            editor.registers.get('b').unwrap().content,
+            ":normal<SPACE>x<CR>"
+        );
+        assert_eq!(contents(&editor), vec!["a!", "b!", "!", "d"]);
+        press_keys(&mut editor, "j0@b");
+        assert_eq!(contents(&editor), vec!["a!", "b!", "!", ""]);
+    }
+
+    #[test]
+    fn test_word_motion() {
+        let mut editor = editor_from("one two three\nfour  five\n日本語のテキスト\n");
+        press_keys(&mut editor, "dw");
+        assert_eq!(contents(&editor)[0], "two three");
+        press_keys(&mut editor, "wdw");
+        // `dw` does not join the next line
+        assert_eq!(contents(&editor)[..2], ["two ", "four  five"]);
+        press_keys(&mut editor, "0cwx");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(contents(&editor)[0], "x ");
+        press_keys(&mut editor, "j0de");
+        assert_eq!(contents(&editor)[1], "  five");
+        press_keys(&mut editor, "0d2W");
+        assert_eq!(contents(&editor)[1], "");
+        press_keys(&mut editor, "u");
+        press_keys(&mut editor, "jdw");
+        assert_eq!(contents(&editor)[2], "のテキスト");
+        // `w` on the last word stops on the last character
+        press_keys(&mut editor, "2w");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(2, 8));
+        press_keys(&mut editor, "gg");
+        assert_eq!(editor.current_view().cursor.line_idx(), 0);
+        press_keys(&mut editor, "jjdgg");
+        assert!(contents(&editor).is_empty());
+
+        // `ge` includes the character under the cursor
+        let mut editor = editor_from("one two\n");
+        press_keys(&mut editor, "wdge");
+        assert_eq!(contents(&editor), vec!["onwo"]);
+        // `dw` on the last word deletes up to the end
+        let mut editor = editor_from("one two\n");
+        press_keys(&mut editor, "ww");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(0, 6));
+        press_keys(&mut editor, "bdw");
+        assert_eq!(contents(&editor), vec!["one "]);
+    }
+
+    #[test]
+    fn test_find_char() {
+        let mut editor = editor_from("fn f(a, b, c) -> 日本\n");
+        press_keys(&mut editor, "dt)");
+        assert_eq!(contents(&editor)[0], ") -> 日本");
+        press_keys(&mut editor, "u0f,;");
+        assert_eq!(editor.current_view().cursor.col_idx(), 9);
+        press_keys(&mut editor, ",");
+        assert_eq!(editor.current_view().cursor.col_idx(), 6);
+        press_keys(&mut editor, "0c2f,x");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(contents(&editor)[0], "x c) -> 日本");
+        press_keys(&mut editor, "f本");
+        assert_eq!(editor.current_view().cursor.col_idx(), 10);
+        press_keys(&mut editor, "dF>");
+        assert_eq!(contents(&editor)[0], "x c) -本");
+        // nothing is deleted when the character is not found
+        press_keys(&mut editor, "0dfz");
+        assert_eq!(contents(&editor)[0], "x c) -本");
+        press_keys(&mut editor, "dtz");
+        assert_eq!(contents(&editor)[0], "x c) -本");
+        assert_eq!(editor.current_view().cursor.col_idx(), 0);
+    }
+
+    #[test]
+    fn test_text_object() {
+        let mut editor = editor_from("let x = f(a, 「日本」);\n\nfn g() {\n    b;\n}\n");
+        press_keys(&mut editor, "diw");
+        assert_eq!(contents(&editor)[0], " x = f(a, 「日本」);");
+        press_keys(&mut editor, "f日ci「x");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(contents(&editor)[0], " x = f(a, 「x」);");
+        press_keys(&mut editor, "0fa\"aya(");
+        assert_eq!(editor.registers.get('a').unwrap().content, "(a, 「x」)");
+        press_keys(&mut editor, "jjjdi{");
+        assert_eq!(contents(&editor)[2..], ["fn g() {", "}"]);
+        press_keys(&mut editor, "u");
+        press_keys(&mut editor, "dap");
+        // no blank line after the last paragraph, the one before it is deleted
+        assert_eq!(contents(&editor), vec![" x = f(a, 「x」);"]);
+        // text objects in the visual mode
+        press_keys(&mut editor, "gg0favi(d");
+        assert_eq!(contents(&editor)[0], " x = f();");
+    }
+
+    #[test]
+    fn test_matching_bracket() {
+        let mut editor = editor_from("if (a[0]) {\n    b();\n}\n");
+        press_keys(&mut editor, "%");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(0, 8));
+        press_keys(&mut editor, "%");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(0, 3));
+        press_keys(&mut editor, "d%");
+        assert_eq!(contents(&editor), vec!["if  {", "    b();", "}"]);
+        press_keys(&mut editor, "f{d%");
+        assert_eq!(contents(&editor), vec!["if  "]);
+        press_keys(&mut editor, "u");
+        // a count is the percentage of the buffer
+        press_keys(&mut editor, "100%");
+        assert_eq!(editor.current_view().cursor.line_idx(), 2);
+        press_keys(&mut editor, "50%");
+        assert_eq!(editor.current_view().cursor.line_idx(), 1);
+    }
+
+    #[test]
+    fn test_count() {
+        let mut editor = editor_from("abcdef\n1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n");
+        press_keys(&mut editor, "3x");
+        assert_eq!(contents(&editor)[0], "def");
+        // `x` stops at the end of the line
+        press_keys(&mut editor, "l10x");
+        assert_eq!(contents(&editor)[0], "d");
+        press_keys(&mut editor, "5j");
+        assert_eq!(editor.current_view().cursor.line_idx(), 5);
+        press_keys(&mut editor, "10G");
+        assert_eq!(editor.current_view().cursor.line_idx(), 9);
+        press_keys(&mut editor, "2gg");
+        assert_eq!(editor.current_view().cursor.line_idx(), 1);
+        press_keys(&mut editor, "2dd");
+        assert_eq!(contents(&editor)[..3], ["d", "3", "4"]);
+        // the counts before and after the operator are multiplied
+        press_keys(&mut editor, "2d2j");
+        assert_eq!(contents(&editor)[..3], ["d", "8", "9"]);
+        press_keys(&mut editor, "u");
+        press_keys(&mut editor, "2");
+        assert_eq!(editor.pending_keys(), "2");
+        press_keys(&mut editor, "\"ad");
+        assert_eq!(editor.pending_keys(), "\"a2d");
+        press_keys(&mut editor, "3");
+        assert_eq!(editor.pending_keys(), "\"a2d3");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(editor.pending_keys(), "");
+
+        let mut editor = editor_from("a\nb\nc\nd\ne\nf\n");
+        press_keys(&mut editor, "3ix");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(contents(&editor)[0], "xxxa");
+        // one undo step for the whole insert
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor)[0], "a");
+        press_keys(&mut editor, "2oy");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(contents(&editor)[..4], ["a", "y", "y", "b"]);
+        press_keys(&mut editor, "yy2p");
+        assert_eq!(contents(&editor)[..6], ["a", "y", "y", "y", "y", "b"]);
+        press_keys(&mut editor, "2u");
+        assert_eq!(contents(&editor)[..2], ["a", "b"]);
+
+        // `.` repeats with the count of the change unless a new count is given
+        press_keys(&mut editor, "gg0jxj2dd");
+        assert_eq!(contents(&editor), vec!["a", "", "e", "f"]);
+        press_keys(&mut editor, ".");
+        assert_eq!(contents(&editor), vec!["a", ""]);
+        press_keys(&mut editor, "u");
+        press_keys(&mut editor, "gg3.");
+        assert_eq!(contents(&editor), vec!["f"]);
+
+        // `3@a` runs the macro 3 times
+        let mut editor = editor_from("1\n2\n3\n4\n5\n");
+        press_keys(&mut editor, "qaA!");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        press_keys(&mut editor, "jq3@a");
+        assert_eq!(contents(&editor), vec!["1!", "2!", "3!", "4!", "5"]);
+    }
+
+    #[test]
+    fn test_change_operator() {
+        let mut editor = editor_from("this\nis\ntest.\n");
+        press_keys(&mut editor, "jcc");
+        assert_eq!(contents(&editor), vec!["this", "", "test."]);
+        assert_eq!(editor.mode, Mode::Insert);
+        editor.handle_key_event_insert(KeyCode::Char('a'), KeyModifiers::NONE);
+        editor.handle_key_event_insert(KeyCode::Esc, KeyModifiers::NONE);
+        assert_eq!(contents(&editor), vec!["this", "a", "test."]);
+        press_keys(&mut editor, "kc$");
+        assert_eq!(contents(&editor), vec!["t", "a", "test."]);
+        editor.handle_key_event_insert(KeyCode::Esc, KeyModifiers::NONE);
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor), vec!["this", "a", "test."]);
+        press_keys(&mut editor, "u");
+        assert_eq!(contents(&editor), vec!["this", "is", "test."]);
+    }
+
     #[test]
     fn test_key_to_string() {
         let str = Editor::key_to_string(KeyCode::Char('a'), KeyModifiers::NONE);
@@ -576,5 +2821,120 @@ mod tests {
         assert_eq!(str, "<CR>");
         let str = Editor::key_to_string(KeyCode::Enter, KeyModifiers::SHIFT);
         assert_eq!(str, "<S-CR>");
+
+        // macros are stored in this notation
+        let keys = [
+            (KeyCode::Char('x'), KeyModifiers::NONE),
+            (KeyCode::Char('r'), KeyModifiers::CONTROL),
+            (
+                KeyCode::Char('a'),
+                KeyModifiers::ALT | KeyModifiers::CONTROL,
+            ),
+            (KeyCode::Left, KeyModifiers::SHIFT),
+            (KeyCode::Esc, KeyModifiers::NONE),
+        ];
+        let notation: String = keys
+            .iter()
+            .map(|(code, modifiers)| Editor::key_to_string(*code, *modifiers))
+            .collect();
+        assert_eq!(key_notation::parse_keys(&notation), keys);
+    }
+
+    #[test]
+    fn test_screen_motion() {
+        let text = (0..30)
+            .map(|idx| idx.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut editor = editor_from(&text);
+        // the view has 10 lines
+        press_keys(&mut editor, "L");
+        assert_eq!(editor.current_view().cursor.line_idx(), 9);
+        press_keys(&mut editor, "3H");
+        assert_eq!(editor.current_view().cursor.line_idx(), 2);
+        press_keys(&mut editor, "M");
+        assert_eq!(editor.current_view().cursor.line_idx(), 4);
+        press_keys(&mut editor, "15Gzt");
+        assert_eq!(editor.current_view().offset.line_idx, 14);
+        assert_eq!(editor.current_view().cursor.line_idx(), 14);
+        press_keys(&mut editor, "2L");
+        assert_eq!(editor.current_view().cursor.line_idx(), 22);
+        press_keys(&mut editor, "zb");
+        assert_eq!(editor.current_view().offset.line_idx, 13);
+        press_keys(&mut editor, "zz");
+        assert_eq!(editor.current_view().offset.line_idx, 18);
+        press_keys(&mut editor, "3zt");
+        assert_eq!(editor.current_view().offset.line_idx, 2);
+        assert_eq!(editor.current_view().cursor.line_idx(), 2);
+        press_keys(&mut editor, "d}");
+        assert_eq!(editor.current_view().buffer.get_lines_count(), 3);
+        press_keys(&mut editor, "u^d$");
+        assert_eq!(contents(&editor)[2], "");
+    }
+
+    #[test]
+    fn test_marks() {
+        let mut editor = editor_from("fn main() {\n    let a = 1;\n    let b = 2;\n}\n");
+        press_keys(&mut editor, "jwwmajjmA");
+        press_keys(&mut editor, "gg`a");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(1, 8));
+        press_keys(&mut editor, "gg'a");
+        assert_eq!(editor.current_view().cursor.position(), Position::new(1, 4));
+        // the marks move with the text
+        press_keys(&mut editor, "ggOuse x;");
+        editor.handle_key_event(KeyCode::Esc, KeyModifiers::NONE);
+        press_keys(&mut editor, "G'a");
+        assert_eq!(editor.current_view().cursor.line_idx(), 2);
+        press_keys(&mut editor, "d'A");
+        assert_eq!(contents(&editor), vec!["use x;", "fn main() {"]);
+        press_keys(&mut editor, "u");
+        // `` ` `` is the position before the last jump
+        press_keys(&mut editor, "gg''");
+        assert_eq!(editor.current_view().cursor.line_idx(), 2);
+        press_keys(&mut editor, "``");
+        assert_eq!(editor.current_view().cursor.line_idx(), 0);
+        press_keys(&mut editor, "yj`]");
+        assert_eq!(editor.current_view().cursor.line_idx(), 1);
+        press_keys(&mut editor, "`.");
+        assert_eq!(editor.current_view().cursor.line_idx(), 2);
+        press_keys(&mut editor, "'z");
+        assert_eq!(editor.message.as_deref(), Some("Mark not set"));
+    }
+
+    #[test]
+    fn test_jumplist() {
+        let mut editor = editor_from("a\nb\nc\nd\ne\nf\n");
+        press_keys(&mut editor, "3Gj5G/f");
+        editor.handle_key_event(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(editor.current_view().cursor.line_idx(), 5);
+        let ctrl_o = |editor: &mut Editor| {
+            editor.handle_key_event(KeyCode::Char('o'), KeyModifiers::CONTROL);
+        };
+        ctrl_o(&mut editor);
+        assert_eq!(editor.current_view().cursor.line_idx(), 4);
+        press_keys(&mut editor, "2");
+        ctrl_o(&mut editor);
+        assert_eq!(editor.current_view().cursor.line_idx(), 0);
+        ctrl_o(&mut editor);
+        assert_eq!(editor.current_view().cursor.line_idx(), 0);
+        editor.handle_key_event(KeyCode::Tab, KeyModifiers::NONE);
+        assert_eq!(editor.current_view().cursor.line_idx(), 3);
+        press_keys(&mut editor, "2");
+        editor.handle_key_event(KeyCode::Tab, KeyModifiers::NONE);
+        assert_eq!(editor.current_view().cursor.line_idx(), 5);
+    }
+
+    #[test]
+    fn test_changelist() {
+        let mut editor = editor_from("a\nb\nc\nd\n");
+        press_keys(&mut editor, "xjjxjx");
+        press_keys(&mut editor, "g;");
+        assert_eq!(editor.current_view().cursor.line_idx(), 3);
+        press_keys(&mut editor, "g;");
+        assert_eq!(editor.current_view().cursor.line_idx(), 2);
+        press_keys(&mut editor, "2g;");
+        assert_eq!(editor.message.as_deref(), Some("At start of changelist"));
+        press_keys(&mut editor, "g;g,");
+        assert_eq!(editor.current_view().cursor.line_idx(), 2);
     }
 }
@@ -0,0 +1,136 @@
+use super::position::Position;
+use std::ops::Range;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+    // `>` and `<`, the lines of the range are shifted
+    ShiftRight,
+    ShiftLeft,
+    // `=`, the lines of the range are indented again
+    Reindent,
+}
+
+impl Operator {
+    pub fn from_key(key_repr: &str) -> Option<Self> {
+        match key_repr {
+            "d" => Some(Operator::Delete),
+            "c" => Some(Operator::Change),
+            "y" => Some(Operator::Yank),
+            ">" => Some(Operator::ShiftRight),
+            "<" => Some(Operator::ShiftLeft),
+            "=" => Some(Operator::Reindent),
+            _ => None,
+        }
+    }
+    pub fn key(self) -> &'static str {
+        match self {
+            Operator::Delete => "d",
+            Operator::Change => "c",
+            Operator::Yank => "y",
+            Operator::ShiftRight => ">",
+            Operator::ShiftLeft => "<",
+            Operator::Reindent => "=",
+        }
+    }
+}
+
+// the region an operator is applied to
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TextRange {
+    // `end` is exclusive
+    Charwise {
+        start: Position,
+        end: Position,
+    },
+    // line indices, `end` is exclusive
+    Linewise(Range<usize>),
+    // a rectangle of display columns, both `end` are exclusive
+    Blockwise {
+        lines: Range<usize>,
+        cols: Range<usize>,
+    },
+}
+
+impl TextRange {
+    // build a range from the cursor position and the motion target
+    pub fn charwise(from: Position, to: Position) -> Self {
+        TextRange::Charwise {
+            start: from.min(to),
+            end: from.max(to),
+        }
+    }
+    pub fn linewise(from: usize, to: usize) -> Self {
+        TextRange::Linewise(from.min(to)..from.max(to).saturating_add(1))
+    }
+    pub fn start(&self) -> Position {
+        match self {
+            TextRange::Charwise { start, .. } => *start,
+            TextRange::Linewise(lines) => Position {
+                line_idx: lines.start,
+                col_idx: 0,
+            },
+            TextRange::Blockwise { lines, cols } => Position {
+                line_idx: lines.start,
+                col_idx: cols.start,
+            },
+        }
+    }
+    // display columns covered in the line
+    pub fn cols_in_line(&self, line_idx: usize, line_width: usize) -> Range<usize> {
+        match self {
+            TextRange::Charwise { start, end } => {
+                let from = if line_idx == start.line_idx {
+                    start.col_idx
+                } else {
+                    0
+                };
+                let to = if line_idx == end.line_idx {
+                    end.col_idx
+                } else {
+                    line_width
+                };
+                from..to
+            }
+            TextRange::Linewise(_) => 0..line_width,
+            TextRange::Blockwise { cols, .. } => cols.clone(),
+        }
+    }
+    // line indices covered by the range, `end` is exclusive
+    pub fn lines(&self) -> Range<usize> {
+        match self {
+            TextRange::Charwise { start, end } => start.line_idx..end.line_idx.saturating_add(1),
+            TextRange::Linewise(lines) | TextRange::Blockwise { lines, .. } => lines.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_range() {
+        let range = TextRange::charwise(Position::new(2, 1), Position::new(0, 3));
+        assert_eq!(
+            range,
+            TextRange::Charwise {
+                start: Position::new(0, 3),
+                end: Position::new(2, 1)
+            }
+        );
+        assert_eq!(range.start(), Position::new(0, 3));
+        let range = TextRange::linewise(3, 1);
+        assert_eq!(range, TextRange::Linewise(1..4));
+        assert_eq!(range.start(), Position::new(1, 0));
+        assert_eq!(range.lines(), 1..4);
+        let range = TextRange::Blockwise {
+            lines: 1..3,
+            cols: 2..6,
+        };
+        assert_eq!(range.start(), Position::new(1, 2));
+        assert_eq!(range.lines(), 1..3);
+    }
+}
@@ -0,0 +1,277 @@
+use super::list_chars::ListChars;
+use super::text_fragment::TAB_WIDTH;
+
+// the full name and the short name of each option
+const NAMES: [(&str, &str); 14] = [
+    ("autoindent", "ai"),
+    ("breakindent", "bri"),
+    ("expandtab", "et"),
+    ("kinsoku", "kin"),
+    ("list", "list"),
+    ("listchars", "lcs"),
+    ("number", "nu"),
+    ("relativenumber", "rnu"),
+    ("shiftwidth", "sw"),
+    ("showbreak", "sbr"),
+    ("smartindent", "si"),
+    ("softtabstop", "sts"),
+    ("tabstop", "ts"),
+    ("wrap", "wrap"),
+];
+
+// options changed by `:set`
+// each boolean option is a flag of its own
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Options {
+    // long lines continue on the next screen rows instead of scrolling horizontally
+    pub wrap: bool,
+    // shown at the start of the continued rows of a wrapped line
+    pub showbreak: String,
+    // the continued rows are indented as much as the line
+    pub breakindent: bool,
+    // wrapped rows follow the Japanese line breaking rules
+    pub kinsoku: bool,
+    // line numbers in the gutter, both of them show the hybrid numbers
+    pub number: bool,
+    pub relativenumber: bool,
+    // whitespace and invisible characters are shown with `listchars`
+    pub list: bool,
+    pub listchars: ListChars,
+    // the columns a tab takes, each buffer has its own
+    pub tabstop: usize,
+    // <Tab> and the indent are made of spaces
+    pub expandtab: bool,
+    // the columns of an indent level for `>>` and <C-T>, `tabstop` when it is 0
+    pub shiftwidth: usize,
+    // <Tab> and <BS> move by this many columns of spaces, off when it is 0
+    pub softtabstop: usize,
+    // a new line starts with the indent of the line it is opened from
+    pub autoindent: bool,
+    // a new line is indented by the rules of the file type, and a closing bracket goes back
+    pub smartindent: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            wrap: false,
+            showbreak: String::new(),
+            breakindent: false,
+            kinsoku: false,
+            number: false,
+            relativenumber: false,
+            list: true,
+            listchars: ListChars::default(),
+            tabstop: TAB_WIDTH,
+            expandtab: false,
+            shiftwidth: 0,
+            softtabstop: 0,
+            autoindent: false,
+            smartindent: false,
+        }
+    }
+}
+
+impl Options {
+    // apply the arguments of `:set` like `wrap showbreak=>>\ `
+    // returns the values asked with `name?`
+    pub fn set(&mut self, args: &str) -> Result<Option<String>, String> {
+        let mut shown = Vec::new();
+        for arg in split_args(args) {
+            if let Some(name) = arg.strip_suffix('?') {
+                shown.push(self.show(full_name(name)?));
+            } else if let Some((name, value)) = arg.split_once('=') {
+                self.set_string(full_name(name)?, value)?;
+            } else if let Some(name) = arg.strip_suffix('!') {
+                let name = full_name(name)?;
+                let value = self.get_bool(name)?;
+                self.set_bool(name, !value)?;
+            } else if let Ok(name) = full_name(&arg) {
+                self.set_bool(name, true)?;
+            } else if let Some(name) = arg.strip_prefix("no") {
+                self.set_bool(full_name(name)?, false)?;
+            } else if let Some(name) = arg.strip_prefix("inv") {
+                let name = full_name(name)?;
+                let value = self.get_bool(name)?;
+                self.set_bool(name, !value)?;
+            } else {
+                return Err(format!("Unknown option: {arg}"));
+            }
+        }
+        Ok((!shown.is_empty()).then(|| shown.join(" ")))
+    }
+    pub fn shift_width(&self) -> usize {
+        if self.shiftwidth == 0 {
+            self.tabstop
+        } else {
+            self.shiftwidth
+        }
+    }
+    // the columns between the stops <Tab> moves to
+    pub fn soft_tab_width(&self) -> usize {
+        if self.softtabstop == 0 {
+            self.tabstop
+        } else {
+            self.softtabstop
+        }
+    }
+    fn get_bool(&self, name: &str) -> Result<bool, String> {
+        match name {
+            "wrap" => Ok(self.wrap),
+            "breakindent" => Ok(self.breakindent),
+            "kinsoku" => Ok(self.kinsoku),
+            "number" => Ok(self.number),
+            "relativenumber" => Ok(self.relativenumber),
+            "list" => Ok(self.list),
+            "expandtab" => Ok(self.expandtab),
+            "autoindent" => Ok(self.autoindent),
+            "smartindent" => Ok(self.smartindent),
+            _ => Err(format!("Invalid argument: {name}")),
+        }
+    }
+    fn set_bool(&mut self, name: &str, value: bool) -> Result<(), String> {
+        match name {
+            "wrap" => self.wrap = value,
+            "breakindent" => self.breakindent = value,
+            "kinsoku" => self.kinsoku = value,
+            "number" => self.number = value,
+            "relativenumber" => self.relativenumber = value,
+            "list" => self.list = value,
+            "expandtab" => self.expandtab = value,
+            "autoindent" => self.autoindent = value,
+            "smartindent" => self.smartindent = value,
+            _ => return Err(format!("Invalid argument: {name}")),
+        }
+        Ok(())
+    }
+    fn set_string(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "showbreak" => self.showbreak = value.to_string(),
+            "listchars" => self.listchars = ListChars::parse(value)?,
+            "tabstop" => self.tabstop = parse_width(name, value, 1)?,
+            "shiftwidth" => self.shiftwidth = parse_width(name, value, 0)?,
+            "softtabstop" => self.softtabstop = parse_width(name, value, 0)?,
+            _ => return Err(format!("Invalid argument: {name}")),
+        }
+        Ok(())
+    }
+    fn show(&self, name: &str) -> String {
+        match name {
+            "showbreak" => format!("showbreak={}", self.showbreak),
+            "listchars" => format!("listchars={}", self.listchars),
+            "tabstop" => format!("tabstop={}", self.tabstop),
+            "shiftwidth" => format!("shiftwidth={}", self.shiftwidth),
+            "softtabstop" => format!("softtabstop={}", self.softtabstop),
+            _ if self.get_bool(name).unwrap_or_default() => name.to_string(),
+            _ => format!("no{name}"),
+        }
+    }
+}
+
+// a number of columns from `min` up to 32
+fn parse_width(name: &str, value: &str, min: usize) -> Result<usize, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|width| (min..=32).contains(width))
+        .ok_or(format!("Invalid argument: {name}={value}"))
+}
+
+fn full_name(name: &str) -> Result<&'static str, String> {
+    NAMES
+        .iter()
+        .find(|(full_name, short_name)| name == *full_name || name == *short_name)
+        .map(|(full_name, _)| *full_name)
+        .ok_or(format!("Unknown option: {name}"))
+}
+
+// split at spaces, a space escaped with a backslash is a part of the value
+fn split_args(args: &str) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut current = String::new();
+    let mut chars = args.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => current.extend(chars.next()),
+            ' ' => {
+                if !current.is_empty() {
+                    result.push(std::mem::take(&mut current));
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        result.push(current);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set() {
+        let mut options = Options::default();
+        assert_eq!(options.set("wrap bri kin"), Ok(None));
+        assert!(options.kinsoku);
+        assert!(options.wrap);
+        assert!(options.breakindent);
+        assert_eq!(options.set("nowrap sbr=>>\\ "), Ok(None));
+        assert!(!options.wrap);
+        assert_eq!(options.showbreak, ">> ");
+        assert_eq!(options.set("wrap! invbreakindent"), Ok(None));
+        assert!(options.wrap);
+        assert!(!options.breakindent);
+        assert_eq!(
+            options.set("wrap? bri? sbr?"),
+            Ok(Some("wrap nobreakindent showbreak=>> ".to_string()))
+        );
+        assert!(options.set("nosbr").is_err());
+        assert!(options.set("foo").is_err());
+    }
+
+    #[test]
+    fn test_list() {
+        let mut options = Options::default();
+        assert!(options.list);
+        assert_eq!(options.tabstop, 4);
+        assert_eq!(options.set("nolist ts=8 lcs=tab:>\\ ,eol:$"), Ok(None));
+        assert!(!options.list);
+        assert_eq!(options.tabstop, 8);
+        assert_eq!(options.listchars.tab, Some(('>', ' ')));
+        assert_eq!(
+            options.set("list? ts? lcs?"),
+            Ok(Some("nolist tabstop=8 listchars=tab:> ,eol:$".to_string())),
+        );
+        assert!(options.set("ts=0").is_err());
+        assert!(options.set("ts=a").is_err());
+        assert!(options.set("lcs=tab:>").is_err());
+    }
+
+    #[test]
+    fn test_indent() {
+        let mut options = Options::default();
+        assert_eq!(options.shift_width(), 4);
+        assert_eq!(options.soft_tab_width(), 4);
+        assert_eq!(options.set("et sw=2 sts=3 ts=8"), Ok(None));
+        assert!(options.expandtab);
+        assert_eq!(options.shift_width(), 2);
+        assert_eq!(options.soft_tab_width(), 3);
+        assert_eq!(
+            options.set("sw? sts?"),
+            Ok(Some("shiftwidth=2 softtabstop=3".to_string()))
+        );
+        assert_eq!(options.set("sw=0 sts=0"), Ok(None));
+        assert_eq!(options.shift_width(), 8);
+        assert!(options.set("sw=33").is_err());
+        assert!(!options.autoindent && !options.smartindent);
+        assert_eq!(options.set("ai si"), Ok(None));
+        assert_eq!(
+            options.set("ai? nosi si?"),
+            Ok(Some("autoindent nosmartindent".to_string()))
+        );
+    }
+}
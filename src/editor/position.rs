@@ -1,6 +1,7 @@
 use std::fmt;
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+// derived ordering compares line_idx first, then col_idx
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Default)]
 pub struct Position {
     pub line_idx: usize,
     pub col_idx: usize,
@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RegisterKind {
+    Charwise,
+    // content ends with a newline
+    Linewise,
+    // lines of a rectangle, joined by newlines
+    Blockwise,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Register {
+    pub content: String,
+    pub kind: RegisterKind,
+}
+
+impl Register {
+    pub fn charwise(content: &str) -> Self {
+        Self {
+            content: content.to_string(),
+            kind: RegisterKind::Charwise,
+        }
+    }
+    pub fn linewise(lines: &[String]) -> Self {
+        Self {
+            content: lines.iter().fold(String::new(), |mut output, line| {
+                let _ = writeln!(output, "{line}");
+                output
+            }),
+            kind: RegisterKind::Linewise,
+        }
+    }
+    pub fn blockwise(lines: &[String]) -> Self {
+        Self {
+            content: lines.join("\n"),
+            kind: RegisterKind::Blockwise,
+        }
+    }
+    // content split into lines, the trailing newline of linewise content is dropped
+    pub fn lines(&self) -> Vec<String> {
+        let content = match self.kind {
+            RegisterKind::Charwise | RegisterKind::Blockwise => &self.content,
+            RegisterKind::Linewise => self.content.strip_suffix('\n').unwrap_or(&self.content),
+        };
+        content.split('\n').map(String::from).collect()
+    }
+    // the content put `count` times, lines of a block are repeated side by side
+    pub fn repeat(&self, count: usize) -> Self {
+        match self.kind {
+            RegisterKind::Charwise | RegisterKind::Linewise => Self {
+                content: self.content.repeat(count),
+                kind: self.kind,
+            },
+            RegisterKind::Blockwise => Self::blockwise(
+                &self
+                    .lines()
+                    .iter()
+                    .map(|line| line.repeat(count))
+                    .collect::<Vec<_>>(),
+            ),
+        }
+    }
+    fn append(&mut self, other: &Self) {
+        if self.kind == RegisterKind::Charwise && other.kind == RegisterKind::Charwise {
+            self.content.push_str(&other.content);
+            return;
+        }
+        // if either is linewise, the result is linewise
+        if !self.content.ends_with('\n') {
+            self.content.push('\n');
+        }
+        self.content.push_str(&other.content);
+        if !self.content.ends_with('\n') {
+            self.content.push('\n');
+        }
+        self.kind = RegisterKind::Linewise;
+    }
+}
+
+pub const UNNAMED: char = '"';
+pub const BLACK_HOLE: char = '_';
+pub const FILE_NAME: char = '%';
+pub const LAST_COMMAND: char = ':';
+pub const LAST_SEARCH: char = '/';
+const NUMBERED_COUNT: usize = 10;
+
+#[derive(Default)]
+pub struct Registers {
+    unnamed: Option<Register>,
+    // a-z, uppercase names are stored as lowercase
+    named: HashMap<char, Register>,
+    // 0 is the last yank, 1-9 are the history of deletes
+    numbered: [Option<Register>; NUMBERED_COUNT],
+    last_command: Option<String>,
+    last_search: Option<String>,
+}
+
+impl Registers {
+    pub fn is_valid_name(name: char) -> bool {
+        name.is_ascii_alphanumeric()
+            || [UNNAMED, BLACK_HOLE, FILE_NAME, LAST_COMMAND, LAST_SEARCH].contains(&name)
+    }
+    // the file name register is not stored here, the editor provides it
+    pub fn get(&self, name: char) -> Option<Register> {
+        match name {
+            UNNAMED => self.unnamed.clone(),
+            'a'..='z' | 'A'..='Z' => self.named.get(&name.to_ascii_lowercase()).cloned(),
+            '0'..='9' => name
+                .to_digit(10)
+                .and_then(|idx| self.numbered.get(usize::try_from(idx).ok()?))
+                .cloned()
+                .flatten(),
+            LAST_COMMAND => self.last_command.as_deref().map(Register::charwise),
+            LAST_SEARCH => self.last_search.as_deref().map(Register::charwise),
+            _ => None,
+        }
+    }
+    // write to the named register, returns false if the register is read-only
+    fn set(&mut self, name: char, register: Register) -> bool {
+        match name {
+            'a'..='z' => {
+                self.named.insert(name, register);
+            }
+            'A'..='Z' => {
+                let name = name.to_ascii_lowercase();
+                if let Some(existing) = self.named.get_mut(&name) {
+                    existing.append(&register);
+                } else {
+                    self.named.insert(name, register);
+                }
+            }
+            '0'..='9' => {
+                if let Some(slot) = name
+                    .to_digit(10)
+                    .and_then(|idx| self.numbered.get_mut(usize::try_from(idx).ok()?))
+                {
+                    *slot = Some(register);
+                }
+            }
+            UNNAMED => {
+                self.unnamed = Some(register);
+            }
+            _ => return false,
+        }
+        true
+    }
+    fn set_unnamed_from(&mut self, name: char) {
+        self.unnamed = self.get(name);
+    }
+    // store yanked text, returns false if the register is read-only
+    pub fn yank(&mut self, name: Option<char>, register: Register) -> bool {
+        match name {
+            Some(BLACK_HOLE) => true,
+            Some(name) if name != UNNAMED => {
+                let result = self.set(name, register);
+                if result {
+                    self.set_unnamed_from(name);
+                }
+                result
+            }
+            _ => {
+                self.numbered[0] = Some(register.clone());
+                self.unnamed = Some(register);
+                true
+            }
+        }
+    }
+    // store deleted text, returns false if the register is read-only
+    pub fn delete(&mut self, name: Option<char>, register: Register) -> bool {
+        match name {
+            Some(BLACK_HOLE) => true,
+            Some(name) if name != UNNAMED => {
+                let result = self.set(name, register);
+                if result {
+                    self.set_unnamed_from(name);
+                }
+                result
+            }
+            _ => {
+                // shift the history: 1 -> 2, ..., 8 -> 9
+                self.numbered[1..].rotate_right(1);
+                self.numbered[1] = Some(register.clone());
+                self.unnamed = Some(register);
+                true
+            }
+        }
+    }
+    // store a recorded macro, returns false if the register is read-only
+    pub fn record(&mut self, name: char, keys: &str) -> bool {
+        self.set(name, Register::charwise(keys))
+    }
+    pub fn set_last_command(&mut self, command: &str) {
+        self.last_command = Some(command.to_string());
+    }
+    pub fn set_last_search(&mut self, pattern: &str) {
+        self.last_search = Some(pattern.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_lines() {
+        let register = Register::linewise(&["a".to_string(), "b".to_string()]);
+        assert_eq!(register.content, "a\nb\n");
+        assert_eq!(register.lines(), vec!["a", "b"]);
+        let register = Register::charwise("a\nb");
+        assert_eq!(register.lines(), vec!["a", "b"]);
+        let register = Register::blockwise(&["a".to_string(), String::new()]);
+        assert_eq!(register.content, "a\n");
+        assert_eq!(register.lines(), vec!["a", ""]);
+    }
+
+    #[test]
+    fn test_yank_and_delete() {
+        let mut registers = Registers::default();
+        registers.yank(None, Register::charwise("yank"));
+        assert_eq!(registers.get('0'), Some(Register::charwise("yank")));
+        assert_eq!(registers.get(UNNAMED), Some(Register::charwise("yank")));
+
+        registers.delete(None, Register::charwise("del1"));
+        registers.delete(None, Register::charwise("del2"));
+        assert_eq!(registers.get('1'), Some(Register::charwise("del2")));
+        assert_eq!(registers.get('2'), Some(Register::charwise("del1")));
+        assert_eq!(registers.get('0'), Some(Register::charwise("yank")));
+        assert_eq!(registers.get(UNNAMED), Some(Register::charwise("del2")));
+
+        registers.delete(Some(BLACK_HOLE), Register::charwise("gone"));
+        assert_eq!(registers.get(UNNAMED), Some(Register::charwise("del2")));
+        assert_eq!(registers.get(BLACK_HOLE), None);
+
+        for i in 0..12 {
+            registers.delete(None, Register::charwise(&i.to_string()));
+        }
+        assert_eq!(registers.get('1'), Some(Register::charwise("11")));
+        assert_eq!(registers.get('9'), Some(Register::charwise("3")));
+    }
+
+    #[test]
+    fn test_named() {
+        let mut registers = Registers::default();
+        assert!(registers.yank(Some('a'), Register::charwise("foo")));
+        assert!(registers.yank(Some('A'), Register::charwise("bar")));
+        assert_eq!(registers.get('a'), Some(Register::charwise("foobar")));
+        assert_eq!(registers.get(UNNAMED), Some(Register::charwise("foobar")));
+        assert_eq!(registers.get('0'), None);
+        registers.delete(Some('A'), Register::linewise(&["baz".to_string()]));
+        assert_eq!(
+            registers.get('a'),
+            Some(Register::linewise(&[
+                "foobar".to_string(),
+                "baz".to_string()
+            ]))
+        );
+
+        assert!(!registers.yank(Some(LAST_SEARCH), Register::charwise("x")));
+        registers.set_last_search("query");
+        assert_eq!(
+            registers.get(LAST_SEARCH),
+            Some(Register::charwise("query"))
+        );
+        assert!(Registers::is_valid_name('%'));
+        assert!(!Registers::is_valid_name('!'));
+    }
+
+    #[test]
+    fn test_read_only() {
+        let mut registers = Registers::default();
+        registers.yank(None, Register::linewise(&["a".to_string()]));
+        // a rejected write keeps the unnamed register
+        assert!(!registers.yank(Some(FILE_NAME), Register::charwise("x")));
+        assert!(!registers.yank(Some(LAST_SEARCH), Register::charwise("x")));
+        assert!(!registers.delete(Some(LAST_COMMAND), Register::charwise("x")));
+        assert_eq!(
+            registers.get(UNNAMED),
+            Some(Register::linewise(&["a".to_string()]))
+        );
+    }
+}
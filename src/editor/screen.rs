@@ -0,0 +1,357 @@
+use super::annotation::Span;
+use super::line::Line;
+use super::position::Position;
+use super::size::Size;
+use super::terminal::{Attributes, Color, Terminal};
+use super::theme::{ColorDepth, HighlightGroup, Theme};
+use std::io::Error;
+use unicode_width::UnicodeWidthStr;
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub attributes: Attributes,
+}
+
+// a column of the screen, the column after a wide character is empty with no width
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Cell {
+    pub grapheme: String,
+    pub width: usize,
+    pub style: Style,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            grapheme: String::from(" "),
+            width: 1,
+            style: Style::default(),
+        }
+    }
+}
+
+// the grid everything is drawn into, only the cells changed since the last flush are printed
+#[derive(Default)]
+pub struct Screen {
+    size: Size,
+    cells: Vec<Cell>,
+    // the cells on the terminal, None when they are unknown like after a resize
+    shown: Option<Vec<Cell>>,
+    theme: Theme,
+    color_depth: ColorDepth,
+}
+
+impl Screen {
+    pub fn resize(&mut self, size: Size) {
+        self.size = size;
+        self.cells = vec![self.blank(); size.width.saturating_mul(size.height)];
+        self.shown = None;
+    }
+    pub fn set_color_depth(&mut self, color_depth: ColorDepth) {
+        self.color_depth = color_depth;
+    }
+    // the colors of the theme are lowered to what the terminal can show
+    // everything has to be drawn again
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme.with_depth(self.color_depth);
+        self.resize(self.size);
+    }
+    pub fn theme_name(&self) -> &str {
+        &self.theme.name
+    }
+    fn blank(&self) -> Cell {
+        Cell {
+            style: self.theme.style(HighlightGroup::Normal),
+            ..Cell::default()
+        }
+    }
+    fn idx(&self, line_idx: usize, col_idx: usize) -> Option<usize> {
+        (line_idx < self.size.height && col_idx < self.size.width).then(|| {
+            line_idx
+                .saturating_mul(self.size.width)
+                .saturating_add(col_idx)
+        })
+    }
+    #[cfg(test)]
+    pub fn cell(&self, line_idx: usize, col_idx: usize) -> Option<&Cell> {
+        self.cells.get(self.idx(line_idx, col_idx)?)
+    }
+    pub fn clear_row(&mut self, line_idx: usize) {
+        let blank = self.blank();
+        for col_idx in 0..self.size.width {
+            if let Some(cell) = self
+                .idx(line_idx, col_idx)
+                .and_then(|idx| self.cells.get_mut(idx))
+            {
+                *cell = blank.clone();
+            }
+        }
+    }
+    // returns the column after the text, the text is cut at the right edge
+    pub fn put_str(&mut self, at: Position, text: &str, group: HighlightGroup) -> usize {
+        let style = self.theme.style(group);
+        let Position {
+            line_idx,
+            mut col_idx,
+        } = at;
+        for grapheme in Line::string_to_graphemes(text) {
+            let width = grapheme.width().clamp(1, 2);
+            if col_idx.saturating_add(width) > self.size.width {
+                // no room for the wide character at the edge
+                while col_idx < self.size.width {
+                    self.set_cell(line_idx, col_idx, " ", 1, style);
+                    col_idx = col_idx.saturating_add(1);
+                }
+                break;
+            }
+            self.set_cell(line_idx, col_idx, grapheme, width, style);
+            col_idx = col_idx.saturating_add(width);
+        }
+        col_idx
+    }
+    // the row is cleared and filled with the spans from the left
+    pub fn put_spans(&mut self, line_idx: usize, spans: &[Span]) {
+        self.clear_row(line_idx);
+        let mut col_idx = 0;
+        for span in spans {
+            col_idx = self.put_str(
+                Position { line_idx, col_idx },
+                &span.text,
+                HighlightGroup::of(span.kind),
+            );
+        }
+    }
+    pub fn put_row(&mut self, line_idx: usize, text: &str, group: HighlightGroup) {
+        self.clear_row(line_idx);
+        self.put_str(
+            Position {
+                line_idx,
+                col_idx: 0,
+            },
+            text,
+            group,
+        );
+    }
+    // a wide character partly overwritten leaves a blank in its other column
+    fn set_cell(
+        &mut self,
+        line_idx: usize,
+        col_idx: usize,
+        grapheme: &str,
+        width: usize,
+        style: Style,
+    ) {
+        let Some(idx) = self.idx(line_idx, col_idx) else {
+            return;
+        };
+        let old_width = self.cells.get(idx).map_or(1, |cell| cell.width);
+        if old_width == 0 {
+            if let Some(lead) = col_idx
+                .checked_sub(1)
+                .and_then(|col_idx| self.idx(line_idx, col_idx))
+                .and_then(|idx| self.cells.get_mut(idx))
+            {
+                lead.grapheme = String::from(" ");
+                lead.width = 1;
+            }
+        }
+        let next = self.idx(line_idx, col_idx.saturating_add(1));
+        if old_width == 2 && width == 1 {
+            if let Some(next) = next.and_then(|idx| self.cells.get_mut(idx)) {
+                next.grapheme = String::from(" ");
+                next.width = 1;
+            }
+        }
+        if width == 2 {
+            let next_width = next
+                .and_then(|idx| self.cells.get(idx))
+                .map_or(1, |cell| cell.width);
+            if next_width == 2 {
+                if let Some(after) = self
+                    .idx(line_idx, col_idx.saturating_add(2))
+                    .and_then(|idx| self.cells.get_mut(idx))
+                {
+                    after.grapheme = String::from(" ");
+                    after.width = 1;
+                }
+            }
+            if let Some(next) = next.and_then(|idx| self.cells.get_mut(idx)) {
+                *next = Cell {
+                    grapheme: String::new(),
+                    width: 0,
+                    style,
+                };
+            }
+        }
+        if let Some(cell) = self.cells.get_mut(idx) {
+            *cell = Cell {
+                grapheme: grapheme.to_string(),
+                width,
+                style,
+            };
+        }
+    }
+    // the runs of cells which differ from the terminal, the empty columns are skipped
+    pub fn changes(&self) -> Vec<(Position, Vec<&Cell>)> {
+        let mut runs = Vec::new();
+        for line_idx in 0..self.size.height {
+            let mut run: Option<(Position, Vec<&Cell>)> = None;
+            for col_idx in 0..self.size.width {
+                let Some(cell) = self
+                    .idx(line_idx, col_idx)
+                    .and_then(|idx| self.cells.get(idx))
+                else {
+                    continue;
+                };
+                if cell.width == 0 {
+                    continue;
+                }
+                let changed = (col_idx..col_idx.saturating_add(cell.width))
+                    .any(|col_idx| self.is_changed(line_idx, col_idx));
+                match (&mut run, changed) {
+                    (Some((_, cells)), true) => cells.push(cell),
+                    (None, true) => run = Some((Position { line_idx, col_idx }, vec![cell])),
+                    (_, false) => runs.extend(run.take()),
+                }
+            }
+            runs.extend(run);
+        }
+        runs
+    }
+    fn is_changed(&self, line_idx: usize, col_idx: usize) -> bool {
+        let Some(idx) = self.idx(line_idx, col_idx) else {
+            return false;
+        };
+        self.shown
+            .as_ref()
+            .is_none_or(|shown| shown.get(idx) != self.cells.get(idx))
+    }
+    // queue the changed cells, they are sent with `Terminal::execute`
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let mut current_style = None;
+        for (position, cells) in self.changes() {
+            Terminal::move_caret_to(position)?;
+            for cell in cells {
+                if current_style != Some(cell.style) {
+                    Terminal::set_style(cell.style)?;
+                    current_style = Some(cell.style);
+                }
+                Terminal::print(&cell.grapheme)?;
+            }
+        }
+        if current_style.is_some() {
+            Terminal::set_style(Style::default())?;
+        }
+        self.shown = Some(self.cells.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::annotation::AnnotationKind;
+    use crate::editor::terminal::Attribute;
+
+    fn row(screen: &Screen, line_idx: usize) -> String {
+        (0..screen.size.width)
+            .filter_map(|col_idx| screen.cell(line_idx, col_idx))
+            .map(|cell| cell.grapheme.as_str())
+            .collect()
+    }
+
+    fn changed_texts(screen: &Screen) -> Vec<(Position, String)> {
+        screen
+            .changes()
+            .into_iter()
+            .map(|(position, cells)| {
+                let text = cells.iter().map(|cell| cell.grapheme.as_str()).collect();
+                (position, text)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_put_str() {
+        let mut screen = Screen::default();
+        screen.resize(Size::new(5, 2));
+        assert_eq!(
+            screen.put_str(Position::new(0, 0), "a日本", HighlightGroup::Normal),
+            5
+        );
+        assert_eq!(row(&screen, 0), "a日本");
+        assert_eq!(screen.cell(0, 2).map(|cell| cell.width), Some(0));
+        // the wide character does not fit at the edge
+        assert_eq!(
+            screen.put_str(Position::new(1, 3), "a語", HighlightGroup::Normal),
+            5
+        );
+        assert_eq!(row(&screen, 1), "   a ");
+        // overwriting a half of a wide character
+        screen.put_str(Position::new(0, 2), "x", HighlightGroup::Normal);
+        assert_eq!(row(&screen, 0), "a x本");
+        screen.put_str(Position::new(0, 3), "y", HighlightGroup::Normal);
+        assert_eq!(row(&screen, 0), "a xy ");
+        screen.put_spans(
+            0,
+            &[
+                Span::plain("🇯🇵"),
+                Span {
+                    text: "b".to_string(),
+                    kind: Some(AnnotationKind::Selection),
+                },
+            ],
+        );
+        assert_eq!(row(&screen, 0), "🇯🇵b  ");
+        assert!(screen
+            .cell(0, 2)
+            .unwrap()
+            .style
+            .attributes
+            .has(Attribute::Reverse));
+    }
+
+    #[test]
+    fn test_changes() {
+        let mut screen = Screen::default();
+        screen.resize(Size::new(4, 2));
+        // everything is printed first
+        assert_eq!(
+            changed_texts(&screen),
+            vec![
+                (Position::new(0, 0), "    ".to_string()),
+                (Position::new(1, 0), "    ".to_string())
+            ]
+        );
+        screen.shown = Some(screen.cells.clone());
+        assert!(screen.changes().is_empty());
+        screen.put_row(1, "ab", HighlightGroup::Normal);
+        screen.put_str(Position::new(0, 3), "c", HighlightGroup::Normal);
+        assert_eq!(
+            changed_texts(&screen),
+            vec![
+                (Position::new(0, 3), "c".to_string()),
+                (Position::new(1, 0), "ab".to_string())
+            ]
+        );
+        screen.shown = Some(screen.cells.clone());
+        // the same text is not printed again
+        screen.put_row(1, "ab", HighlightGroup::Normal);
+        assert!(screen.changes().is_empty());
+        // a style change is a change
+        screen.put_row(1, "ab", HighlightGroup::Search);
+        assert_eq!(
+            changed_texts(&screen),
+            vec![(Position::new(1, 0), "ab".to_string())]
+        );
+        screen.shown = Some(screen.cells.clone());
+        // a wide character is printed from its first column
+        screen.put_str(Position::new(1, 1), "日", HighlightGroup::Normal);
+        assert_eq!(
+            changed_texts(&screen),
+            vec![(Position::new(1, 1), "日".to_string())]
+        );
+    }
+}
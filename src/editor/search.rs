@@ -0,0 +1,204 @@
+use super::buffer::Buffer;
+use super::line::Line;
+use super::position::Position;
+use regex::Regex;
+use std::ops::Range;
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SearchDirection {
+    #[default]
+    Forward,
+    Backward,
+}
+
+impl SearchDirection {
+    pub fn reverse(self) -> Self {
+        match self {
+            SearchDirection::Forward => SearchDirection::Backward,
+            SearchDirection::Backward => SearchDirection::Forward,
+        }
+    }
+    pub fn prompt(self) -> &'static str {
+        match self {
+            SearchDirection::Forward => "/",
+            SearchDirection::Backward => "?",
+        }
+    }
+    pub fn wrap_message(self) -> &'static str {
+        match self {
+            SearchDirection::Forward => "search hit BOTTOM, continuing at TOP",
+            SearchDirection::Backward => "search hit TOP, continuing at BOTTOM",
+        }
+    }
+}
+
+// the result of a search, `wrapped` is true if the search passed the end of the buffer
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SearchMatch {
+    pub position: Position,
+    pub wrapped: bool,
+}
+
+// col_idx ranges of the matches in the line, empty matches are skipped
+pub fn find_matches_in_line(line: &Line, regex: &Regex) -> Vec<Range<usize>> {
+    regex
+        .find_iter(line.content())
+        .filter(|found| !found.is_empty())
+        .map(|found| line.byte_idx_to_col_idx(found.start())..line.byte_idx_to_col_idx(found.end()))
+        .collect()
+}
+
+fn find_match_starts(line: &Line, regex: &Regex) -> Vec<usize> {
+    regex
+        .find_iter(line.content())
+        .map(|found| line.byte_idx_to_col_idx(found.start()))
+        .collect()
+}
+
+// find the nearest match from the position, the position itself is not a match
+pub fn find_match(
+    buffer: &Buffer,
+    regex: &Regex,
+    from: Position,
+    direction: SearchDirection,
+) -> Option<SearchMatch> {
+    let lines_count = buffer.get_lines_count();
+    if lines_count == 0 {
+        return None;
+    }
+    let from_line_idx = from.line_idx.min(lines_count.saturating_sub(1));
+    // visit every line once, and the starting line twice for the part before the cursor
+    let lines: Vec<(usize, bool)> = match direction {
+        SearchDirection::Forward => (from_line_idx..lines_count)
+            .map(|line_idx| (line_idx, false))
+            .chain((0..=from_line_idx).map(|line_idx| (line_idx, true)))
+            .collect(),
+        SearchDirection::Backward => (0..=from_line_idx)
+            .rev()
+            .map(|line_idx| (line_idx, false))
+            .chain(
+                (from_line_idx..lines_count)
+                    .rev()
+                    .map(|line_idx| (line_idx, true)),
+            )
+            .collect(),
+    };
+    for (step, (line_idx, wrapped)) in lines.into_iter().enumerate() {
+        let starts = find_match_starts(&buffer.lines[line_idx], regex);
+        let found = match direction {
+            SearchDirection::Forward => starts
+                .into_iter()
+                .find(|&col_idx| step != 0 || col_idx > from.col_idx),
+            SearchDirection::Backward => starts
+                .into_iter()
+                .rev()
+                .find(|&col_idx| step != 0 || col_idx < from.col_idx),
+        };
+        if let Some(col_idx) = found {
+            return Some(SearchMatch {
+                position: Position { line_idx, col_idx },
+                wrapped,
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_matches_in_line() {
+        let regex = Regex::new("に|b+").unwrap();
+        let line = Line::from("a\tこんにちはbb にb");
+        assert_eq!(
+            find_matches_in_line(&line, &regex),
+            vec![8..10, 14..16, 17..19, 19..20]
+        );
+        let regex = Regex::new("x*").unwrap();
+        assert!(find_matches_in_line(&line, &regex).is_empty());
+    }
+
+    #[test]
+    fn test_find_match() {
+        let buffer = Buffer::from_string("foo bar\n日本語 foo\nbaz\n");
+        let regex = Regex::new("foo").unwrap();
+        let found = find_match(
+            &buffer,
+            &regex,
+            Position::new(0, 0),
+            SearchDirection::Forward,
+        );
+        assert_eq!(
+            found,
+            Some(SearchMatch {
+                position: Position::new(1, 7),
+                wrapped: false
+            })
+        );
+        let found = find_match(
+            &buffer,
+            &regex,
+            Position::new(1, 7),
+            SearchDirection::Forward,
+        );
+        assert_eq!(
+            found,
+            Some(SearchMatch {
+                position: Position::new(0, 0),
+                wrapped: true
+            })
+        );
+        let found = find_match(
+            &buffer,
+            &regex,
+            Position::new(1, 7),
+            SearchDirection::Backward,
+        );
+        assert_eq!(
+            found,
+            Some(SearchMatch {
+                position: Position::new(0, 0),
+                wrapped: false
+            })
+        );
+        let found = find_match(
+            &buffer,
+            &regex,
+            Position::new(0, 0),
+            SearchDirection::Backward,
+        );
+        assert_eq!(
+            found,
+            Some(SearchMatch {
+                position: Position::new(1, 7),
+                wrapped: true
+            })
+        );
+
+        // the only match is under the cursor
+        let regex = Regex::new("baz").unwrap();
+        let found = find_match(
+            &buffer,
+            &regex,
+            Position::new(2, 0),
+            SearchDirection::Forward,
+        );
+        assert_eq!(
+            found,
+            Some(SearchMatch {
+                position: Position::new(2, 0),
+                wrapped: true
+            })
+        );
+        let regex = Regex::new("qux").unwrap();
+        let found = find_match(
+            &buffer,
+            &regex,
+            Position::new(2, 0),
+            SearchDirection::Forward,
+        );
+        assert_eq!(found, None);
+    }
+}
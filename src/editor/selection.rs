@@ -0,0 +1,161 @@
+use super::annotation::{Annotation, AnnotationKind};
+use super::buffer::Buffer;
+use super::operator::TextRange;
+use super::position::Position;
+use std::ops::Range;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SelectionKind {
+    Charwise,
+    Linewise,
+    Blockwise,
+}
+
+// the visual selection from the anchor to the cursor, both are inclusive
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Selection {
+    pub kind: SelectionKind,
+    pub anchor: Position,
+}
+
+impl Selection {
+    pub fn text_range(&self, cursor: Position, buffer: &Buffer) -> TextRange {
+        let start = self.anchor.min(cursor);
+        let end = self.anchor.max(cursor);
+        match self.kind {
+            SelectionKind::Charwise => TextRange::Charwise {
+                start,
+                end: next_position(end, buffer),
+            },
+            SelectionKind::Linewise => TextRange::linewise(start.line_idx, end.line_idx),
+            SelectionKind::Blockwise => {
+                // display columns, not graphemes, so the rectangle is kept over wide characters
+                let left = self.anchor.col_idx.min(cursor.col_idx);
+                let right = fragment_end(self.anchor, buffer).max(fragment_end(cursor, buffer));
+                TextRange::Blockwise {
+                    lines: start.line_idx..end.line_idx.saturating_add(1),
+                    cols: left..right,
+                }
+            }
+        }
+    }
+    // annotations of the selected part in `lines`
+    pub fn annotations(
+        &self,
+        cursor: Position,
+        buffer: &Buffer,
+        lines: Range<usize>,
+    ) -> Vec<Annotation> {
+        let range = self.text_range(cursor, buffer);
+        let selected = range.lines();
+        (lines.start.max(selected.start)..lines.end.min(selected.end))
+            .map(|line_idx| Annotation {
+                kind: AnnotationKind::Selection,
+                line_idx,
+                range: range.cols_in_line(line_idx, buffer.get_line_col_width(line_idx)),
+            })
+            .collect()
+    }
+}
+
+// the column right after the character at the position
+fn fragment_end(position: Position, buffer: &Buffer) -> usize {
+    buffer
+        .lines
+        .get(position.line_idx)
+        .and_then(|line| line.get_fragment_by_col_idx(position.col_idx))
+        .map_or(position.col_idx.saturating_add(1), |fragment| {
+            fragment.left_col_width().saturating_add(fragment.width())
+        })
+}
+
+// the position right after the character, the newline is selected at the end of the line
+pub fn next_position(position: Position, buffer: &Buffer) -> Position {
+    let line_width = buffer.get_line_col_width(position.line_idx);
+    if position.col_idx < line_width {
+        Position {
+            line_idx: position.line_idx,
+            col_idx: fragment_end(position, buffer),
+        }
+    } else if position.line_idx.saturating_add(1) < buffer.get_lines_count() {
+        Position {
+            line_idx: position.line_idx.saturating_add(1),
+            col_idx: 0,
+        }
+    } else {
+        Position {
+            line_idx: position.line_idx,
+            col_idx: line_width,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_range() {
+        let buffer = Buffer::from_string("あいう\nabcdef\na\tb\n");
+        let selection = Selection {
+            kind: SelectionKind::Charwise,
+            anchor: Position::new(1, 3),
+        };
+        assert_eq!(
+            selection.text_range(Position::new(0, 2), &buffer),
+            TextRange::Charwise {
+                start: Position::new(0, 2),
+                end: Position::new(1, 4)
+            }
+        );
+        assert_eq!(
+            selection.text_range(Position::new(1, 6), &buffer),
+            TextRange::Charwise {
+                start: Position::new(1, 3),
+                end: Position::new(2, 0)
+            }
+        );
+        let selection = Selection {
+            kind: SelectionKind::Linewise,
+            anchor: Position::new(2, 0),
+        };
+        assert_eq!(
+            selection.text_range(Position::new(1, 3), &buffer),
+            TextRange::Linewise(1..3)
+        );
+        // the block covers whole wide characters and tabs
+        let selection = Selection {
+            kind: SelectionKind::Blockwise,
+            anchor: Position::new(0, 2),
+        };
+        assert_eq!(
+            selection.text_range(Position::new(2, 1), &buffer),
+            TextRange::Blockwise {
+                lines: 0..3,
+                cols: 1..4
+            }
+        );
+        assert_eq!(
+            selection.text_range(Position::new(1, 4), &buffer),
+            TextRange::Blockwise {
+                lines: 0..2,
+                cols: 2..5
+            }
+        );
+    }
+
+    #[test]
+    fn test_annotations() {
+        let buffer = Buffer::from_string("abc\nde\nfgh\n");
+        let selection = Selection {
+            kind: SelectionKind::Charwise,
+            anchor: Position::new(0, 1),
+        };
+        let ranges: Vec<(usize, Range<usize>)> = selection
+            .annotations(Position::new(2, 0), &buffer, 1..3)
+            .into_iter()
+            .map(|annotation| (annotation.line_idx, annotation.range))
+            .collect();
+        assert_eq!(ranges, vec![(1, 0..2), (2, 0..1)]);
+    }
+}
@@ -1,9 +1,12 @@
 use super::file_info::FileType;
-use super::terminal::Terminal;
+use super::screen::Screen;
+use super::theme::HighlightGroup;
 use crate::editor::{Editor, Mode};
-use std::io::Error;
 use unicode_width::UnicodeWidthStr;
 
+// the width of the area showing the pending keys
+const SHOWCMD_WIDTH: usize = 10;
+
 #[derive(Default, Eq, PartialEq)]
 pub struct DocumentStatus {
     file_name: Option<String>,
@@ -14,6 +17,10 @@ pub struct DocumentStatus {
     current_col_idx: usize,
     modified: bool,
     mode: Mode,
+    // the register a macro is being recorded into
+    recording: Option<char>,
+    // the keys of the command in progress like `2d3`
+    pending_keys: String,
 }
 
 impl DocumentStatus {
@@ -27,8 +34,10 @@ impl DocumentStatus {
             total_cols: buffer.get_line_col_width(cursor.line_idx()),
             current_line_idx: cursor.line_idx(),
             current_col_idx: cursor.col_idx(),
-            modified: buffer.modified_count != 0,
+            modified: buffer.modified_count() != 0,
             mode: editor.mode,
+            recording: editor.recording,
+            pending_keys: editor.pending_keys(),
         }
     }
     pub fn file_name_string(&self) -> String {
@@ -43,6 +52,11 @@ impl DocumentStatus {
             String::default()
         }
     }
+    pub fn recording_string(&self) -> String {
+        self.recording
+            .map(|name| format!(" recording @{name}"))
+            .unwrap_or_default()
+    }
     pub fn file_type_string(&self) -> String {
         self.file_type.as_ref().map_or_else(
             || String::from("No Type"),
@@ -77,23 +91,28 @@ impl StatusBar {
             self.needs_redraw = true;
         }
     }
+    pub fn ensure_redraw(&mut self) {
+        self.needs_redraw = true;
+    }
     pub fn set_size(&mut self, width: usize) {
         self.width = width;
         self.needs_redraw = true;
     }
-    pub fn render(&mut self, line_idx: usize) -> Result<(), Error> {
+    pub fn render(&mut self, screen: &mut Screen, line_idx: usize) {
         if !self.needs_redraw {
-            return Ok(());
+            return;
         }
 
         let left = format!(
-            " {:?} | {} {}",
+            " {:?}{} | {} {}",
             self.document_status.mode,
+            self.document_status.recording_string(),
             self.document_status.file_name_string(),
             self.document_status.modified_string()
         );
         let right = format!(
-            "{} | {}|{} ",
+            "{:<SHOWCMD_WIDTH$} {} | {}|{} ",
+            self.document_status.pending_keys,
             self.document_status.file_type_string(),
             self.document_status.lines_info_string(),
             self.document_status.cols_info_string()
@@ -103,8 +122,13 @@ impl StatusBar {
         let reminder_len = self.width.saturating_sub(left.width()).saturating_sub(1);
         let mut line_text = format!("{left} {right:>reminder_len$}");
         line_text.truncate(self.width);
-        Terminal::print_invert_row(line_idx, &line_text)?;
+        let width = self.width;
+        // :width$ -> pad to width
+        screen.put_row(
+            line_idx,
+            &format!("{line_text:width$}"),
+            HighlightGroup::StatusLine,
+        );
         self.needs_redraw = false;
-        Ok(())
     }
 }
@@ -0,0 +1,317 @@
+use super::buffer::Buffer;
+use super::position::Position;
+use regex::Regex;
+use std::ops::Range;
+
+// a running `:s` command, which replaces matches one by one
+pub struct Substitution {
+    regex: Regex,
+    // in the syntax of the regex crate
+    replacement: String,
+    // the replacement as typed, for the confirmation prompt
+    raw_replacement: String,
+    global: bool,
+    pub confirm: bool,
+    line_idx: usize,
+    end_line_idx: usize,
+    byte_idx: usize,
+    // the current match in bytes of the line at line_idx
+    current: Option<Range<usize>>,
+    count: usize,
+    lines_count: usize,
+    last_line_idx: Option<usize>,
+}
+
+impl Substitution {
+    // parse `/pattern/replacement/flags`, the first character is the delimiter
+    // an empty pattern uses the last search pattern
+    pub fn parse(
+        args: &str,
+        range: Range<usize>,
+        last_search: Option<&str>,
+    ) -> Result<(Self, String), String> {
+        let mut chars = args.chars();
+        let delimiter = chars
+            .next()
+            .filter(|c| !c.is_alphanumeric() && *c != '\\' && *c != '"' && *c != ' ')
+            .ok_or("Invalid delimiter")?;
+        let mut parts = split_by_delimiter(chars.as_str(), delimiter);
+        let mut pattern = parts.next().unwrap_or_default();
+        let raw_replacement = parts.next().unwrap_or_default();
+        let flags = parts.next().unwrap_or_default();
+        if pattern.is_empty() {
+            pattern = last_search
+                .ok_or("No previous regular expression")?
+                .to_string();
+        }
+        let mut global = false;
+        let mut confirm = false;
+        let mut ignore_case = false;
+        for flag in flags.trim().chars() {
+            match flag {
+                'g' => global = true,
+                'c' => confirm = true,
+                'i' => ignore_case = true,
+                'I' => ignore_case = false,
+                _ => return Err(format!("Invalid flag: {flag}")),
+            }
+        }
+        let regex = if ignore_case {
+            Regex::new(&format!("(?i){pattern}"))
+        } else {
+            Regex::new(&pattern)
+        }
+        .map_err(|_| format!("Invalid pattern: {pattern}"))?;
+        let substitution = Self {
+            regex,
+            replacement: convert_replacement(&raw_replacement),
+            raw_replacement,
+            global,
+            confirm,
+            line_idx: range.start,
+            end_line_idx: range.end,
+            byte_idx: 0,
+            current: None,
+            count: 0,
+            lines_count: 0,
+            last_line_idx: None,
+        };
+        Ok((substitution, pattern))
+    }
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+    pub fn prompt(&self) -> String {
+        format!("replace with {} (y/n/a/q/l)?", self.raw_replacement)
+    }
+    // find the next match and return its position
+    pub fn find_next(&mut self, buffer: &Buffer) -> Option<Position> {
+        while self.line_idx < self.end_line_idx {
+            let line = buffer.lines.get(self.line_idx)?;
+            let content = line.content();
+            if self.byte_idx <= content.len() {
+                if let Some(found) = self.regex.find_at(content, self.byte_idx) {
+                    self.current = Some(found.range());
+                    return Some(Position {
+                        line_idx: self.line_idx,
+                        col_idx: line.byte_idx_to_col_idx(found.start()),
+                    });
+                }
+            }
+            self.next_line();
+        }
+        self.current = None;
+        None
+    }
+    fn next_line(&mut self) {
+        self.line_idx = self.line_idx.saturating_add(1);
+        self.byte_idx = 0;
+    }
+    // move past the current match
+    fn advance(&mut self, content: &str, end: usize, is_empty: bool) {
+        if !self.global {
+            self.next_line();
+            return;
+        }
+        self.byte_idx = end;
+        if is_empty {
+            // avoid matching the same empty string forever
+            self.byte_idx = content[end..]
+                .chars()
+                .next()
+                .map_or(usize::MAX, |c| end.saturating_add(c.len_utf8()));
+        }
+    }
+    pub fn skip(&mut self, buffer: &Buffer) {
+        let Some(current) = self.current.take() else {
+            return;
+        };
+        let content = buffer
+            .lines
+            .get(self.line_idx)
+            .map(|line| line.content().to_string())
+            .unwrap_or_default();
+        self.advance(&content, current.end, current.is_empty());
+    }
+    pub fn replace(&mut self, buffer: &mut Buffer) {
+        let Some(current) = self.current.take() else {
+            return;
+        };
+        let Some(line) = buffer.lines.get(self.line_idx) else {
+            return;
+        };
+        let content = line.content().to_string();
+        let Some(captures) = self.regex.captures_at(&content, current.start) else {
+            return;
+        };
+        let mut replaced = String::new();
+        captures.expand(&self.replacement, &mut replaced);
+        let new_content = format!(
+            "{}{replaced}{}",
+            &content[..current.start],
+            &content[current.end..]
+        );
+        buffer.set_line(&new_content, self.line_idx);
+        self.count = self.count.saturating_add(1);
+        if self.last_line_idx != Some(self.line_idx) {
+            self.lines_count = self.lines_count.saturating_add(1);
+            self.last_line_idx = Some(self.line_idx);
+        }
+        let end = current.start.saturating_add(replaced.len());
+        self.advance(&new_content, end, current.is_empty());
+    }
+    // replace all the remaining matches
+    pub fn replace_all(&mut self, buffer: &mut Buffer) {
+        while self.find_next(buffer).is_some() {
+            self.replace(buffer);
+        }
+    }
+    pub fn last_line_idx(&self) -> Option<usize> {
+        self.last_line_idx
+    }
+    pub fn message(&self, pattern: &str) -> String {
+        if self.count == 0 {
+            return format!("Pattern not found: {pattern}");
+        }
+        let substitutions = if self.count == 1 {
+            "substitution"
+        } else {
+            "substitutions"
+        };
+        let lines = if self.lines_count == 1 {
+            "line"
+        } else {
+            "lines"
+        };
+        format!(
+            "{} {substitutions} on {} {lines}",
+            self.count, self.lines_count
+        )
+    }
+}
+
+// split by the delimiter, `\` followed by the delimiter is the delimiter itself
+fn split_by_delimiter(input: &str, delimiter: char) -> impl Iterator<Item = String> {
+    let mut parts = vec![String::new()];
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        let current = parts.last_mut().unwrap();
+        if c == '\\' {
+            match chars.next() {
+                Some(next) if next == delimiter => current.push(next),
+                Some(next) => {
+                    current.push(c);
+                    current.push(next);
+                }
+                None => current.push(c),
+            }
+        } else if c == delimiter {
+            parts.push(String::new());
+        } else {
+            current.push(c);
+        }
+    }
+    parts.into_iter()
+}
+
+// convert vim style replacement to the regex crate style
+// `&` and `\0` are the whole match, `\1`-`\9` are the capture groups, `$1` is the same as `\1`
+fn convert_replacement(replacement: &str) -> String {
+    let mut result = String::new();
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => result.push_str("${0}"),
+            // braces keep the regex crate from reading `$1a` as the group `1a`
+            '$' if chars.peek().is_some_and(char::is_ascii_digit) => {
+                result.push_str("${");
+                result.extend(chars.next());
+                result.push('}');
+            }
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => {
+                    result.push_str("${");
+                    result.push(digit);
+                    result.push('}');
+                }
+                Some('$') => result.push_str("$$"),
+                Some(next) => result.push(next),
+                None => result.push(c),
+            },
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_replacement() {
+        assert_eq!(convert_replacement("<&>"), "<${0}>");
+        assert_eq!(convert_replacement("\\2-\\1"), "${2}-${1}");
+        assert_eq!(convert_replacement("$1 \\& \\\\"), "${1} & \\");
+        assert_eq!(convert_replacement("\\$1"), "$$1");
+        assert_eq!(convert_replacement("$1a"), "${1}a");
+        assert_eq!(convert_replacement("$12 $"), "${1}2 $");
+    }
+
+    #[test]
+    fn test_split_by_delimiter() {
+        let parts: Vec<String> = split_by_delimiter("a\\/b/c\\1/g", '/').collect();
+        assert_eq!(parts, vec!["a/b", "c\\1", "g"]);
+        let parts: Vec<String> = split_by_delimiter("a#b", '#').collect();
+        assert_eq!(parts, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_replace_all() {
+        let mut buffer = Buffer::from_string("foo bar foo\nbaz\n日本語 foo\n");
+        let (mut substitution, _) = Substitution::parse("/foo/[&]/", 0..3, None).unwrap();
+        substitution.replace_all(&mut buffer);
+        assert_eq!(buffer.lines[0].content(), "[foo] bar foo");
+        assert_eq!(buffer.lines[2].content(), "日本語 [foo]");
+        assert_eq!(substitution.message("foo"), "2 substitutions on 2 lines");
+
+        let mut buffer = Buffer::from_string("foo bar foo\nbaz\n日本語 foo\n");
+        let (mut substitution, _) = Substitution::parse("#(\\w)(o+)#$2\\1#g", 0..1, None).unwrap();
+        substitution.replace_all(&mut buffer);
+        assert_eq!(buffer.lines[0].content(), "oof bar oof");
+        assert_eq!(buffer.lines[2].content(), "日本語 foo");
+        assert_eq!(substitution.message("foo"), "2 substitutions on 1 line");
+
+        let mut buffer = Buffer::from_string("abc\n");
+        let (mut substitution, _) = Substitution::parse("/x*/-/g", 0..1, None).unwrap();
+        substitution.replace_all(&mut buffer);
+        assert_eq!(buffer.lines[0].content(), "-a-b-c-");
+
+        let mut buffer = Buffer::from_string("Foo foo\n");
+        let (mut substitution, pattern) = Substitution::parse("///gi", 0..1, Some("FOO")).unwrap();
+        assert_eq!(pattern, "FOO");
+        substitution.replace_all(&mut buffer);
+        assert_eq!(buffer.lines[0].content(), " ");
+
+        assert!(Substitution::parse("/a/b/x", 0..1, None).is_err());
+        assert!(Substitution::parse("//b/", 0..1, None).is_err());
+        assert!(Substitution::parse("/(/b/", 0..1, None).is_err());
+    }
+
+    #[test]
+    fn test_confirm() {
+        let mut buffer = Buffer::from_string("a a\na\n");
+        let (mut substitution, _) = Substitution::parse("/a/b/gc", 0..2, None).unwrap();
+        assert!(substitution.confirm);
+        assert_eq!(substitution.find_next(&buffer), Some(Position::new(0, 0)));
+        substitution.skip(&buffer);
+        assert_eq!(substitution.find_next(&buffer), Some(Position::new(0, 2)));
+        substitution.replace(&mut buffer);
+        assert_eq!(substitution.find_next(&buffer), Some(Position::new(1, 0)));
+        substitution.replace(&mut buffer);
+        assert_eq!(substitution.find_next(&buffer), None);
+        assert_eq!(buffer.lines[0].content(), "a b");
+        assert_eq!(buffer.lines[1].content(), "b");
+        assert_eq!(substitution.last_line_idx(), Some(1));
+    }
+}
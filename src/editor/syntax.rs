@@ -0,0 +1,164 @@
+use super::file_info::FileType;
+use regex::Regex;
+
+// what a part of the text is, each kind has its own color
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TokenKind {
+    Comment,
+    String,
+    Keyword,
+    Type,
+    Number,
+    Function,
+    Macro,
+    Attribute,
+    // lifetimes, escapes and globs
+    Special,
+}
+
+// a token which can continue on the next lines, like a block comment
+pub struct Region {
+    pub kind: TokenKind,
+    pub start: Regex,
+    pub end: RegionEnd,
+    // the start opens another level inside the region
+    pub nested: bool,
+}
+
+pub enum RegionEnd {
+    Pattern(Regex),
+    // `\1` is replaced with the first group of the start, like the hashes of a raw string
+    Template(&'static str),
+}
+
+// the rules of a file type, the regions win over the tokens starting at the same place
+pub struct Syntax {
+    pub regions: Vec<Region>,
+    // a token is the first group if the pattern has one, the whole match otherwise
+    pub tokens: Vec<(Regex, TokenKind)>,
+}
+
+const RUST_KEYWORDS: &str = r"\b(?:as|async|await|break|const|continue|crate|dyn|else|enum|extern|false|fn|for|if|impl|in|let|loop|match|mod|move|mut|pub|ref|return|self|Self|static|struct|super|trait|true|type|unsafe|use|where|while)\b";
+const RUST_TYPES: &str =
+    r"\b(?:[A-Z][A-Za-z0-9_]*|[iu](?:8|16|32|64|128|size)|f32|f64|bool|char|str)\b";
+const RUST_NUMBERS: &str = r"\b(?:0x[0-9a-fA-F_]+|0o[0-7_]+|0b[01_]+|[0-9][0-9_]*(?:\.[0-9][0-9_]*)?(?:[eE][+-]?[0-9_]+)?)(?:[iu](?:8|16|32|64|128|size)|f32|f64)?\b";
+const VIM_KEYWORDS: &str = r"\b(?:set|setlocal|let|unlet|if|elseif|else|endif|for|endfor|while|endwhile|function|endfunction|return|call|execute|source|map|nmap|vmap|xmap|imap|omap|noremap|nnoremap|vnoremap|xnoremap|inoremap|onoremap|autocmd|augroup|syntax|filetype|colorscheme|highlight)\b!?";
+
+impl Syntax {
+    pub fn for_file_type(file_type: FileType) -> Option<Self> {
+        match file_type {
+            FileType::Rust => Some(Self::rust()),
+            FileType::Gitcommit => Some(Self::gitcommit()),
+            FileType::Gitignore => Some(Self::gitignore()),
+            FileType::Vim => Some(Self::vim()),
+            FileType::Text => None,
+        }
+    }
+    fn rust() -> Self {
+        Self {
+            regions: vec![
+                region(TokenKind::Comment, r"/\*", end(r"\*/"), true),
+                region(
+                    TokenKind::String,
+                    r#"\bb?r(#*)""#,
+                    RegionEnd::Template(r#""\1"#),
+                    false,
+                ),
+                // a quote after an even number of backslashes
+                region(
+                    TokenKind::String,
+                    r#"""#,
+                    end(r#"(?:^|[^\\])(?:\\\\)*""#),
+                    false,
+                ),
+            ],
+            tokens: vec![
+                token(r"//.*", TokenKind::Comment),
+                token(
+                    r"'(?:[^'\\]|\\.|\\u\{[0-9a-fA-F]{1,6}\})'",
+                    TokenKind::String,
+                ),
+                token(r"'[a-zA-Z_][a-zA-Z0-9_]*", TokenKind::Special),
+                token(r"#!?\[[^\]]*\]", TokenKind::Attribute),
+                token(r"\b([a-zA-Z_][a-zA-Z0-9_]*!)\s*[(\[{]", TokenKind::Macro),
+                token(RUST_KEYWORDS, TokenKind::Keyword),
+                token(RUST_TYPES, TokenKind::Type),
+                token(RUST_NUMBERS, TokenKind::Number),
+                token(
+                    r"\b([a-z_][a-zA-Z0-9_]*)\s*(?:::<[^>]*>)?\(",
+                    TokenKind::Function,
+                ),
+            ],
+        }
+    }
+    fn gitcommit() -> Self {
+        Self {
+            regions: Vec::new(),
+            tokens: vec![
+                token(r"^#.*", TokenKind::Comment),
+                token(
+                    r"^(?:Signed-off-by|Co-authored-by|Reviewed-by|Fixes|Closes):",
+                    TokenKind::Keyword,
+                ),
+            ],
+        }
+    }
+    fn gitignore() -> Self {
+        Self {
+            regions: Vec::new(),
+            tokens: vec![
+                token(r"^#.*", TokenKind::Comment),
+                token(r"^!|\*\*|[*?]|\[[^\]]*\]", TokenKind::Special),
+            ],
+        }
+    }
+    fn vim() -> Self {
+        Self {
+            regions: Vec::new(),
+            tokens: vec![
+                token(r#"^\s*".*"#, TokenKind::Comment),
+                token(r#"'[^']*'|"(?:[^"\\]|\\.)*""#, TokenKind::String),
+                token(r"<[A-Za-z0-9-]+>", TokenKind::Special),
+                token(VIM_KEYWORDS, TokenKind::Keyword),
+                token(r"\b[0-9]+\b", TokenKind::Number),
+                token(r"\b([a-zA-Z_:#]+)\(", TokenKind::Function),
+            ],
+        }
+    }
+}
+
+// the patterns are fixed, so they are checked by the tests
+fn token(pattern: &str, kind: TokenKind) -> (Regex, TokenKind) {
+    (Regex::new(pattern).unwrap(), kind)
+}
+
+fn region(kind: TokenKind, start: &str, end: RegionEnd, nested: bool) -> Region {
+    Region {
+        kind,
+        start: Regex::new(start).unwrap(),
+        end,
+        nested,
+    }
+}
+
+fn end(pattern: &str) -> RegionEnd {
+    RegionEnd::Pattern(Regex::new(pattern).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_file_type() {
+        for file_type in [
+            FileType::Rust,
+            FileType::Gitcommit,
+            FileType::Gitignore,
+            FileType::Vim,
+        ] {
+            assert!(Syntax::for_file_type(file_type).is_some());
+        }
+        assert!(Syntax::for_file_type(FileType::Text).is_none());
+    }
+}
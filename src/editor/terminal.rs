@@ -1,12 +1,13 @@
 use super::position::Position;
+use super::screen::Style;
 use super::size::Size;
 pub use crossterm::cursor::SetCursorStyle as CursorStyle;
 use crossterm::cursor::{Hide, MoveTo, Show};
 use crossterm::event::read;
 pub use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+pub use crossterm::style::{Attribute, Attributes, Color};
 use crossterm::style::{
-    Attribute::{Reset, Reverse},
-    Print,
+    Print, SetAttribute, SetAttributes, SetBackgroundColor, SetForegroundColor,
 };
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, size, Clear, ClearType, DisableLineWrap, EnableLineWrap,
@@ -56,10 +57,7 @@ impl Terminal {
                 if key_event.kind == crossterm::event::KeyEventKind::Press {
                     event
                 } else {
-                    Err(Error::new(
-                        std::io::ErrorKind::Other,
-                        "Ignore not-press event",
-                    ))
+                    Err(Error::other("Ignore not-press event"))
                 }
             }
             _ => event,
@@ -71,9 +69,6 @@ impl Terminal {
     pub fn clear_screen() -> Result<(), Error> {
         Self::queue_command(Clear(ClearType::All))
     }
-    fn clear_line() -> Result<(), Error> {
-        Self::queue_command(Clear(ClearType::UntilNewLine))
-    }
     pub fn move_caret_to(position: Position) -> Result<(), Error> {
         #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
         Self::queue_command(MoveTo(position.col_idx as u16, position.line_idx as u16))
@@ -87,20 +82,19 @@ impl Terminal {
     pub fn print(string: &str) -> Result<(), Error> {
         Self::queue_command(Print(string))
     }
-    pub fn print_row(line_idx: usize, line_text: &str) -> Result<(), Error> {
-        let col_idx = 0;
-        Self::move_caret_to(Position { line_idx, col_idx })?;
-        Self::clear_line()?;
-        Self::print(line_text)
-    }
-    pub fn print_invert_row(line_idx: usize, line_text: &str) -> Result<(), Error> {
-        let width = Self::size()?.width;
-        // :width$ -> pad to width
-        // .width$ -> truncate to width
-        Self::print_row(
-            line_idx,
-            &format!("{Reverse}{line_text:width$.width$}{Reset}"),
-        )
+    // the attributes are reset before the colors are set
+    pub fn set_style(style: Style) -> Result<(), Error> {
+        Self::queue_command(SetAttribute(Attribute::Reset))?;
+        if let Some(color) = style.fg {
+            Self::queue_command(SetForegroundColor(color))?;
+        }
+        if let Some(color) = style.bg {
+            Self::queue_command(SetBackgroundColor(color))?;
+        }
+        if !style.attributes.is_empty() {
+            Self::queue_command(SetAttributes(style.attributes))?;
+        }
+        Ok(())
     }
     // pub fn print_at(at: Position, width:usize, line_text: &str) -> Result<(), Error> {
     //     Self::move_caret_to(at)?;
@@ -1,3 +1,4 @@
+use super::list_chars::ListChars;
 use std::fmt;
 use unicode_width::UnicodeWidthStr;
 
@@ -6,60 +7,65 @@ pub struct TextFragment {
     grapheme: String,
     width: usize,
     left_col_width: usize,
-    replacement: Option<String>,
+    special: Option<Special>,
+}
+
+// the graphemes which can be shown with another text
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Special {
+    Tab,
+    Space,
+    Nbsp,
+    IdeographicSpace,
+    ZeroWidth,
+    // the character after `^`
+    Control(char),
 }
 
 const NBSP: &str = "\u{00a0}";
 const NNBSP: &str = "\u{202f}";
-const REPLACE_NBSP: &str = "␣";
-const REPLACE_NNBSP: &str = "␣";
+const IDEOGRAPHIC_SPACE: &str = "\u{3000}";
 
-const TAB_WIDTH: usize = 4;
+pub const TAB_WIDTH: usize = 4;
 
 impl TextFragment {
-    pub fn new(grapheme: &str, left_col_width: usize) -> Self {
-        let replacement = Self::get_replacement(grapheme, left_col_width);
-        let width = if let Some(replace_str) = &replacement {
-            replace_str.width()
-        } else if grapheme.width() <= 1 {
-            1
-        } else {
-            2
-        };
-        Self {
-            grapheme: String::from(grapheme),
-            width,
-            left_col_width,
-            replacement,
-        }
-    }
-    fn get_replacement(grapheme: &str, left_col_width: usize) -> Option<String> {
+    pub fn new(grapheme: &str, left_col_width: usize, tab_width: usize) -> Self {
+        let special = Self::get_special(grapheme);
         // modulo operation is necessary in this case
         #[allow(clippy::arithmetic_side_effects)]
-        let g_width = if grapheme == "\t" {
+        let width = match special {
             // special case: tab
             // left_col_width = 0: 4 - 0 % 4 = 4
             // left_col_width = 1: 4 - 1 % 4 = 3
             // left_col_width = 2: 4 - 2 % 4 = 2
             // left_col_width = 3: 4 - 3 % 4 = 1
             // left_col_width = 4: 4 - 4 % 4 = 0
-            TAB_WIDTH - left_col_width % TAB_WIDTH
-        } else {
-            grapheme.width()
+            Some(Special::Tab) => tab_width - left_col_width % tab_width,
+            Some(Special::Control(_)) => 2,
+            _ if grapheme.width() <= 1 => 1,
+            _ => 2,
         };
+        Self {
+            grapheme: String::from(grapheme),
+            width,
+            left_col_width,
+            special,
+        }
+    }
+    fn get_special(grapheme: &str) -> Option<Special> {
         match grapheme {
-            " " => None,
-            "\t" => Some(format!("→{}", " ".repeat(g_width.saturating_sub(1))).to_string()),
-            NBSP => Some(REPLACE_NBSP.to_string()),
-            NNBSP => Some(REPLACE_NNBSP.to_string()),
-            _ if g_width == 0 => Some("·".to_string()),
+            "\t" => Some(Special::Tab),
+            " " => Some(Special::Space),
+            NBSP | NNBSP => Some(Special::Nbsp),
+            IDEOGRAPHIC_SPACE => Some(Special::IdeographicSpace),
+            _ if grapheme.width() == 0 => Some(Special::ZeroWidth),
             _ => {
                 let mut chars = grapheme.chars();
                 if let Some(ch) = chars.next() {
                     if ch.is_control() && chars.next().is_none() {
                         #[allow(clippy::as_conversions, clippy::arithmetic_side_effects)]
                         let replacement = ((ch as u8) + 64) as char;
-                        return Some(format!("^{replacement}").to_string());
+                        return Some(Special::Control(replacement));
                     }
                 }
                 None
@@ -78,9 +84,35 @@ impl TextFragment {
     pub fn left_col_width(&self) -> usize {
         self.left_col_width
     }
-    // pub fn replacement(&self) -> Option<&str> {
-    //     self.replacement.as_deref()
-    // }
+    // the text shown instead of the grapheme, None to show the grapheme
+    // without `list_chars` the whitespace is blank, control characters are always `^X`
+    pub fn replacement(&self, list_chars: Option<&ListChars>, trailing: bool) -> Option<String> {
+        let fill = |head: char, fill: char| {
+            let mut text = head.to_string();
+            text.extend(std::iter::repeat_n(fill, self.width.saturating_sub(1)));
+            text
+        };
+        match (self.special?, list_chars) {
+            (Special::Control(ch), _) => Some(format!("^{ch}")),
+            (Special::Tab | Special::ZeroWidth, None) => Some(" ".repeat(self.width)),
+            (Special::Tab, Some(list_chars)) => Some(
+                list_chars
+                    .tab
+                    .map_or(" ".repeat(self.width), |(head, rest)| fill(head, rest)),
+            ),
+            (Special::ZeroWidth, Some(list_chars)) => {
+                Some(list_chars.zerowidth.unwrap_or(' ').to_string())
+            }
+            (_, None) => None,
+            (Special::Space, Some(list_chars)) => {
+                list_chars.trail.filter(|_| trailing).map(String::from)
+            }
+            (Special::Nbsp, Some(list_chars)) => list_chars.nbsp.map(String::from),
+            (Special::IdeographicSpace, Some(list_chars)) => {
+                list_chars.ideosp.map(|head| fill(head, ' '))
+            }
+        }
+    }
 }
 
 impl fmt::Display for TextFragment {
@@ -88,7 +120,9 @@ impl fmt::Display for TextFragment {
         write!(
             formatter,
             "{}",
-            self.replacement.as_ref().unwrap_or(&self.grapheme)
+            self.replacement(Some(&ListChars::default()), false)
+                .as_deref()
+                .unwrap_or(&self.grapheme)
         )
     }
 }
@@ -99,32 +133,68 @@ mod tests {
 
     #[test]
     fn test_new() {
+        let list_chars = ListChars::default();
+        let list = Some(&list_chars);
+
         // normal character
-        let f = TextFragment::new("a", 0);
+        let f = TextFragment::new("a", 0, TAB_WIDTH);
         assert_eq!(f.grapheme, "a");
         assert_eq!(f.width(), 1);
-        assert_eq!(f.replacement, None);
+        assert_eq!(f.replacement(list, false), None);
 
         // full-width character
-        let f = TextFragment::new("緑", 0);
+        let f = TextFragment::new("緑", 0, TAB_WIDTH);
         assert_eq!(f.grapheme, "緑");
         assert_eq!(f.width(), 2);
-        assert_eq!(f.replacement, None);
+        assert_eq!(f.replacement(list, false), None);
 
         // tab
-        let f = TextFragment::new("\t", 0);
+        let f = TextFragment::new("\t", 0, TAB_WIDTH);
         assert_eq!(f.grapheme, "\t");
         assert_eq!(f.width(), 4);
-        assert_eq!(f.replacement, Some("→   ".to_string()));
-        let f = TextFragment::new("\t", 1);
+        assert_eq!(f.replacement(list, false), Some("→   ".to_string()));
+        let f = TextFragment::new("\t", 1, TAB_WIDTH);
         assert_eq!(f.grapheme, "\t");
         assert_eq!(f.width(), 3);
-        assert_eq!(f.replacement, Some("→  ".to_string()));
+        assert_eq!(f.replacement(list, false), Some("→  ".to_string()));
+        assert_eq!(f.replacement(None, false), Some("   ".to_string()));
+        let f = TextFragment::new("\t", 1, 8);
+        assert_eq!(f.width(), 7);
+        let f = TextFragment::new("\t", 3, 2);
+        assert_eq!(f.width(), 1);
 
         // ctrl character
-        let f = TextFragment::new("\x01", 0);
+        let f = TextFragment::new("\x01", 0, TAB_WIDTH);
         assert_eq!(f.grapheme, "\x01");
         assert_eq!(f.width(), 2);
-        assert_eq!(f.replacement, Some("^A".to_string()));
+        assert_eq!(f.replacement(list, false), Some("^A".to_string()));
+        assert_eq!(f.replacement(None, false), Some("^A".to_string()));
+    }
+
+    #[test]
+    fn test_replacement() {
+        let list_chars = ListChars::parse("tab:>-,trail:~,ideosp:□").unwrap();
+        let list = Some(&list_chars);
+        let f = TextFragment::new("\t", 0, TAB_WIDTH);
+        assert_eq!(f.replacement(list, false), Some(">---".to_string()));
+        // only the trailing spaces
+        let f = TextFragment::new(" ", 0, TAB_WIDTH);
+        assert_eq!(f.replacement(list, false), None);
+        assert_eq!(f.replacement(list, true), Some("~".to_string()));
+        assert_eq!(f.replacement(None, true), None);
+        let f = TextFragment::new("\u{3000}", 0, TAB_WIDTH);
+        assert_eq!(f.width(), 2);
+        assert_eq!(f.replacement(list, false), Some("□ ".to_string()));
+        assert_eq!(f.replacement(None, false), None);
+        // the items not given show the text as is
+        let f = TextFragment::new("\u{00a0}", 0, TAB_WIDTH);
+        assert_eq!(f.replacement(list, false), None);
+        let f = TextFragment::new("\u{200b}", 0, TAB_WIDTH);
+        assert_eq!(f.width(), 1);
+        assert_eq!(f.replacement(list, false), Some(" ".to_string()));
+        assert_eq!(
+            f.replacement(Some(&ListChars::default()), false),
+            Some("·".to_string())
+        );
     }
 }
@@ -0,0 +1,717 @@
+use super::bracket::BRACKETS;
+use super::buffer::Buffer;
+use super::char_class::CharClass;
+use super::line::Line;
+use super::operator::TextRange;
+use super::position::Position;
+use super::text_fragment::TextFragment;
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+const QUOTES: [char; 3] = ['"', '\'', '`'];
+// sentences end with these, the full-width ones do not need a following space
+const SENTENCE_ENDS: [char; 3] = ['.', '!', '?'];
+const FULL_WIDTH_SENTENCE_ENDS: [char; 3] = ['。', '！', '？'];
+// closing characters which may follow the end of a sentence
+const SENTENCE_CLOSERS: [char; 7] = [')', ']', '"', '\'', '」', '』', '）'];
+// an opening, closing or self-closing tag with its name
+static TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<(/?)([A-Za-z][\w:.-]*)[^<>]*?(/?)>").unwrap());
+
+// the range of a text object like `iw` or `a(` around the cursor
+// a count selects more words or an outer pair
+pub fn select(keys: &str, cursor: Position, buffer: &Buffer, count: usize) -> Option<TextRange> {
+    let mut chars = keys.chars();
+    let inner = match chars.next()? {
+        'i' => true,
+        'a' => false,
+        _ => return None,
+    };
+    let (Some(object), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+    let count = count.max(1);
+    // the objects in one line or of whole lines do not need the text of the buffer
+    match object {
+        'w' | 'W' => return word(cursor, buffer, object == 'W', inner, count),
+        'p' => return paragraph(cursor.line_idx, buffer, inner, count),
+        quote if QUOTES.contains(&quote) => return self::quote(cursor, buffer, quote, inner),
+        _ => (),
+    }
+    // the text kept by the buffer until an edit
+    let scan = buffer.text_scan();
+    let text = &scan.text;
+    let offset = text.offset(cursor, buffer);
+    let range = match object {
+        's' => sentence(text, cursor.line_idx, offset, buffer, inner)?,
+        't' => tag(&text.content, offset, inner, count)?,
+        'b' => bracket(&text.content, offset, ('(', ')'), inner, count)?,
+        'B' => bracket(&text.content, offset, ('{', '}'), inner, count)?,
+        object => {
+            // `<>` pairs only as a text object, they are usually not brackets in code
+            let pair = BRACKETS
+                .into_iter()
+                .chain([('<', '>')])
+                .find(|(open, close)| *open == object || *close == object)?;
+            bracket(&text.content, offset, pair, inner, count)?
+        }
+    };
+    Some(TextRange::Charwise {
+        start: text.position(range.start, buffer),
+        end: text.position(range.end, buffer),
+    })
+}
+
+// the buffer as one string, to find objects across lines
+pub struct Text {
+    pub content: String,
+    // byte offset of each line in the content
+    line_starts: Vec<usize>,
+}
+
+impl Text {
+    pub fn new(buffer: &Buffer) -> Self {
+        let mut content = String::new();
+        let mut line_starts = Vec::with_capacity(buffer.lines.len());
+        for (idx, line) in buffer.lines.iter().enumerate() {
+            if idx > 0 {
+                content.push('\n');
+            }
+            line_starts.push(content.len());
+            content.push_str(line.content());
+        }
+        Self {
+            content,
+            line_starts,
+        }
+    }
+    pub fn offset(&self, position: Position, buffer: &Buffer) -> usize {
+        let Some(line) = buffer.lines.get(position.line_idx) else {
+            return self.content.len();
+        };
+        self.line_starts.get(position.line_idx).map_or(0, |start| {
+            start.saturating_add(line.col_idx_to_byte_idx(position.col_idx))
+        })
+    }
+    pub fn position(&self, offset: usize, buffer: &Buffer) -> Position {
+        let line_idx = self
+            .line_starts
+            .partition_point(|start| *start <= offset)
+            .saturating_sub(1);
+        let line_start = self.line_starts.get(line_idx).copied().unwrap_or_default();
+        let col_idx = buffer.lines.get(line_idx).map_or(0, |line| {
+            line.byte_idx_to_col_idx(offset.saturating_sub(line_start))
+        });
+        Position { line_idx, col_idx }
+    }
+}
+
+// `iw` is a run of characters of the same class, `aw` adds the whitespace around it
+fn word(
+    cursor: Position,
+    buffer: &Buffer,
+    bigword: bool,
+    inner: bool,
+    count: usize,
+) -> Option<TextRange> {
+    let line = buffer.lines.get(cursor.line_idx)?;
+    let fragments = line.fragments();
+    let classes: Vec<CharClass> = fragments
+        .iter()
+        .map(|fragment| CharClass::of(fragment.grapheme(), bigword))
+        .collect();
+    let len = classes.len();
+    let current = fragments
+        .iter()
+        .position(|fragment| fragment.left_col_width() >= cursor.col_idx)
+        .unwrap_or(len)
+        .min(len.checked_sub(1)?);
+    let run_end = |idx: usize| {
+        let class = classes.get(idx);
+        (idx..len)
+            .find(|next| classes.get(*next) != class)
+            .unwrap_or(len)
+    };
+    let run_start = |idx: usize| {
+        let class = classes.get(idx);
+        (0..idx)
+            .rev()
+            .find(|prev| classes.get(*prev) != class)
+            .map_or(0, |prev| prev.saturating_add(1))
+    };
+    let mut start = run_start(current);
+    let mut end = current;
+    for _ in 0..count {
+        let Some(class) = classes.get(end) else {
+            break;
+        };
+        let blank = class.is_blank();
+        end = run_end(end);
+        // `aw` takes the whitespace after a word, or the word after whitespace
+        if !inner && (blank || classes.get(end).is_some_and(|class| class.is_blank())) {
+            end = run_end(end);
+        }
+    }
+    let ends_with_blank = end
+        .checked_sub(1)
+        .and_then(|last| classes.get(last))
+        .is_some_and(|class| class.is_blank());
+    if !inner && !ends_with_blank && start > 0 {
+        // no whitespace after the word, take the whitespace before it
+        let prev = start.saturating_sub(1);
+        if classes.get(prev).is_some_and(|class| class.is_blank()) {
+            start = run_start(prev);
+        }
+    }
+    let col_idx = |idx: usize| {
+        fragments
+            .get(idx)
+            .map_or(line.col_width(), TextFragment::left_col_width)
+    };
+    Some(TextRange::Charwise {
+        start: Position {
+            line_idx: cursor.line_idx,
+            col_idx: col_idx(start),
+        },
+        end: Position {
+            line_idx: cursor.line_idx,
+            col_idx: col_idx(end),
+        },
+    })
+}
+
+// `ip` is the lines of the paragraph or of the blank lines, `ap` adds the blank lines after it
+fn paragraph(line_idx: usize, buffer: &Buffer, inner: bool, count: usize) -> Option<TextRange> {
+    let lines_count = buffer.get_lines_count();
+    let is_blank = |idx: usize| buffer.is_blank_line(idx);
+    if line_idx >= lines_count {
+        return None;
+    }
+    let blank = is_blank(line_idx);
+    let mut start = line_idx;
+    while start > 0 && is_blank(start.saturating_sub(1)) == blank {
+        start = start.saturating_sub(1);
+    }
+    // each count is a paragraph and blank lines for `ap`
+    let blocks = if inner {
+        count
+    } else {
+        count.saturating_mul(2)
+    };
+    let mut end = line_idx;
+    let mut last_blank = blank;
+    for _ in 0..blocks {
+        if end >= lines_count {
+            break;
+        }
+        last_blank = is_blank(end);
+        while end < lines_count && is_blank(end) == last_blank {
+            end = end.saturating_add(1);
+        }
+    }
+    if !inner && !blank && !last_blank {
+        // no blank lines after the paragraph, take the blank lines before it
+        while start > 0 && is_blank(start.saturating_sub(1)) {
+            start = start.saturating_sub(1);
+        }
+    }
+    Some(TextRange::Linewise(start..end))
+}
+
+// `is` ends at the end of the sentence, `as` adds the whitespace after it
+// sentences do not continue over a blank line
+fn sentence(
+    text: &Text,
+    line_idx: usize,
+    offset: usize,
+    buffer: &Buffer,
+    inner: bool,
+) -> Option<Range<usize>> {
+    let lines = paragraph_lines(line_idx, buffer);
+    let para_start = text.offset(
+        Position {
+            line_idx: lines.start,
+            col_idx: 0,
+        },
+        buffer,
+    );
+    let last = lines.end.saturating_sub(1);
+    let para_end = text.offset(
+        Position {
+            line_idx: last,
+            col_idx: buffer.get_line_col_width(last),
+        },
+        buffer,
+    );
+    let para = text.content.get(para_start..para_end)?;
+    let sentences = split_sentences(para);
+    let cursor = offset.saturating_sub(para_start);
+    let position = sentences
+        .iter()
+        .position(|(_, _, next)| cursor < *next)
+        .or_else(|| sentences.len().checked_sub(1))?;
+    let (start, text_end, next) = sentences.get(position)?;
+    let range = if inner {
+        *start..*text_end
+    } else if next > text_end {
+        *start..*next
+    } else {
+        // no whitespace after the last sentence, take the whitespace before it
+        let prev_end = position
+            .checked_sub(1)
+            .and_then(|prev| sentences.get(prev))
+            .map_or(*start, |(_, prev_end, _)| *prev_end);
+        prev_end..*text_end
+    };
+    Some(range.start.saturating_add(para_start)..range.end.saturating_add(para_start))
+}
+
+// (start, end of the text, start of the next sentence) of each sentence in a paragraph
+fn split_sentences(para: &str) -> Vec<(usize, usize, usize)> {
+    let chars: Vec<(usize, char)> = para.char_indices().collect();
+    let byte_at = |idx: usize| chars.get(idx).map_or(para.len(), |(byte, _)| *byte);
+    let skip_blank = |mut idx: usize| {
+        while chars.get(idx).is_some_and(|(_, c)| c.is_whitespace()) {
+            idx = idx.saturating_add(1);
+        }
+        idx
+    };
+    let mut sentences = Vec::new();
+    let mut start = skip_blank(0);
+    let mut idx = start;
+    while let Some((_, c)) = chars.get(idx) {
+        let full_width = FULL_WIDTH_SENTENCE_ENDS.contains(c);
+        if !full_width && !SENTENCE_ENDS.contains(c) {
+            idx = idx.saturating_add(1);
+            continue;
+        }
+        let mut end = idx.saturating_add(1);
+        while chars
+            .get(end)
+            .is_some_and(|(_, c)| SENTENCE_CLOSERS.contains(c))
+        {
+            end = end.saturating_add(1);
+        }
+        if full_width || chars.get(end).is_none_or(|(_, c)| c.is_whitespace()) {
+            let next = skip_blank(end);
+            sentences.push((byte_at(start), byte_at(end), byte_at(next)));
+            start = next;
+            idx = next;
+        } else {
+            idx = end;
+        }
+    }
+    if start < chars.len() {
+        let text_end = para.trim_end().len().max(byte_at(start));
+        sentences.push((byte_at(start), text_end, para.len()));
+    }
+    sentences
+}
+
+// the lines of the paragraph at the line, or the blank line itself
+fn paragraph_lines(line_idx: usize, buffer: &Buffer) -> Range<usize> {
+    if buffer.is_blank_line(line_idx) {
+        return line_idx..line_idx.saturating_add(1);
+    }
+    let mut start = line_idx;
+    while start > 0 && !buffer.is_blank_line(start.saturating_sub(1)) {
+        start = start.saturating_sub(1);
+    }
+    let mut end = line_idx.saturating_add(1);
+    while !buffer.is_blank_line(end) {
+        end = end.saturating_add(1);
+    }
+    start..end
+}
+
+// the lines of the paragraph or the blank line at the line, and where `(` and `)` stop in them
+// the stops are the start of each sentence, or the start of the blank line
+fn sentence_stops(line_idx: usize, buffer: &Buffer) -> (Range<usize>, Vec<Position>) {
+    let Range { start, end } = paragraph_lines(line_idx, buffer);
+    if buffer.is_blank_line(line_idx) {
+        return (
+            start..end,
+            vec![Position {
+                line_idx,
+                col_idx: 0,
+            }],
+        );
+    }
+    let lines = buffer.lines.get(start..end).unwrap_or_default();
+    let para = lines
+        .iter()
+        .map(Line::content)
+        .collect::<Vec<_>>()
+        .join("\n");
+    // byte offset of each line in the paragraph
+    let line_starts: Vec<usize> = lines
+        .iter()
+        .scan(0, |offset: &mut usize, line| {
+            let line_start = *offset;
+            *offset = offset
+                .saturating_add(line.content().len())
+                .saturating_add(1);
+            Some(line_start)
+        })
+        .collect();
+    let stops = split_sentences(&para)
+        .iter()
+        .map(|(sentence_start, _, _)| {
+            let idx = line_starts
+                .partition_point(|line_start| line_start <= sentence_start)
+                .saturating_sub(1);
+            let line_start = line_starts.get(idx).copied().unwrap_or_default();
+            Position {
+                line_idx: start.saturating_add(idx),
+                col_idx: lines.get(idx).map_or(0, |line| {
+                    line.byte_idx_to_col_idx(sentence_start.saturating_sub(line_start))
+                }),
+            }
+        })
+        .collect();
+    (start..end, stops)
+}
+
+// `)` and `(`, the start of the count-th sentence after or before the cursor
+// only the paragraphs up to the target are read, it stops at the end or the start of the buffer
+pub fn sentence_motion(cursor: Position, buffer: &Buffer, forward: bool, count: usize) -> Position {
+    let lines_count = buffer.get_lines_count();
+    let mut remaining = count.max(1);
+    let mut line_idx = cursor.line_idx.min(lines_count.saturating_sub(1));
+    while line_idx < lines_count {
+        let (lines, stops) = sentence_stops(line_idx, buffer);
+        // the stops past the cursor, from the nearest one
+        let passed: Vec<Position> = if forward {
+            stops.into_iter().filter(|stop| *stop > cursor).collect()
+        } else {
+            stops
+                .into_iter()
+                .rev()
+                .filter(|stop| *stop < cursor)
+                .collect()
+        };
+        if let Some(target) = passed.get(remaining.saturating_sub(1)) {
+            return *target;
+        }
+        remaining = remaining.saturating_sub(passed.len());
+        if forward {
+            line_idx = lines.end;
+        } else if let Some(prev) = lines.start.checked_sub(1) {
+            line_idx = prev;
+        } else {
+            break;
+        }
+    }
+    if forward {
+        let line_idx = lines_count.saturating_sub(1);
+        Position {
+            line_idx,
+            col_idx: buffer.get_line_col_width(line_idx),
+        }
+    } else {
+        Position::default()
+    }
+}
+
+// a quoted string in the line, the quotes are paired from the start of the line
+fn quote(cursor: Position, buffer: &Buffer, quote: char, inner: bool) -> Option<TextRange> {
+    let line_idx = cursor.line_idx;
+    let text_line = buffer.lines.get(line_idx)?;
+    let line = text_line.content();
+    let cursor = text_line.col_idx_to_byte_idx(cursor.col_idx);
+    // quotes escaped by a backslash are not counted
+    let quotes: Vec<usize> = line
+        .char_indices()
+        .filter(|(idx, c)| *c == quote && !line.get(..*idx).is_some_and(|s| s.ends_with('\\')))
+        .map(|(idx, _)| idx)
+        .collect();
+    let (open, close) = quotes
+        .chunks_exact(2)
+        .map(|pair| (pair.first().copied(), pair.get(1).copied()))
+        .find_map(|pair| match pair {
+            (Some(open), Some(close)) if cursor <= close => Some((open, close)),
+            _ => None,
+        })?;
+    let quote_len = quote.len_utf8();
+    let range = if inner {
+        open.saturating_add(quote_len)..close
+    } else {
+        let end = close.saturating_add(quote_len);
+        let trailing = line.get(end..)?;
+        let trimmed = trailing.trim_start();
+        if trimmed.len() < trailing.len() {
+            open..line.len().saturating_sub(trimmed.len())
+        } else {
+            // no whitespace after the closing quote, take the whitespace before the opening one
+            let leading = line.get(..open)?;
+            leading.trim_end().len()..end
+        }
+    };
+    let position = |byte_idx: usize| Position {
+        line_idx,
+        col_idx: text_line.byte_idx_to_col_idx(byte_idx),
+    };
+    Some(TextRange::Charwise {
+        start: position(range.start),
+        end: position(range.end),
+    })
+}
+
+// the pair of brackets around the offset, the count-th one from the inside
+fn bracket(
+    content: &str,
+    offset: usize,
+    (open, close): (char, char),
+    inner: bool,
+    count: usize,
+) -> Option<Range<usize>> {
+    // a bracket under the cursor belongs to the pair
+    let search_end = if content.get(offset..)?.starts_with(open) {
+        offset.saturating_add(open.len_utf8())
+    } else {
+        offset
+    };
+    let mut depth: usize = 0;
+    let mut remaining = count;
+    let open_idx = content
+        .get(..search_end)?
+        .char_indices()
+        .rev()
+        .find_map(|(idx, c)| {
+            if c == close {
+                depth = depth.saturating_add(1);
+            } else if c == open {
+                if depth == 0 {
+                    remaining = remaining.saturating_sub(1);
+                    if remaining == 0 {
+                        return Some(idx);
+                    }
+                } else {
+                    depth = depth.saturating_sub(1);
+                }
+            }
+            None
+        })?;
+    let inner_start = open_idx.saturating_add(open.len_utf8());
+    let mut depth: usize = 0;
+    let close_idx = content
+        .get(inner_start..)?
+        .char_indices()
+        .find_map(|(idx, c)| {
+            if c == open {
+                depth = depth.saturating_add(1);
+            } else if c == close {
+                if depth == 0 {
+                    return Some(inner_start.saturating_add(idx));
+                }
+                depth = depth.saturating_sub(1);
+            }
+            None
+        })?;
+    if !inner {
+        return Some(open_idx..close_idx.saturating_add(close.len_utf8()));
+    }
+    // the lines between brackets on their own lines, without the line breaks next to the brackets
+    let inner_content = content.get(inner_start..close_idx)?;
+    let start = if inner_content.starts_with('\n') {
+        inner_start.saturating_add(1)
+    } else {
+        inner_start
+    };
+    let end = match inner_content.rfind('\n') {
+        Some(idx) if inner_content.get(idx..)?.trim().is_empty() => {
+            inner_start.saturating_add(idx).saturating_add(1)
+        }
+        _ => close_idx,
+    };
+    Some(start..end.max(start))
+}
+
+// a pair of XML or HTML tags around the offset, the count-th one from the inside
+fn tag(content: &str, offset: usize, inner: bool, count: usize) -> Option<Range<usize>> {
+    let mut opened: Vec<(&str, Range<usize>)> = Vec::new();
+    let mut pairs: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+    for caps in TAG.captures_iter(content) {
+        let (Some(whole), Some(name)) = (caps.get(0), caps.get(2)) else {
+            continue;
+        };
+        let closing = caps.get(1).is_some_and(|m| !m.as_str().is_empty());
+        let self_closing = caps.get(3).is_some_and(|m| !m.as_str().is_empty());
+        if self_closing {
+            continue;
+        }
+        if !closing {
+            opened.push((name.as_str(), whole.range()));
+        } else if let Some(idx) = opened.iter().rposition(|(open, _)| *open == name.as_str()) {
+            // tags opened inside and not closed are dropped
+            if let Some((_, open)) = opened.drain(idx..).next() {
+                pairs.push((open, whole.range()));
+            }
+        }
+    }
+    let mut enclosing: Vec<(Range<usize>, Range<usize>)> = pairs
+        .into_iter()
+        .filter(|(open, close)| open.start <= offset && offset < close.end)
+        .collect();
+    enclosing.sort_by_key(|(open, _)| std::cmp::Reverse(open.start));
+    let (open, close) = enclosing.get(count.saturating_sub(1))?;
+    Some(if inner {
+        open.end..close.start
+    } else {
+        open.start..close.end
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected(text: &str, cursor: Position, keys: &str, count: usize) -> String {
+        let buffer = Buffer::from_string(text);
+        match select(keys, cursor, &buffer, count) {
+            Some(TextRange::Charwise { start, end }) => buffer.get_text(start, end),
+            Some(TextRange::Linewise(lines)) => buffer.get_lines(lines).join("|"),
+            _ => "-".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_word() {
+        let text = "foo bar.baz  qux\n";
+        assert_eq!(selected(text, Position::new(0, 5), "iw", 1), "bar");
+        assert_eq!(selected(text, Position::new(0, 5), "aw", 1), " bar");
+        assert_eq!(selected(text, Position::new(0, 0), "aw", 1), "foo ");
+        assert_eq!(selected(text, Position::new(0, 3), "aw", 1), " bar");
+        assert_eq!(selected(text, Position::new(0, 5), "iW", 1), "bar.baz");
+        assert_eq!(selected(text, Position::new(0, 5), "iw", 3), "bar.baz");
+        assert_eq!(selected(text, Position::new(0, 0), "aw", 2), "foo bar");
+        assert_eq!(
+            selected("私はカタカナ\n", Position::new(0, 6), "iw", 1),
+            "カタカナ"
+        );
+    }
+
+    #[test]
+    fn test_paragraph() {
+        let text = "a\nb\n\n\nc\n";
+        assert_eq!(selected(text, Position::new(1, 0), "ip", 1), "a|b");
+        assert_eq!(selected(text, Position::new(1, 0), "ap", 1), "a|b||");
+        assert_eq!(selected(text, Position::new(2, 0), "ip", 1), "|");
+        assert_eq!(selected(text, Position::new(4, 0), "ap", 1), "||c");
+        assert_eq!(selected(text, Position::new(0, 0), "ip", 3), "a|b|||c");
+    }
+
+    #[test]
+    fn test_sentence() {
+        let text = "One. Two three!  Four\nfive.\n\nSix.\n";
+        assert_eq!(selected(text, Position::new(0, 6), "is", 1), "Two three!");
+        assert_eq!(selected(text, Position::new(0, 6), "as", 1), "Two three!  ");
+        assert_eq!(selected(text, Position::new(1, 0), "is", 1), "Four\nfive.");
+        assert_eq!(
+            selected(text, Position::new(1, 0), "as", 1),
+            "  Four\nfive."
+        );
+        assert_eq!(selected(text, Position::new(3, 0), "is", 1), "Six.");
+        // a line of whitespace only ends the paragraph
+        let text = "One\n  \ntwo.\n";
+        assert_eq!(selected(text, Position::new(2, 0), "is", 1), "two.");
+        assert_eq!(selected(text, Position::new(0, 0), "as", 1), "One");
+        let text = "今日は晴れ。明日は雨！\n";
+        assert_eq!(selected(text, Position::new(0, 14), "is", 1), "明日は雨！");
+    }
+
+    #[test]
+    fn test_quote() {
+        let text = "say \"a \\\"b\\\"\" and 'c'\n";
+        assert_eq!(selected(text, Position::new(0, 6), "i\"", 1), "a \\\"b\\\"");
+        assert_eq!(
+            selected(text, Position::new(0, 0), "a\"", 1),
+            "\"a \\\"b\\\"\" "
+        );
+        assert_eq!(selected(text, Position::new(0, 19), "a'", 1), " 'c'");
+        assert_eq!(selected(text, Position::new(0, 21), "i`", 1), "-");
+    }
+
+    #[test]
+    fn test_bracket() {
+        let text = "f(a, (b), c)\n";
+        assert_eq!(selected(text, Position::new(0, 6), "i(", 1), "b");
+        assert_eq!(selected(text, Position::new(0, 6), "ib", 2), "a, (b), c");
+        assert_eq!(selected(text, Position::new(0, 5), "a)", 1), "(b)");
+        assert_eq!(selected(text, Position::new(0, 11), "a(", 1), "(a, (b), c)");
+        assert_eq!(selected(text, Position::new(0, 0), "i(", 1), "-");
+        let text = "fn f() {\n    a;\n    {b}\n}\n";
+        assert_eq!(
+            selected(text, Position::new(1, 4), "i{", 1),
+            "    a;\n    {b}\n"
+        );
+        assert_eq!(selected(text, Position::new(2, 5), "iB", 1), "b");
+        assert_eq!(
+            selected(text, Position::new(2, 5), "aB", 2),
+            "{\n    a;\n    {b}\n}"
+        );
+        let text = "「外側『内側』の」\n";
+        assert_eq!(selected(text, Position::new(0, 6), "i『", 1), "内側");
+        assert_eq!(
+            selected(text, Position::new(0, 6), "a」", 1),
+            text.trim_end()
+        );
+        assert_eq!(
+            selected("【見出し】（注）\n", Position::new(0, 12), "i）", 1),
+            "注"
+        );
+        assert_eq!(selected("〈a〉\n", Position::new(0, 2), "i〈", 1), "a");
+    }
+
+    #[test]
+    fn test_tag() {
+        let text = "<div id=\"x\"><p>a<br/>b</p>\n<p>c</p></div>\n";
+        assert_eq!(selected(text, Position::new(0, 16), "it", 1), "a<br/>b");
+        assert_eq!(
+            selected(text, Position::new(0, 16), "at", 1),
+            "<p>a<br/>b</p>"
+        );
+        assert_eq!(
+            selected(text, Position::new(0, 16), "it", 2),
+            "<p>a<br/>b</p>\n<p>c</p>"
+        );
+        assert_eq!(
+            selected(text, Position::new(1, 8), "it", 1),
+            "<p>a<br/>b</p>\n<p>c</p>"
+        );
+    }
+
+    #[test]
+    fn test_sentence_motion() {
+        let buffer = Buffer::from_string("One. Two!  Three\nfour.\n\n今日は晴れ。明日\n");
+        let forward = |line_idx, col_idx, count| {
+            sentence_motion(Position::new(line_idx, col_idx), &buffer, true, count)
+        };
+        let backward = |line_idx, col_idx, count| {
+            sentence_motion(Position::new(line_idx, col_idx), &buffer, false, count)
+        };
+        assert_eq!(forward(0, 0, 1), Position::new(0, 5));
+        assert_eq!(forward(0, 0, 2), Position::new(0, 11));
+        assert_eq!(forward(0, 11, 1), Position::new(2, 0));
+        assert_eq!(forward(2, 0, 1), Position::new(3, 0));
+        assert_eq!(forward(3, 0, 1), Position::new(3, 12));
+        assert_eq!(forward(3, 12, 1), Position::new(3, 16));
+        assert_eq!(backward(3, 14, 1), Position::new(3, 12));
+        assert_eq!(backward(3, 0, 1), Position::new(2, 0));
+        assert_eq!(backward(1, 2, 1), Position::new(0, 11));
+        assert_eq!(backward(0, 11, 5), Position::new(0, 0));
+        // a line of spaces separates paragraphs like with `}`
+        let buffer = Buffer::from_string("a. b\n  \nc\n");
+        let forward = |line_idx, col_idx, count| {
+            sentence_motion(Position::new(line_idx, col_idx), &buffer, true, count)
+        };
+        assert_eq!(forward(0, 0, 1), Position::new(0, 3));
+        assert_eq!(forward(0, 3, 1), Position::new(1, 0));
+        assert_eq!(forward(0, 0, 3), Position::new(2, 0));
+        assert_eq!(
+            sentence_motion(Position::new(2, 0), &buffer, false, 1),
+            Position::new(1, 0)
+        );
+    }
+}
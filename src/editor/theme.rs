@@ -0,0 +1,471 @@
+use super::annotation::AnnotationKind;
+use super::screen::Style;
+use super::syntax::TokenKind;
+use super::terminal::{Attribute, Color};
+use std::collections::HashMap;
+use std::env;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+// the names a theme gives colors to
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub enum HighlightGroup {
+    Normal,
+    StatusLine,
+    Search,
+    Visual,
+    MatchParen,
+    LineNr,
+    CursorLineNr,
+    // `~` below the last line and the replacements like `→` and `·`
+    NonText,
+    Comment,
+    String,
+    Keyword,
+    Type,
+    Number,
+    Function,
+    Macro,
+    Attribute,
+    Special,
+}
+
+const GROUPS: [(&str, HighlightGroup); 17] = [
+    ("Normal", HighlightGroup::Normal),
+    ("StatusLine", HighlightGroup::StatusLine),
+    ("Search", HighlightGroup::Search),
+    ("Visual", HighlightGroup::Visual),
+    ("MatchParen", HighlightGroup::MatchParen),
+    ("LineNr", HighlightGroup::LineNr),
+    ("CursorLineNr", HighlightGroup::CursorLineNr),
+    ("NonText", HighlightGroup::NonText),
+    ("Comment", HighlightGroup::Comment),
+    ("String", HighlightGroup::String),
+    ("Keyword", HighlightGroup::Keyword),
+    ("Type", HighlightGroup::Type),
+    ("Number", HighlightGroup::Number),
+    ("Function", HighlightGroup::Function),
+    ("Macro", HighlightGroup::Macro),
+    ("Attribute", HighlightGroup::Attribute),
+    ("Special", HighlightGroup::Special),
+];
+
+// the 16 colors in the order of their ANSI numbers, with the usual xterm values
+const ANSI_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (205, 0, 0)),
+    (Color::DarkGreen, (0, 205, 0)),
+    (Color::DarkYellow, (205, 205, 0)),
+    (Color::DarkBlue, (0, 0, 238)),
+    (Color::DarkMagenta, (205, 0, 205)),
+    (Color::DarkCyan, (0, 205, 205)),
+    (Color::Grey, (229, 229, 229)),
+    (Color::DarkGrey, (127, 127, 127)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (92, 92, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+// the levels of the 6x6x6 color cube of the 256 colors
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl HighlightGroup {
+    pub fn of(kind: Option<AnnotationKind>) -> Self {
+        match kind {
+            None => Self::Normal,
+            Some(AnnotationKind::SearchMatch) => Self::Search,
+            Some(AnnotationKind::Selection) => Self::Visual,
+            Some(AnnotationKind::MatchingBracket) => Self::MatchParen,
+            Some(AnnotationKind::LineNumber) => Self::LineNr,
+            Some(AnnotationKind::CursorLineNumber) => Self::CursorLineNr,
+            Some(AnnotationKind::NonText) => Self::NonText,
+            Some(AnnotationKind::Syntax(token)) => match token {
+                TokenKind::Comment => Self::Comment,
+                TokenKind::String => Self::String,
+                TokenKind::Keyword => Self::Keyword,
+                TokenKind::Type => Self::Type,
+                TokenKind::Number => Self::Number,
+                TokenKind::Function => Self::Function,
+                TokenKind::Macro => Self::Macro,
+                TokenKind::Attribute => Self::Attribute,
+                TokenKind::Special => Self::Special,
+            },
+        }
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        GROUPS
+            .iter()
+            .find(|(group_name, _)| group_name.eq_ignore_ascii_case(name))
+            .map(|(_, group)| *group)
+    }
+}
+
+// the colors the terminal can show
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    #[default]
+    Ansi16,
+}
+
+impl ColorDepth {
+    pub fn detect() -> Self {
+        Self::from_env(
+            env::var("COLORTERM").ok().as_deref(),
+            env::var("TERM").ok().as_deref(),
+        )
+    }
+    fn from_env(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            Self::TrueColor
+        } else if term.is_some_and(|term| term.contains("256color")) {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+    // a color the terminal cannot show is replaced with the nearest one it can
+    pub fn convert(self, color: Color) -> Color {
+        match (self, color) {
+            (Self::Ansi256, Color::Rgb { r, g, b }) => Color::AnsiValue(rgb_to_ansi256(r, g, b)),
+            (Self::Ansi16, Color::Rgb { r, g, b }) => nearest_ansi16((r, g, b)),
+            (Self::Ansi16, Color::AnsiValue(value)) => {
+                ANSI_COLORS.get(usize::from(value)).map_or_else(
+                    || nearest_ansi16(ansi256_to_rgb(value)),
+                    |(color, _)| *color,
+                )
+            }
+            _ => color,
+        }
+    }
+}
+
+#[allow(clippy::integer_division)]
+fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    if r == g && g == b {
+        // the gray ramp from 232 to 255, from 8 to 238 in steps of 10
+        return match r {
+            0..8 => 16,
+            249.. => 231,
+            _ => 232u8.saturating_add((r.saturating_sub(8) / 10).min(23)),
+        };
+    }
+    let level = |value: u8| -> u8 {
+        match value {
+            0..48 => 0,
+            48..115 => 1,
+            _ => (value.saturating_sub(35)) / 40,
+        }
+    };
+    16u8.saturating_add(level(r).saturating_mul(36))
+        .saturating_add(level(g).saturating_mul(6))
+        .saturating_add(level(b))
+}
+
+#[allow(clippy::integer_division)]
+fn ansi256_to_rgb(value: u8) -> (u8, u8, u8) {
+    match value {
+        0..16 => ANSI_COLORS
+            .get(usize::from(value))
+            .map_or((0, 0, 0), |(_, rgb)| *rgb),
+        16..232 => {
+            let idx = value.saturating_sub(16);
+            let level = |level: u8| {
+                CUBE_LEVELS
+                    .get(usize::from(level % 6))
+                    .copied()
+                    .unwrap_or(0)
+            };
+            (level(idx / 36), level(idx / 6), level(idx))
+        }
+        _ => {
+            let gray = value
+                .saturating_sub(232)
+                .saturating_mul(10)
+                .saturating_add(8);
+            (gray, gray, gray)
+        }
+    }
+}
+
+fn nearest_ansi16(rgb: (u8, u8, u8)) -> Color {
+    let distance = |other: (u8, u8, u8)| {
+        let diff = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+        diff(rgb.0, other.0)
+            .saturating_add(diff(rgb.1, other.1))
+            .saturating_add(diff(rgb.2, other.2))
+    };
+    ANSI_COLORS
+        .iter()
+        .min_by_key(|(_, other)| distance(*other))
+        .map_or(Color::Reset, |(color, _)| *color)
+}
+
+// the styles of the highlight groups
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    styles: HashMap<HighlightGroup, Style>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let fg = |color: Color| Style {
+            fg: Some(color),
+            ..Style::default()
+        };
+        let on = |fg: Color, bg: Color| Style {
+            fg: Some(fg),
+            bg: Some(bg),
+            ..Style::default()
+        };
+        let reverse = Style {
+            attributes: Attribute::Reverse.into(),
+            ..Style::default()
+        };
+        Self {
+            name: String::from("default"),
+            styles: HashMap::from([
+                (HighlightGroup::Normal, Style::default()),
+                (HighlightGroup::StatusLine, reverse),
+                (HighlightGroup::Search, on(Color::Black, Color::Yellow)),
+                (HighlightGroup::Visual, reverse),
+                (HighlightGroup::MatchParen, on(Color::Black, Color::Cyan)),
+                (HighlightGroup::LineNr, fg(Color::DarkGrey)),
+                (HighlightGroup::CursorLineNr, fg(Color::Yellow)),
+                (HighlightGroup::NonText, fg(Color::DarkGrey)),
+                (HighlightGroup::Comment, fg(Color::DarkGrey)),
+                (HighlightGroup::String, fg(Color::Green)),
+                (HighlightGroup::Keyword, fg(Color::Magenta)),
+                (HighlightGroup::Type, fg(Color::Yellow)),
+                (HighlightGroup::Number, fg(Color::Cyan)),
+                (HighlightGroup::Function, fg(Color::Blue)),
+                (HighlightGroup::Macro, fg(Color::DarkCyan)),
+                (HighlightGroup::Attribute, fg(Color::DarkYellow)),
+                (HighlightGroup::Special, fg(Color::Red)),
+            ]),
+        }
+    }
+}
+
+impl Theme {
+    // `:colorscheme name` reads `colors/name.theme` in the config directory, a path is read as is
+    pub fn load(name: &str) -> Result<Self, String> {
+        if name == "default" {
+            return Ok(Self::default());
+        }
+        let is_path = name.contains('/')
+            || Path::new(name)
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("theme"));
+        let path = if is_path {
+            PathBuf::from(name)
+        } else {
+            let config_dir = env::var("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .or_else(|_| env::var("HOME").map(|home| PathBuf::from(home).join(".config")))
+                .map_err(|_| format!("Cannot find color scheme: {name}"))?;
+            config_dir
+                .join("koi")
+                .join("colors")
+                .join(format!("{name}.theme"))
+        };
+        let text =
+            read_to_string(&path).map_err(|_| format!("Cannot find color scheme: {name}"))?;
+        Self::parse(name, &text)
+    }
+    // a line is a group and its colors like `Comment fg=#808080 bg=none italic`
+    // the groups not in the text keep the default colors
+    pub fn parse(name: &str, text: &str) -> Result<Self, String> {
+        let mut theme = Self {
+            name: name.to_string(),
+            ..Self::default()
+        };
+        for (line_idx, line) in text.lines().enumerate() {
+            let line_number = line_idx.saturating_add(1);
+            let mut words = line.split_whitespace();
+            let Some(group_name) = words.next().filter(|word| !word.starts_with('#')) else {
+                continue;
+            };
+            let group = HighlightGroup::from_name(group_name)
+                .ok_or(format!("line {line_number}: unknown group {group_name}"))?;
+            let mut style = Style::default();
+            for word in words {
+                match word.split_once('=') {
+                    Some(("fg", value)) => style.fg = parse_color(value, line_number)?,
+                    Some(("bg", value)) => style.bg = parse_color(value, line_number)?,
+                    _ => style.attributes.set(parse_attribute(word, line_number)?),
+                }
+            }
+            theme.styles.insert(group, style);
+        }
+        Ok(theme)
+    }
+    // the colors a group does not set are the colors of Normal
+    pub fn style(&self, group: HighlightGroup) -> Style {
+        let normal = self
+            .styles
+            .get(&HighlightGroup::Normal)
+            .copied()
+            .unwrap_or_default();
+        let style = self.styles.get(&group).copied().unwrap_or_default();
+        Style {
+            fg: style.fg.or(normal.fg),
+            bg: style.bg.or(normal.bg),
+            attributes: style.attributes,
+        }
+    }
+    pub fn with_depth(mut self, depth: ColorDepth) -> Self {
+        for style in self.styles.values_mut() {
+            style.fg = style.fg.map(|color| depth.convert(color));
+            style.bg = style.bg.map(|color| depth.convert(color));
+        }
+        self
+    }
+}
+
+fn parse_color(value: &str, line_number: usize) -> Result<Option<Color>, String> {
+    let invalid = || format!("line {line_number}: invalid color {value}");
+    if let Some(hex) = value.strip_prefix('#') {
+        let channel = |range| {
+            hex.get(range)
+                .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+        };
+        return match (hex.len(), channel(0..2), channel(2..4), channel(4..6)) {
+            (6, Some(r), Some(g), Some(b)) => Ok(Some(Color::Rgb { r, g, b })),
+            _ => Err(invalid()),
+        };
+    }
+    if let Ok(value) = value.parse::<u8>() {
+        return Ok(Some(Color::AnsiValue(value)));
+    }
+    let color = match value.to_ascii_lowercase().as_str() {
+        "none" => return Ok(None),
+        "black" => Color::Black,
+        "darkred" => Color::DarkRed,
+        "darkgreen" => Color::DarkGreen,
+        "darkyellow" => Color::DarkYellow,
+        "darkblue" => Color::DarkBlue,
+        "darkmagenta" => Color::DarkMagenta,
+        "darkcyan" => Color::DarkCyan,
+        "grey" | "gray" => Color::Grey,
+        "darkgrey" | "darkgray" => Color::DarkGrey,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        _ => return Err(invalid()),
+    };
+    Ok(Some(color))
+}
+
+fn parse_attribute(word: &str, line_number: usize) -> Result<Attribute, String> {
+    match word {
+        "bold" => Ok(Attribute::Bold),
+        "italic" => Ok(Attribute::Italic),
+        "underline" => Ok(Attribute::Underlined),
+        "reverse" => Ok(Attribute::Reverse),
+        _ => Err(format!("line {line_number}: unknown attribute {word}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_depth() {
+        assert_eq!(
+            ColorDepth::from_env(Some("truecolor"), Some("xterm-256color")),
+            ColorDepth::TrueColor
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("xterm-256color")),
+            ColorDepth::Ansi256
+        );
+        assert_eq!(
+            ColorDepth::from_env(None, Some("xterm")),
+            ColorDepth::Ansi16
+        );
+        assert_eq!(ColorDepth::from_env(None, None), ColorDepth::Ansi16);
+    }
+
+    #[test]
+    fn test_convert() {
+        let orange = Color::Rgb {
+            r: 255,
+            g: 135,
+            b: 0,
+        };
+        assert_eq!(ColorDepth::TrueColor.convert(orange), orange);
+        assert_eq!(ColorDepth::Ansi256.convert(orange), Color::AnsiValue(208));
+        assert_eq!(ColorDepth::Ansi16.convert(orange), Color::DarkYellow);
+        let gray = Color::Rgb {
+            r: 128,
+            g: 128,
+            b: 128,
+        };
+        assert_eq!(ColorDepth::Ansi256.convert(gray), Color::AnsiValue(244));
+        assert_eq!(ColorDepth::Ansi16.convert(gray), Color::DarkGrey);
+        assert_eq!(ColorDepth::Ansi16.convert(Color::AnsiValue(9)), Color::Red);
+        assert_eq!(
+            ColorDepth::Ansi16.convert(Color::AnsiValue(21)),
+            Color::DarkBlue
+        );
+        assert_eq!(ColorDepth::Ansi16.convert(Color::Cyan), Color::Cyan);
+    }
+
+    #[test]
+    fn test_parse() {
+        let theme = Theme::parse(
+            "test",
+            "# comment\n\nNormal fg=#d0d0d0 bg=#1c1c1c\nComment fg=244 italic\nvisual bg=darkblue\n",
+        )
+        .unwrap();
+        assert_eq!(
+            theme.style(HighlightGroup::Comment),
+            Style {
+                fg: Some(Color::AnsiValue(244)),
+                bg: Some(Color::Rgb {
+                    r: 0x1c,
+                    g: 0x1c,
+                    b: 0x1c
+                }),
+                attributes: Attribute::Italic.into(),
+            }
+        );
+        assert_eq!(
+            theme.style(HighlightGroup::Visual).bg,
+            Some(Color::DarkBlue)
+        );
+        // the groups not in the file are the default ones
+        assert_eq!(theme.style(HighlightGroup::Search).bg, Some(Color::Yellow));
+        let theme = theme.with_depth(ColorDepth::Ansi256);
+        assert_eq!(
+            theme.style(HighlightGroup::Normal).fg,
+            Some(Color::AnsiValue(252))
+        );
+        assert_eq!(
+            Theme::parse("test", "Foo fg=red"),
+            Err("line 1: unknown group Foo".to_string())
+        );
+        assert_eq!(
+            Theme::parse("test", "Normal fg=#12"),
+            Err("line 1: invalid color #12".to_string())
+        );
+        assert_eq!(
+            Theme::parse("test", "Normal blink"),
+            Err("line 1: unknown attribute blink".to_string())
+        );
+        // the theme in the repository
+        assert!(Theme::parse("dark", include_str!("../../colors/dark.theme")).is_ok());
+    }
+}
@@ -1,10 +1,22 @@
+use super::annotation::{Annotation, AnnotationKind};
+use super::bracket;
 use super::buffer::Buffer;
-use super::cursor::Cursor;
+use super::cursor::{CharSearch, Cursor};
+use super::gutter::Gutter;
+use super::indent::{
+    compute_indents, fill_whitespace, shift_line, with_indent, Indenter, CLOSE_BRACKETS,
+};
 use super::line::Line;
+use super::options::Options;
 use super::position::Position;
+use super::screen::Screen;
+use super::search::{find_match, find_matches_in_line, SearchDirection, SearchMatch};
+use super::selection::Selection;
 use super::size::Size;
-use super::terminal::Terminal;
 use super::text_fragment::TextFragment;
+use super::text_object;
+use super::wrap::{wrap_line, ScreenRow};
+use regex::Regex;
 use std::{cmp::min, io::Error};
 
 #[derive(Copy, Clone, PartialEq)]
@@ -18,6 +30,70 @@ pub enum MoveCode {
     FirstChar,
     LastChar,
     FirstNonBlank,
+    // a line index, `G` and `gg` with a count
+    Line(usize),
+    // word motions `w`, `e`, `b` and `ge`, the flag is set for WORD motions like `W`
+    NextWordStart(bool),
+    NextWordEnd(bool),
+    PrevWordStart(bool),
+    PrevWordEnd(bool),
+    // the end of the word under the cursor, `cw` changes up to here
+    CurrentWordEnd(bool),
+    // `f`, `F`, `t` and `T` in the line
+    FindChar(CharSearch),
+    // `%`, the bracket paired with the one under the cursor or the next one in the line
+    MatchingBracket,
+    // `}` and `{`, paragraphs are separated by blank lines
+    NextParagraph,
+    PrevParagraph,
+    // `)` and `(`
+    NextSentence,
+    PrevSentence,
+    // `` `a `` goes to the position of the mark, `'a` to the first non-blank of its line
+    Mark(char),
+    MarkLine(char),
+    // `gj` and `gk`, the next or the previous screen row of wrapped lines
+    DisplayDown,
+    DisplayUp,
+}
+
+impl MoveCode {
+    // whether an operator with this motion works on whole lines
+    pub fn is_linewise(self) -> bool {
+        matches!(
+            self,
+            MoveCode::Up
+                | MoveCode::Down
+                | MoveCode::FirstLine
+                | MoveCode::LastLine
+                | MoveCode::Line(_)
+                | MoveCode::MarkLine(_)
+        )
+    }
+    // whether the position before the motion is remembered in the jumplist
+    pub fn is_jump(self) -> bool {
+        matches!(
+            self,
+            MoveCode::FirstLine
+                | MoveCode::LastLine
+                | MoveCode::Line(_)
+                | MoveCode::MatchingBracket
+                | MoveCode::NextParagraph
+                | MoveCode::PrevParagraph
+                | MoveCode::NextSentence
+                | MoveCode::PrevSentence
+                | MoveCode::Mark(_)
+                | MoveCode::MarkLine(_)
+        )
+    }
+    // whether an operator with this motion includes the character at the target
+    pub fn is_inclusive(self) -> bool {
+        matches!(
+            self,
+            MoveCode::NextWordEnd(_) | MoveCode::PrevWordEnd(_) | MoveCode::CurrentWordEnd(_)
+        ) || matches!(self, MoveCode::FindChar(search) if search.forward)
+            || self == MoveCode::MatchingBracket
+    }
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -26,6 +102,10 @@ pub enum ScrollCode {
     Right(usize),
     Up(usize),
     Down(usize),
+    // `zt`, `zz` and `zb` put the cursor line at the top, middle or bottom of the screen
+    CursorTop,
+    CursorMiddle,
+    CursorBottom,
 }
 
 pub struct View {
@@ -36,6 +116,22 @@ pub struct View {
     size: Size,
     // I don't think View should have buffer as a member, but put it here for now
     pub buffer: Buffer,
+    // matches of this pattern are highlighted
+    highlight_pattern: Option<Regex>,
+    // the visual selection, which ends at the cursor
+    pub selection: Option<Selection>,
+    // the bracket paired with the one under the cursor, which is highlighted
+    matching_bracket: Option<Position>,
+    // the cursor and the buffer revision it was found for
+    matching_bracket_key: Option<(Position, usize)>,
+    pub options: Options,
+    // the rows of the top line above the screen when the lines are wrapped
+    skip_rows: usize,
+    // the gutter of the last render, redrawn when the numbers change
+    rendered_gutter: Option<Gutter>,
+    // the line given only an indent when it was opened, and the buffer revision after it
+    // the indent is removed if nothing is typed on the line
+    auto_indent: Option<(usize, usize)>,
 }
 
 impl View {
@@ -45,6 +141,14 @@ impl View {
             offset: Position::default(),
             size,
             buffer,
+            highlight_pattern: None,
+            selection: None,
+            matching_bracket: None,
+            matching_bracket_key: None,
+            options: Options::default(),
+            skip_rows: 0,
+            rendered_gutter: None,
+            auto_indent: None,
         }
     }
     pub fn has_path(&self) -> bool {
@@ -61,7 +165,146 @@ impl View {
         self.ensure_redraw();
     }
     pub fn caret_screen_position(&self) -> Position {
-        self.cursor.get_screen_position(&self.offset)
+        let gutter_width = self.gutter().width();
+        if !self.options.wrap {
+            let position = self.cursor.get_screen_position(&self.offset);
+            return Position {
+                line_idx: position.line_idx,
+                col_idx: position.col_idx.saturating_add(gutter_width),
+            };
+        }
+        let Position { line_idx, col_idx } = self.cursor.position();
+        let rows_above: usize = (self.offset.line_idx..line_idx)
+            .map(|idx| self.line_rows(idx).len())
+            .sum();
+        let rows = self.line_rows(line_idx);
+        let row_idx = Self::row_in_line(&rows, col_idx);
+        let screen_col = rows.get(row_idx).map_or(0, |row| row.screen_col(col_idx));
+        Position {
+            line_idx: rows_above
+                .saturating_add(row_idx)
+                .saturating_sub(self.skip_rows),
+            col_idx: screen_col
+                .min(self.text_width().saturating_sub(1))
+                .saturating_add(gutter_width),
+        }
+    }
+    fn gutter(&self) -> Gutter {
+        Gutter::new(
+            &self.options,
+            self.buffer.get_lines_count(),
+            self.cursor.line_idx(),
+        )
+    }
+    // the width of the screen without the gutter
+    fn text_width(&self) -> usize {
+        self.size.width.saturating_sub(self.gutter().width())
+    }
+    // `:set`, returns the values asked with `name?`
+    pub fn set_options(&mut self, args: &str) -> Result<Option<String>, String> {
+        let shown = self.options.set(args)?;
+        self.set_tab_width(self.options.tabstop);
+        if self.options.wrap {
+            self.offset.col_idx = 0;
+        } else {
+            self.skip_rows = 0;
+        }
+        self.scroll_into_view();
+        self.ensure_redraw();
+        Ok(shown)
+    }
+    // the cursor stays on the same character while the columns move
+    fn set_tab_width(&mut self, tab_width: usize) {
+        if tab_width == self.buffer.tab_width() {
+            return;
+        }
+        let Position { line_idx, col_idx } = self.cursor.position();
+        let byte_idx = self
+            .get_line(line_idx)
+            .map_or(0, |line| line.col_idx_to_byte_idx(col_idx));
+        self.buffer.set_tab_width(tab_width);
+        let col_idx = self
+            .get_line(line_idx)
+            .map_or(0, |line| line.byte_idx_to_col_idx(byte_idx));
+        self.set_cursor_position(Position { line_idx, col_idx });
+    }
+    // the rows of a line on the screen, a line is one row unless it is wrapped
+    fn line_rows(&self, line_idx: usize) -> Vec<ScreenRow> {
+        match self.get_line(line_idx) {
+            Some(line) if self.options.wrap => {
+                wrap_line(line, line_idx, self.text_width(), &self.options)
+            }
+            line => vec![ScreenRow {
+                line_idx,
+                cols: 0..line.map_or(0, Line::col_width),
+                prefix: String::new(),
+            }],
+        }
+    }
+    // the row which has the column, the end of the line is in the last row
+    fn row_in_line(rows: &[ScreenRow], col_idx: usize) -> usize {
+        rows.iter()
+            .rposition(|row| row.cols.start <= col_idx)
+            .unwrap_or(0)
+    }
+    // the rows from the top of the screen, the rows below the last line have no text
+    fn screen_rows(&self) -> Vec<ScreenRow> {
+        let width = self.text_width();
+        let height = self.size.height;
+        let top = self.offset.line_idx;
+        if !self.options.wrap {
+            let left = self.offset.col_idx;
+            return (top..top.saturating_add(height))
+                .map(|line_idx| ScreenRow {
+                    line_idx,
+                    cols: left..min(
+                        left.saturating_add(width),
+                        self.buffer.get_line_col_width(line_idx),
+                    ),
+                    prefix: String::new(),
+                })
+                .collect();
+        }
+        let mut rows = Vec::with_capacity(height);
+        let mut skip = self.skip_rows;
+        for line_idx in top.. {
+            if rows.len() >= height {
+                break;
+            }
+            rows.extend(self.line_rows(line_idx).into_iter().skip(skip));
+            skip = 0;
+        }
+        rows.truncate(height);
+        rows
+    }
+    // the number of rows from the top of the screen to the end of the buffer, `limit` at most
+    fn rows_below_top(&self, limit: usize) -> usize {
+        let mut count = 0usize;
+        let mut skip = self.skip_rows;
+        for line_idx in self.offset.line_idx..self.buffer.get_lines_count() {
+            if count >= limit {
+                break;
+            }
+            count = count.saturating_add(self.line_rows(line_idx).len().saturating_sub(skip));
+            skip = 0;
+        }
+        count.min(limit)
+    }
+    // scroll so that the row of the cursor is at the row of the screen
+    fn place_cursor_row(&mut self, screen_row: usize) {
+        let Position { line_idx, col_idx } = self.cursor.position();
+        let row_idx = Self::row_in_line(&self.line_rows(line_idx), col_idx);
+        let mut top = line_idx;
+        let mut skip = row_idx.saturating_sub(screen_row);
+        let mut above = screen_row.saturating_sub(row_idx);
+        while above > 0 && top > 0 {
+            top = top.saturating_sub(1);
+            let rows_count = self.line_rows(top).len();
+            skip = rows_count.saturating_sub(above);
+            above = above.saturating_sub(rows_count);
+        }
+        self.offset.line_idx = top;
+        self.skip_rows = skip;
     }
     // pub fn get_current_line_content(&self) -> String {
     //     self.get_line(self.cursor.line_idx())
@@ -79,9 +322,20 @@ impl View {
     // TODO: support string
     pub fn insert_char(&mut self, c: char) {
         if c == '\n' {
+            let line_idx = self.cursor.line_idx();
+            let unused_indent = self.take_unused_indent();
             if self.buffer.insert_newline(self.cursor.position()) {
                 self.ensure_redraw();
                 self.move_position(MoveCode::Right);
+                let new_line_idx = line_idx.saturating_add(1);
+                let indented = self.indent_new_line(new_line_idx, line_idx);
+                // the new line takes the indent, and the line left behind is cleared
+                if unused_indent == Some(line_idx) {
+                    self.buffer.set_line("", line_idx);
+                }
+                if indented {
+                    self.auto_indent = Some((new_line_idx, self.buffer.revision()));
+                }
             }
             return;
         }
@@ -92,8 +346,12 @@ impl View {
     }
     pub fn insert_char_without_move(&mut self, c: char) {
         if c == '\n' {
+            let line_idx = self.cursor.line_idx();
             if self.buffer.insert_newline(self.cursor.position()) {
                 self.ensure_redraw();
+                if self.indent_new_line(line_idx, line_idx.saturating_add(1)) {
+                    self.auto_indent = Some((line_idx, self.buffer.revision()));
+                }
             }
             return;
         }
@@ -101,47 +359,298 @@ impl View {
             self.ensure_redraw();
         }
     }
+    // the indent of the line opened from another with `autoindent`, or of the file type with `smartindent`
+    // the cursor goes to the end of the indent, returns true if the line has only the indent
+    fn indent_new_line(&mut self, line_idx: usize, from_line_idx: usize) -> bool {
+        let indenter = Indenter::for_file_type(self.buffer.file_info.get_file_type());
+        let width = if self.options.smartindent && indenter == Indenter::Brackets {
+            compute_indents(
+                &self.buffer,
+                line_idx..line_idx.saturating_add(1),
+                indenter,
+                &self.options,
+            )
+            .first()
+            .copied()
+            .flatten()
+        } else if self.options.autoindent || self.options.smartindent {
+            self.get_line(from_line_idx).map(Line::indent_width)
+        } else {
+            None
+        };
+        let Some((width, line)) = width.zip(self.get_line(line_idx)) else {
+            return false;
+        };
+        let content = with_indent(line, width, &self.options);
+        if content != line.content() {
+            self.buffer.set_line(&content, line_idx);
+        }
+        self.set_cursor_position(Position {
+            line_idx,
+            col_idx: width,
+        });
+        width > 0 && content.trim().is_empty()
+    }
+    // the line with the indent of `indent_new_line`, if nothing has been edited since
+    fn take_unused_indent(&mut self) -> Option<usize> {
+        let (line_idx, revision) = self.auto_indent.take()?;
+        (revision == self.buffer.revision()).then_some(line_idx)
+    }
+    // leaving the insert mode removes the indent nothing is typed after
+    pub fn remove_unused_indent(&mut self) {
+        let Some(line_idx) = self.take_unused_indent() else {
+            return;
+        };
+        self.buffer.set_line("", line_idx);
+        if self.cursor.line_idx() == line_idx {
+            self.set_cursor_position(Position {
+                line_idx,
+                col_idx: 0,
+            });
+        }
+    }
+    // a closing bracket typed first on the line goes back to the indent of the line opening it
+    pub fn indent_closing_bracket(&mut self, c: char) {
+        let indenter = Indenter::for_file_type(self.buffer.file_info.get_file_type());
+        if !self.options.smartindent
+            || indenter != Indenter::Brackets
+            || !CLOSE_BRACKETS.contains(&c)
+        {
+            return;
+        }
+        let Position { line_idx, col_idx } = self.cursor.position();
+        let Some(line) = self.get_line(line_idx) else {
+            return;
+        };
+        if line.get_content_by_col_range(0..col_idx).trim_start() != c.to_string() {
+            return;
+        }
+        let indents = compute_indents(
+            &self.buffer,
+            line_idx..line_idx.saturating_add(1),
+            indenter,
+            &self.options,
+        );
+        let Some(width) = indents.first().copied().flatten() else {
+            return;
+        };
+        let content = with_indent(line, width, &self.options);
+        if content != line.content() {
+            self.buffer.set_line(&content, line_idx);
+            self.ensure_redraw();
+        }
+        self.set_cursor_position(Position {
+            line_idx,
+            col_idx: width.saturating_add(1),
+        });
+    }
     pub fn remove_char(&mut self) {
         if self.buffer.remove_char(self.cursor.position()) {
             self.ensure_redraw();
         }
     }
+    // a tab, or whitespace up to the next stop of `softtabstop` with `expandtab` or `softtabstop`
+    pub fn insert_tab(&mut self) {
+        if !self.options.expandtab && self.options.softtabstop == 0 {
+            self.insert_char('\t');
+            return;
+        }
+        let col_idx = self.cursor.col_idx();
+        let stop = self.options.soft_tab_width();
+        // modulo operation is necessary in this case
+        #[allow(clippy::arithmetic_side_effects)]
+        let target = col_idx.saturating_add(stop - col_idx % stop);
+        // the spaces before the cursor may become a tab
+        let start = if self.options.expandtab {
+            col_idx
+        } else {
+            self.whitespace_start()
+        };
+        let text = fill_whitespace(start, target, &self.options);
+        self.replace_before_cursor(start, &text);
+    }
+    // <BS> after whitespace removes it back to the previous stop of `softtabstop`
+    // returns false without `softtabstop` or whitespace
+    pub fn remove_soft_tab(&mut self) -> bool {
+        let col_idx = self.cursor.col_idx();
+        let start = self.whitespace_start();
+        if self.options.softtabstop == 0 || start == col_idx {
+            return false;
+        }
+        let stop = self.options.softtabstop;
+        #[allow(clippy::arithmetic_side_effects, clippy::integer_division)]
+        let target = (col_idx.saturating_sub(1) / stop).saturating_mul(stop);
+        let text = fill_whitespace(start, target.max(start), &self.options);
+        self.replace_before_cursor(start, &text);
+        true
+    }
+    // <C-T> and <C-D>, the cursor stays on the same character
+    pub fn shift_cursor_line(&mut self, right: bool) {
+        let Position { line_idx, col_idx } = self.cursor.position();
+        let Some(line) = self.get_line(line_idx) else {
+            return;
+        };
+        let indent_width = line.indent_width();
+        let shifted = shift_line(line, 1, right, &self.options);
+        let new_indent_width = self.buffer.new_line(&shifted).indent_width();
+        self.buffer.set_line(&shifted, line_idx);
+        self.set_cursor_position(Position {
+            line_idx,
+            col_idx: col_idx
+                .saturating_sub(indent_width)
+                .saturating_add(new_indent_width),
+        });
+        self.ensure_redraw();
+    }
+    // the column where the spaces and tabs just before the cursor start
+    fn whitespace_start(&self) -> usize {
+        let Position { line_idx, col_idx } = self.cursor.position();
+        let Some(line) = self.get_line(line_idx) else {
+            return col_idx;
+        };
+        let fragments = line.fragments();
+        let before = fragments
+            .iter()
+            .take_while(|fragment| fragment.left_col_width() < col_idx)
+            .count();
+        fragments[..before]
+            .iter()
+            .rev()
+            .take_while(|fragment| matches!(fragment.grapheme(), " " | "\t"))
+            .last()
+            .map_or(col_idx, TextFragment::left_col_width)
+    }
+    // replace the text from the column to the cursor, the cursor goes after the new text
+    fn replace_before_cursor(&mut self, col_idx: usize, text: &str) {
+        let Position {
+            line_idx,
+            col_idx: cursor_col_idx,
+        } = self.cursor.position();
+        let (head, tail) = self.get_line(line_idx).map_or_else(
+            || (String::new(), String::new()),
+            |line| {
+                (
+                    line.get_content_by_col_range(0..col_idx).to_string(),
+                    line.get_content_by_col_range(cursor_col_idx..line.col_width())
+                        .to_string(),
+                )
+            },
+        );
+        let head = format!("{head}{text}");
+        let col_idx = self.buffer.new_line(&head).col_width();
+        self.buffer.set_line(&format!("{head}{tail}"), line_idx);
+        self.set_cursor_position(Position { line_idx, col_idx });
+        self.ensure_redraw();
+    }
+    // returns false if there is nothing to undo
+    pub fn undo(&mut self) -> bool {
+        let Some(at) = self.buffer.undo() else {
+            return false;
+        };
+        self.set_cursor_position(at);
+        true
+    }
+    // returns false if there is nothing to redo
+    pub fn redo(&mut self) -> bool {
+        let Some(at) = self.buffer.redo() else {
+            return false;
+        };
+        self.set_cursor_position(at);
+        true
+    }
 
     pub fn height(&self) -> usize {
         self.size.height
     }
+    // the first and the last line shown on the screen, for `H`, `M` and `L`
+    pub fn visible_lines(&self) -> (usize, usize) {
+        let top = self.offset.line_idx;
+        let lines_count = self.buffer.get_lines_count();
+        let bottom = self
+            .screen_rows()
+            .iter()
+            .map(|row| row.line_idx)
+            .filter(|line_idx| *line_idx < lines_count)
+            .max()
+            .unwrap_or(top)
+            .max(top);
+        (top, bottom)
+    }
     pub fn scroll_screen(&mut self, code: ScrollCode) {
-        let saved_offset = self.offset;
+        let saved = (self.offset, self.skip_rows);
         match code {
-            ScrollCode::Left(_step) => self.scroll_left(),
-            ScrollCode::Right(_step) => self.scroll_right(),
+            // wrapped lines do not scroll horizontally
+            ScrollCode::Left(_) | ScrollCode::Right(_) if self.options.wrap => (),
+            ScrollCode::Left(step) => self.scroll_left(step),
+            ScrollCode::Right(step) => self.scroll_right(step),
             ScrollCode::Up(step) => self.scroll_up(step),
             ScrollCode::Down(step) => self.scroll_down(step),
-            // ScrollCode::PageUp => self.scroll_up(self.size.height),
-            // ScrollCode::PageDown => self.scroll_down(self.size.height),
-        };
-        self.buffer.needs_redraw = self.offset != saved_offset;
+            ScrollCode::CursorTop => self.place_cursor_row(0),
+            ScrollCode::CursorMiddle => {
+                #[allow(clippy::integer_division)]
+                let half = self.size.height.saturating_sub(1) / 2;
+                self.place_cursor_row(half);
+            }
+            ScrollCode::CursorBottom => {
+                self.place_cursor_row(self.size.height.saturating_sub(1));
+            }
+        }
+        if (self.offset, self.skip_rows) != saved {
+            self.ensure_redraw();
+        }
     }
-    fn scroll_left(&mut self) {
-        self.cursor.move_prev_grapheme_nowrap();
-        self.offset.col_idx = self.offset.col_idx.saturating_sub(1);
+    fn scroll_left(&mut self, step: usize) {
+        (0..step).for_each(|_| self.cursor.move_prev_grapheme_nowrap());
+        self.offset.col_idx = self.offset.col_idx.saturating_sub(step);
     }
-    fn scroll_right(&mut self) {
-        self.cursor.move_next_grapheme_nowrap(&self.buffer);
+    fn scroll_right(&mut self, step: usize) {
+        (0..step).for_each(|_| self.cursor.move_next_grapheme_nowrap(&self.buffer));
         self.offset.col_idx = min(
-            self.offset.col_idx.saturating_add(1),
+            self.offset.col_idx.saturating_add(step),
             self.buffer
                 .get_line_col_width(self.cursor.line_idx())
                 .saturating_add(1)
-                .saturating_sub(self.size.width),
+                .saturating_sub(self.text_width()),
         );
     }
     fn scroll_up(&mut self, step: usize) {
+        if self.options.wrap {
+            // the screen and the cursor move by rows
+            for _ in 0..step {
+                if self.skip_rows > 0 {
+                    self.skip_rows = self.skip_rows.saturating_sub(1);
+                } else if self.offset.line_idx > 0 {
+                    self.offset.line_idx = self.offset.line_idx.saturating_sub(1);
+                    self.skip_rows = self.line_rows(self.offset.line_idx).len().saturating_sub(1);
+                } else {
+                    break;
+                }
+            }
+            self.move_position_by(MoveCode::DisplayUp, step);
+            return;
+        }
         let off_l = self.offset.line_idx;
         self.cursor.move_prev_line(step, &self.buffer);
         self.offset.line_idx = off_l.saturating_sub(step);
     }
     fn scroll_down(&mut self, step: usize) {
+        if self.options.wrap {
+            // the last row of the buffer stays above the bottom of the screen
+            let height = self.size.height;
+            for _ in 0..step {
+                if self.rows_below_top(height) < height {
+                    break;
+                }
+                if self.skip_rows.saturating_add(1) < self.line_rows(self.offset.line_idx).len() {
+                    self.skip_rows = self.skip_rows.saturating_add(1);
+                } else {
+                    self.offset.line_idx = self.offset.line_idx.saturating_add(1);
+                    self.skip_rows = 0;
+                }
+            }
+            self.move_position_by(MoveCode::DisplayDown, step);
+            return;
+        }
         let off_l = self.offset.line_idx;
         self.cursor.move_next_line(step, &self.buffer);
         self.offset.line_idx = min(
@@ -153,23 +662,153 @@ impl View {
         );
     }
     pub fn move_position(&mut self, code: MoveCode) {
+        self.move_position_by(code, 1);
+    }
+    pub fn move_position_by(&mut self, code: MoveCode, count: usize) {
+        let mut cursor = self.cursor;
+        self.move_cursor(&mut cursor, code, count);
+        if matches!(code, MoveCode::NextWordStart(_)) {
+            // an operator still reaches the end of the buffer with `dw`
+            cursor.move_off_buffer_end(&self.buffer);
+        }
+        self.cursor = cursor;
+        self.scroll_into_view();
+    }
+    // the position the cursor would reach, without moving it
+    pub fn get_move_target(&self, code: MoveCode) -> Position {
+        self.get_move_target_by(code, 1)
+    }
+    pub fn get_move_target_by(&self, code: MoveCode, count: usize) -> Position {
+        let mut cursor = self.cursor;
+        self.move_cursor(&mut cursor, code, count);
+        cursor.position()
+    }
+    fn move_cursor(&self, cursor: &mut Cursor, code: MoveCode, count: usize) {
+        let buffer = &self.buffer;
         match code {
-            MoveCode::Left => self.cursor.move_prev_grapheme(&self.buffer),
-            MoveCode::Right => self.cursor.move_next_grapheme(&self.buffer),
-            MoveCode::Up => self.cursor.move_prev_line(1, &self.buffer),
-            MoveCode::Down => self.cursor.move_next_line(1, &self.buffer),
-            MoveCode::FirstChar => self.cursor.move_first_char(&self.buffer),
-            MoveCode::LastChar => self.cursor.move_last_char(&self.buffer),
-            MoveCode::FirstLine => self.cursor.move_first_line(&self.buffer),
-            MoveCode::LastLine => self.cursor.move_last_line(&self.buffer),
-            MoveCode::FirstNonBlank => self.cursor.move_first_non_blank(&self.buffer),
+            MoveCode::Left => (0..count).for_each(|_| cursor.move_prev_grapheme(buffer)),
+            MoveCode::Right => (0..count).for_each(|_| cursor.move_next_grapheme(buffer)),
+            MoveCode::Up => cursor.move_prev_line(count, buffer),
+            MoveCode::Down => cursor.move_next_line(count, buffer),
+            MoveCode::FirstChar => cursor.move_first_char(buffer),
+            MoveCode::LastChar => {
+                // `3$` moves to the end of the line 2 lines below
+                cursor.move_next_line(count.saturating_sub(1), buffer);
+                cursor.move_last_char(buffer);
+            }
+            MoveCode::NextWordStart(bigword) => {
+                (0..count).for_each(|_| cursor.move_next_word_start(bigword, buffer));
+            }
+            MoveCode::NextWordEnd(bigword) => {
+                (0..count).for_each(|_| cursor.move_next_word_end(bigword, buffer));
+            }
+            MoveCode::PrevWordStart(bigword) => {
+                (0..count).for_each(|_| cursor.move_prev_word_start(bigword, buffer));
+            }
+            MoveCode::PrevWordEnd(bigword) => {
+                (0..count).for_each(|_| cursor.move_prev_word_end(bigword, buffer));
+            }
+            MoveCode::CurrentWordEnd(bigword) => {
+                cursor.move_current_word_end(bigword, buffer);
+                (1..count).for_each(|_| cursor.move_next_word_end(bigword, buffer));
+            }
+            MoveCode::FindChar(search) => {
+                cursor.move_to_char(search, count, buffer);
+            }
+            MoveCode::MatchingBracket => {
+                if let Some(position) = bracket::find_pair_from(buffer, cursor.position()) {
+                    cursor.set_position(position, buffer);
+                }
+            }
+            MoveCode::NextParagraph => {
+                (0..count).for_each(|_| cursor.move_next_paragraph(buffer));
+            }
+            MoveCode::PrevParagraph => {
+                (0..count).for_each(|_| cursor.move_prev_paragraph(buffer));
+            }
+            MoveCode::NextSentence | MoveCode::PrevSentence => {
+                let forward = code == MoveCode::NextSentence;
+                let position =
+                    text_object::sentence_motion(cursor.position(), buffer, forward, count);
+                cursor.set_position(position, buffer);
+            }
+            MoveCode::Mark(name) | MoveCode::MarkLine(name) => {
+                if let Some(position) = buffer.get_mark(name) {
+                    cursor.set_position(position, buffer);
+                }
+                if matches!(code, MoveCode::MarkLine(_)) {
+                    cursor.move_first_non_blank(buffer);
+                }
+            }
+            MoveCode::DisplayDown | MoveCode::DisplayUp => {
+                let down = code == MoveCode::DisplayDown;
+                if self.options.wrap {
+                    (0..count).for_each(|_| self.move_cursor_row(cursor, down));
+                } else if down {
+                    cursor.move_next_line(count, buffer);
+                } else {
+                    cursor.move_prev_line(count, buffer);
+                }
+            }
+            MoveCode::Line(line_idx) => {
+                let current = cursor.position().line_idx;
+                if line_idx < current {
+                    cursor.move_prev_line(current.saturating_sub(line_idx), buffer);
+                } else {
+                    cursor.move_next_line(line_idx.saturating_sub(current), buffer);
+                }
+            }
+            MoveCode::FirstLine => cursor.move_first_line(buffer),
+            MoveCode::LastLine => cursor.move_last_line(buffer),
+            MoveCode::FirstNonBlank => cursor.move_first_non_blank(buffer),
+        }
+    }
+    // the next or the previous screen row, keeping the screen column
+    fn move_cursor_row(&self, cursor: &mut Cursor, down: bool) {
+        let Position { line_idx, col_idx } = cursor.position();
+        let rows = self.line_rows(line_idx);
+        let row_idx = Self::row_in_line(&rows, col_idx);
+        let screen_col = rows.get(row_idx).map_or(0, |row| row.screen_col(col_idx));
+        let (rows, row_idx) = if down {
+            if row_idx.saturating_add(1) < rows.len() {
+                (rows, row_idx.saturating_add(1))
+            } else if line_idx.saturating_add(1) < self.buffer.get_lines_count() {
+                (self.line_rows(line_idx.saturating_add(1)), 0)
+            } else {
+                return;
+            }
+        } else if row_idx > 0 {
+            (rows, row_idx.saturating_sub(1))
+        } else if line_idx > 0 {
+            let rows = self.line_rows(line_idx.saturating_sub(1));
+            let last = rows.len().saturating_sub(1);
+            (rows, last)
+        } else {
+            return;
+        };
+        let Some(row) = rows.get(row_idx) else {
+            return;
         };
+        let last_row = row_idx.saturating_add(1) == rows.len();
+        let position = Position {
+            line_idx: row.line_idx,
+            col_idx: row.col_at(screen_col, last_row),
+        };
+        cursor.set_position(position, &self.buffer);
+    }
+    pub fn set_cursor_position(&mut self, position: Position) {
+        self.cursor.set_position(position, &self.buffer);
         self.scroll_into_view();
     }
 
     fn scroll_into_view(&mut self) {
+        if self.options.wrap {
+            self.scroll_into_view_wrapped();
+            return;
+        }
         let Position { line_idx, col_idx } = self.cursor.position();
-        let Size { width, height } = self.size;
+        let width = self.text_width();
+        let height = self.size.height;
         // horizontal
         if col_idx < self.offset.col_idx {
             self.offset.col_idx = col_idx;
@@ -187,15 +826,138 @@ impl View {
             self.buffer.ensure_redraw();
         }
     }
+    // the row of the cursor is kept on the screen
+    fn scroll_into_view_wrapped(&mut self) {
+        let saved = (self.offset, self.skip_rows);
+        self.offset.col_idx = 0;
+        let Position { line_idx, col_idx } = self.cursor.position();
+        let row_idx = Self::row_in_line(&self.line_rows(line_idx), col_idx);
+        let top = self.offset.line_idx;
+        let height = self.size.height;
+        if line_idx < top || (line_idx == top && row_idx < self.skip_rows) {
+            self.place_cursor_row(0);
+        } else if line_idx.saturating_sub(top) >= height
+            || self.caret_screen_position().line_idx >= height
+        {
+            self.place_cursor_row(height.saturating_sub(1));
+        }
+        if (self.offset, self.skip_rows) != saved {
+            self.ensure_redraw();
+        }
+    }
     pub fn ensure_redraw(&mut self) {
         self.buffer.needs_redraw = true;
     }
-    pub fn render(&mut self) -> Result<(), Error> {
-        self.buffer
-            .render(self.size, self.offset, Terminal::print_row)
+    pub fn render(&mut self, screen: &mut Screen) {
+        if self.size.width == 0 {
+            return;
+        }
+        self.update_matching_bracket();
+        self.update_gutter();
+        let (_, bottom) = self.visible_lines();
+        self.buffer.highlight(bottom.saturating_add(1));
+        let annotations = self.get_annotations();
+        let rows = self.screen_rows();
+        let gutter = self.gutter();
+        let wrap = self.options.wrap;
+        self.buffer.render(
+            &rows,
+            &annotations,
+            self.options.list.then_some(&self.options.listchars),
+            |row| gutter.spans(row, wrap && row.cols.start > 0),
+            |row_idx, spans| screen.put_spans(row_idx, spans),
+        );
+    }
+    // redraw when the numbers or the width of the gutter change
+    fn update_gutter(&mut self) {
+        let gutter = self.gutter();
+        let width_changed = self
+            .rendered_gutter
+            .as_ref()
+            .is_some_and(|rendered| rendered.width() != gutter.width());
+        // the cursor line does not matter without the gutter
+        if self.rendered_gutter.as_ref() != Some(&gutter) && (gutter.width() > 0 || width_changed) {
+            self.ensure_redraw();
+        }
+        self.rendered_gutter = Some(gutter);
+        if width_changed {
+            self.scroll_into_view();
+        }
+    }
+    // redraw when the cursor moves onto or off a bracket
+    // it is found again only after the cursor moves or the buffer is edited
+    fn update_matching_bracket(&mut self) {
+        let key = (self.cursor.position(), self.buffer.revision());
+        if self.matching_bracket_key == Some(key) {
+            return;
+        }
+        self.matching_bracket_key = Some(key);
+        let matching_bracket = bracket::find_pair(&self.buffer, self.cursor.position());
+        if matching_bracket != self.matching_bracket {
+            self.matching_bracket = matching_bracket;
+            self.ensure_redraw();
+        }
+    }
+    // annotations of the visible lines
+    // the syntax comes first, so the other annotations are drawn over it
+    fn get_annotations(&self) -> Vec<Annotation> {
+        let (top, bottom) = self.visible_lines();
+        let mut annotations = self
+            .buffer
+            .syntax_annotations(top..bottom.saturating_add(1));
+        if let Some(regex) = &self.highlight_pattern {
+            let top = self.offset.line_idx;
+            for line_idx in top..top.saturating_add(self.size.height) {
+                let Some(line) = self.get_line(line_idx) else {
+                    break;
+                };
+                annotations.extend(find_matches_in_line(line, regex).into_iter().map(|range| {
+                    Annotation {
+                        kind: AnnotationKind::SearchMatch,
+                        line_idx,
+                        range,
+                    }
+                }));
+            }
+        }
+        if let Some(position) = self.matching_bracket {
+            if let Some(fragment) = self.get_fragment_by_position(position) {
+                annotations.push(Annotation {
+                    kind: AnnotationKind::MatchingBracket,
+                    line_idx: position.line_idx,
+                    range: fragment.left_col_width()
+                        ..fragment.left_col_width().saturating_add(fragment.width()),
+                });
+            }
+        }
+        if let Some(selection) = &self.selection {
+            let top = self.offset.line_idx;
+            annotations.extend(selection.annotations(
+                self.cursor.position(),
+                &self.buffer,
+                top..top.saturating_add(self.size.height),
+            ));
+        }
+        annotations
+    }
+    pub fn set_selection(&mut self, selection: Option<Selection>) {
+        self.selection = selection;
+        self.ensure_redraw();
+    }
+    pub fn set_highlight_pattern(&mut self, regex: Option<Regex>) {
+        self.highlight_pattern = regex;
+        self.ensure_redraw();
     }
-    pub fn search(&self, _query: &str) {
-        // TODO
+    // move the cursor to the nearest match from `from`
+    pub fn search(
+        &mut self,
+        regex: &Regex,
+        from: Position,
+        direction: SearchDirection,
+    ) -> Option<SearchMatch> {
+        let found = find_match(&self.buffer, regex, from, direction)?;
+        self.set_cursor_position(found.position);
+        Some(found)
     }
 }
 
@@ -211,44 +973,54 @@ mod tests {
         view.scroll_screen(ScrollCode::Down(1));
         assert_eq!(view.caret_screen_position(), Position::new(0, 0));
         assert_eq!(view.offset, Position::new(1, 0));
-        assert_eq!(view.buffer.needs_redraw, true);
+        assert!(view.buffer.needs_redraw);
         view.buffer.needs_redraw = false;
         view.scroll_screen(ScrollCode::Down(1));
         assert_eq!(view.caret_screen_position(), Position::new(1, 0));
         assert_eq!(view.offset, Position::new(1, 0));
-        assert_eq!(view.buffer.needs_redraw, false);
+        assert!(!view.buffer.needs_redraw);
         view.buffer.needs_redraw = false;
         view.scroll_screen(ScrollCode::Up(1));
         assert_eq!(view.caret_screen_position(), Position::new(1, 0));
         assert_eq!(view.offset, Position::new(0, 0));
-        assert_eq!(view.buffer.needs_redraw, true);
+        assert!(view.buffer.needs_redraw);
         view.buffer.needs_redraw = false;
         view.scroll_screen(ScrollCode::Up(1));
         assert_eq!(view.caret_screen_position(), Position::new(0, 0));
         assert_eq!(view.offset, Position::new(0, 0));
-        assert_eq!(view.buffer.needs_redraw, false);
+        assert!(!view.buffer.needs_redraw);
         view.buffer.needs_redraw = false;
 
         view.scroll_screen(ScrollCode::Right(1));
         assert_eq!(view.caret_screen_position(), Position::new(0, 0));
         assert_eq!(view.offset, Position::new(0, 1));
-        assert_eq!(view.buffer.needs_redraw, true);
+        assert!(view.buffer.needs_redraw);
         view.buffer.needs_redraw = false;
         view.scroll_screen(ScrollCode::Right(1));
         assert_eq!(view.caret_screen_position(), Position::new(0, 1));
         assert_eq!(view.offset, Position::new(0, 1));
-        assert_eq!(view.buffer.needs_redraw, false);
+        assert!(!view.buffer.needs_redraw);
         view.buffer.needs_redraw = false;
         view.scroll_screen(ScrollCode::Left(1));
         assert_eq!(view.caret_screen_position(), Position::new(0, 1));
         assert_eq!(view.offset, Position::new(0, 0));
-        assert_eq!(view.buffer.needs_redraw, true);
+        assert!(view.buffer.needs_redraw);
         view.buffer.needs_redraw = false;
         view.scroll_screen(ScrollCode::Left(1));
         assert_eq!(view.caret_screen_position(), Position::new(0, 0));
         assert_eq!(view.offset, Position::new(0, 0));
-        assert_eq!(view.buffer.needs_redraw, false);
+        assert!(!view.buffer.needs_redraw);
         view.buffer.needs_redraw = false;
+
+        // a count scrolls by that many columns
+        let buffer = Buffer::from_string("abcdefgh\n");
+        let mut view = View::new(buffer, Size::new(4, 2));
+        view.scroll_screen(ScrollCode::Right(3));
+        assert_eq!(view.offset, Position::new(0, 3));
+        assert_eq!(view.cursor.position(), Position::new(0, 3));
+        view.scroll_screen(ScrollCode::Left(2));
+        assert_eq!(view.offset, Position::new(0, 1));
+        assert_eq!(view.cursor.position(), Position::new(0, 1));
     }
 
     #[test]
@@ -278,4 +1050,116 @@ mod tests {
         view.remove_char();
         assert_eq!(view.buffer.lines[0].content(), "tisis");
     }
+
+    #[test]
+    fn test_matching_bracket() {
+        let buffer = Buffer::from_string("f(「a」)\n");
+        let mut view = View::new(buffer, Size::new(10, 2));
+        view.buffer.needs_redraw = false;
+        view.update_matching_bracket();
+        assert!(view.get_annotations().is_empty());
+        view.set_cursor_position(Position::new(0, 2));
+        view.buffer.needs_redraw = false;
+        view.update_matching_bracket();
+        assert!(view.buffer.needs_redraw);
+        assert_eq!(
+            view.get_annotations(),
+            vec![Annotation {
+                kind: AnnotationKind::MatchingBracket,
+                line_idx: 0,
+                range: 5..7,
+            }]
+        );
+        // an edit finds the pair again at the same cursor
+        view.buffer.set_line("f(「a)", 0);
+        view.update_matching_bracket();
+        assert!(view.get_annotations().is_empty());
+    }
+
+    #[test]
+    fn test_wrap() {
+        let buffer = Buffer::from_string("abcdefgh\nij\n");
+        let mut view = View::new(buffer, Size::new(4, 2));
+        view.set_options("wrap").unwrap();
+        view.set_cursor_position(Position::new(0, 5));
+        assert_eq!(view.caret_screen_position(), Position::new(1, 1));
+        assert_eq!(view.visible_lines(), (0, 0));
+        view.move_position_by(MoveCode::DisplayDown, 1);
+        assert_eq!(view.cursor.position(), Position::new(1, 1));
+        assert_eq!(view.caret_screen_position(), Position::new(1, 1));
+        assert_eq!((view.offset, view.skip_rows), (Position::new(0, 0), 1));
+        view.move_position_by(MoveCode::DisplayUp, 1);
+        assert_eq!(view.cursor.position(), Position::new(0, 5));
+        assert_eq!(view.caret_screen_position(), Position::new(0, 1));
+        view.move_position_by(MoveCode::DisplayUp, 1);
+        assert_eq!(view.cursor.position(), Position::new(0, 1));
+        assert_eq!((view.offset, view.skip_rows), (Position::new(0, 0), 0));
+        // the page scrolls by rows
+        view.scroll_screen(ScrollCode::Down(1));
+        assert_eq!((view.offset, view.skip_rows), (Position::new(0, 0), 1));
+        assert_eq!(view.cursor.position(), Position::new(0, 5));
+        view.scroll_screen(ScrollCode::Down(1));
+        assert_eq!((view.offset, view.skip_rows), (Position::new(1, 0), 0));
+        assert_eq!(view.cursor.position(), Position::new(1, 1));
+        view.scroll_screen(ScrollCode::Down(1));
+        assert_eq!((view.offset, view.skip_rows), (Position::new(1, 0), 0));
+        view.scroll_screen(ScrollCode::Up(1));
+        assert_eq!((view.offset, view.skip_rows), (Position::new(0, 0), 1));
+        assert_eq!(view.cursor.position(), Position::new(0, 5));
+        assert_eq!(view.caret_screen_position(), Position::new(0, 1));
+        // showbreak takes the room of the text
+        view.set_options("sbr=>").unwrap();
+        view.set_cursor_position(Position::new(0, 7));
+        assert_eq!(view.caret_screen_position(), Position::new(1, 1));
+        assert_eq!(view.line_rows(0).len(), 3);
+    }
+
+    #[test]
+    fn test_gutter() {
+        let buffer = Buffer::from_string(&"abcdef\n".repeat(9));
+        let mut view = View::new(buffer, Size::new(6, 3));
+        view.set_options("nu").unwrap();
+        view.update_gutter();
+        view.set_cursor_position(Position::new(0, 5));
+        assert_eq!(view.offset, Position::new(0, 2));
+        assert_eq!(view.caret_screen_position(), Position::new(0, 5));
+        // the gutter grows at the tenth line
+        view.set_cursor_position(Position::new(8, 0));
+        view.insert_char('\n');
+        view.set_cursor_position(Position::new(9, 5));
+        view.buffer.needs_redraw = false;
+        view.update_gutter();
+        assert!(view.buffer.needs_redraw);
+        assert_eq!(view.offset, Position::new(7, 3));
+        assert_eq!(view.caret_screen_position(), Position::new(2, 5));
+        // relative numbers change with the cursor line
+        view.set_options("rnu").unwrap();
+        view.update_gutter();
+        view.buffer.needs_redraw = false;
+        view.set_cursor_position(Position::new(8, 5));
+        view.update_gutter();
+        assert!(view.buffer.needs_redraw);
+        // the wrapped rows are narrower
+        view.set_options("wrap").unwrap();
+        assert_eq!(view.line_rows(0).len(), 2);
+        view.set_cursor_position(Position::new(9, 4));
+        assert_eq!(view.caret_screen_position(), Position::new(2, 4));
+    }
+
+    #[test]
+    fn test_tabstop() {
+        let buffer = Buffer::from_string("\tab\n");
+        let mut view = View::new(buffer, Size::new(20, 2));
+        view.set_cursor_position(Position::new(0, 5));
+        assert_eq!(view.caret_screen_position(), Position::new(0, 5));
+        // the cursor stays on `b`
+        view.set_options("ts=8").unwrap();
+        assert_eq!(view.buffer.lines[0].col_width(), 10);
+        assert_eq!(view.cursor.position(), Position::new(0, 9));
+        // the new lines have the tab width of the buffer
+        view.set_cursor_position(Position::new(0, 10));
+        view.insert_char('\n');
+        view.insert_char('\t');
+        assert_eq!(view.buffer.lines[1].col_width(), 8);
+    }
 }
@@ -0,0 +1,231 @@
+use super::line::Line;
+use super::options::Options;
+use super::text_fragment::TextFragment;
+use std::ops::Range;
+use unicode_width::UnicodeWidthStr;
+
+// a part of a line shown in one row of the screen
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ScreenRow {
+    pub line_idx: usize,
+    // display columns of the line
+    pub cols: Range<usize>,
+    // shown before the text, the indent and `showbreak` of a continued row
+    pub prefix: String,
+}
+
+impl ScreenRow {
+    pub fn prefix_width(&self) -> usize {
+        self.prefix.width()
+    }
+    // the screen column of a column of the line in this row
+    pub fn screen_col(&self, col_idx: usize) -> usize {
+        self.prefix_width()
+            .saturating_add(col_idx.saturating_sub(self.cols.start))
+    }
+    // the column of the line at a screen column, the last column of the row at most
+    // the end of the line is allowed in the last row of the line
+    pub fn col_at(&self, screen_col: usize, last_row: bool) -> usize {
+        let col_idx = self
+            .cols
+            .start
+            .saturating_add(screen_col.saturating_sub(self.prefix_width()));
+        if last_row {
+            col_idx.min(self.cols.end)
+        } else {
+            col_idx.min(self.cols.end.saturating_sub(1))
+        }
+    }
+}
+
+// characters which do not start a row, closing punctuation and small kana
+const NO_START: &str = "、。，．,.:;?!‼⁇⁈⁉・：；？！)]}）］｝〕〉》」』】〙〗〟’”｠»ゝゞヽヾーぁぃぅぇぉっゃゅょゎゕゖァィゥェォッャュョヮヵヶㇰㇱㇲㇳㇴㇵㇶㇷㇸㇹㇺㇻㇼㇽㇾㇿ々〻‐゠–〜～";
+// characters which do not end a row, opening brackets
+const NO_END: &str = "([{（［｛〔〈《「『【〘〖〝‘“｟«";
+
+// split the line into rows of the width
+// a character is never split, it goes to the next row if it does not fit
+pub fn wrap_line(line: &Line, line_idx: usize, width: usize, options: &Options) -> Vec<ScreenRow> {
+    let prefix = continuation_prefix(line, width, options);
+    let fragments = line.fragments();
+    let mut rows = Vec::new();
+    let mut start = 0;
+    let mut room = width;
+    let mut idx = 0;
+    while let Some(fragment) = fragments.get(idx) {
+        let fragment_width = fragment.width();
+        if fragment_width <= room || idx == start {
+            room = room.saturating_sub(fragment_width);
+            idx = idx.saturating_add(1);
+            continue;
+        }
+        let end = if options.kinsoku {
+            kinsoku_break(fragments, start, idx)
+        } else {
+            idx
+        };
+        rows.push(ScreenRow {
+            line_idx,
+            cols: col_of(fragments, start)..col_of(fragments, end),
+            prefix: if rows.is_empty() {
+                String::new()
+            } else {
+                prefix.clone()
+            },
+        });
+        start = end;
+        idx = end;
+        room = width.saturating_sub(prefix.width());
+    }
+    rows.push(ScreenRow {
+        line_idx,
+        cols: col_of(fragments, start)..line.col_width(),
+        prefix: if rows.is_empty() {
+            String::new()
+        } else {
+            prefix
+        },
+    });
+    rows
+}
+
+fn col_of(fragments: &[TextFragment], idx: usize) -> usize {
+    fragments.get(idx).map_or_else(
+        || {
+            fragments.last().map_or(0, |fragment| {
+                fragment.left_col_width().saturating_add(fragment.width())
+            })
+        },
+        TextFragment::left_col_width,
+    )
+}
+
+// move the break before `idx` back so that the next row does not start with closing
+// punctuation and the row does not end with an opening bracket
+// the break stays if the whole row would move
+fn kinsoku_break(fragments: &[TextFragment], start: usize, idx: usize) -> usize {
+    let breakable = |end: usize| {
+        let next = fragments.get(end).map(TextFragment::grapheme);
+        let prev = end
+            .checked_sub(1)
+            .and_then(|prev| fragments.get(prev))
+            .map(TextFragment::grapheme);
+        !next.is_some_and(|grapheme| is_in(NO_START, grapheme))
+            && !prev.is_some_and(|grapheme| is_in(NO_END, grapheme))
+    };
+    (start.saturating_add(1)..=idx)
+        .rev()
+        .find(|end| breakable(*end))
+        .unwrap_or(idx)
+}
+
+fn is_in(chars: &str, grapheme: &str) -> bool {
+    let mut grapheme_chars = grapheme.chars();
+    match (grapheme_chars.next(), grapheme_chars.next()) {
+        (Some(c), None) => chars.contains(c),
+        _ => false,
+    }
+}
+
+// the indent for `breakindent` and `showbreak`
+// they are dropped if nothing of the text fits after them
+fn continuation_prefix(line: &Line, width: usize, options: &Options) -> String {
+    let indent = if options.breakindent {
+        " ".repeat(line.indent_width())
+    } else {
+        String::new()
+    };
+    let prefix = format!("{indent}{}", options.showbreak);
+    if prefix.width() < width {
+        prefix
+    } else if options.showbreak.width() < width {
+        options.showbreak.clone()
+    } else {
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cols(rows: &[ScreenRow]) -> Vec<Range<usize>> {
+        rows.iter().map(|row| row.cols.clone()).collect()
+    }
+
+    #[test]
+    fn test_wrap_line() {
+        let options = Options::default();
+        let line = Line::from("abcdefg");
+        assert_eq!(
+            cols(&wrap_line(&line, 0, 3, &options)),
+            vec![0..3, 3..6, 6..7]
+        );
+        assert_eq!(cols(&wrap_line(&line, 0, 7, &options)), vec![0..7]);
+        assert_eq!(
+            cols(&wrap_line(&Line::from(""), 0, 3, &options)),
+            vec![0..0]
+        );
+        // a full-width character is not split
+        let line = Line::from("a日本語");
+        assert_eq!(cols(&wrap_line(&line, 0, 4, &options)), vec![0..3, 3..7]);
+        // a tab keeps its width in the line
+        let line = Line::from("ab\tc");
+        assert_eq!(cols(&wrap_line(&line, 0, 3, &options)), vec![0..2, 2..5]);
+    }
+
+    #[test]
+    fn test_prefix() {
+        let options = Options {
+            showbreak: ">".to_string(),
+            breakindent: true,
+            ..Options::default()
+        };
+        let rows = wrap_line(&Line::from("  abcdef"), 0, 5, &options);
+        assert_eq!(cols(&rows), vec![0..5, 5..7, 7..8]);
+        assert_eq!(rows[0].prefix, "");
+        assert_eq!(rows[1].prefix, "  >");
+        assert_eq!(rows[1].screen_col(6), 4);
+        assert_eq!(rows[1].col_at(4, false), 6);
+        assert_eq!(rows[2].col_at(9, true), 8);
+        // no room for the indent
+        let rows = wrap_line(&Line::from("    abcdef"), 0, 4, &options);
+        assert_eq!(rows[1].prefix, ">");
+    }
+
+    #[test]
+    fn test_kinsoku() {
+        let mut options = Options::default();
+        let line = Line::from("日本語🇯🇵の「表示」テスト。");
+        // without the rule a row starts with `。`
+        assert_eq!(
+            cols(&wrap_line(&line, 0, 8, &options)),
+            vec![0..8, 8..16, 16..24, 24..26]
+        );
+        options.kinsoku = true;
+        // `」` and `。` are taken to the next row with the character before them
+        assert_eq!(
+            cols(&wrap_line(&line, 0, 8, &options)),
+            vec![0..8, 8..14, 14..22, 22..26]
+        );
+        // `「` goes to the next row
+        assert_eq!(
+            cols(&wrap_line(&line, 0, 12, &options)),
+            vec![0..10, 10..22, 22..26]
+        );
+        // `ー` and small kana do not start a row
+        let line = Line::from("コピーをファイルへ");
+        assert_eq!(
+            cols(&wrap_line(&line, 0, 4, &options)),
+            vec![0..2, 2..6, 6..8, 8..12, 12..16, 16..18]
+        );
+        let line = Line::from("これは、テストです。");
+        assert_eq!(
+            cols(&wrap_line(&line, 0, 6, &options)),
+            vec![0..4, 4..10, 10..16, 16..20]
+        );
+        // the row is broken as usual when nothing can move
+        let line = Line::from("「「「「");
+        assert_eq!(cols(&wrap_line(&line, 0, 4, &options)), vec![0..4, 4..8]);
+    }
+}
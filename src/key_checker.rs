@@ -22,7 +22,7 @@ async fn print_events() {
     let mut timer_active = false;
 
     loop {
-        let mut delay = Delay::new(Duration::from_millis(1_000)).fuse();
+        let mut delay = Delay::new(Duration::from_secs(1)).fuse();
         let mut event = reader.next().fuse();
 
         futures::select! {